@@ -1,6 +1,7 @@
 use failure_derive::Fail;
 
 #[derive(Debug, Fail)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     #[fail(display = "Parser error: {}", _0)]
     ParserError(#[cause] ferrous_chloride::Error),
@@ -8,6 +9,12 @@ pub enum Error {
     IOError(#[cause] std::io::Error),
     #[fail(display = "Unknown command: {}", _0)]
     UnknownCommand(String),
+    #[fail(display = "Invalid query {}", _0)]
+    InvalidQuery(String),
+    #[fail(display = "Query did not match {}", _0)]
+    QueryNotFound(String),
+    #[fail(display = "JSON Error: {}", _0)]
+    JsonError(#[cause] serde_json::Error),
 }
 
 impl From<ferrous_chloride::Error> for Error {
@@ -21,3 +28,9 @@ impl From<std::io::Error> for Error {
         Error::IOError(e)
     }
 }
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::JsonError(e)
+    }
+}
@@ -1,5 +1,10 @@
 mod error;
+mod output;
 
+use ferrous_chloride::diff::diff;
+use ferrous_chloride::parser::lexer::tokenize;
+use ferrous_chloride::parser::read_document;
+use ferrous_chloride::select::Selector;
 use ferrous_chloride::value::from_reader;
 use ferrous_chloride::MergeBehaviour;
 use std::fs::File;
@@ -25,6 +30,9 @@ fn main() -> Result<(), Error> {
 fn run_subcommand(args: &ArgMatches) -> Result<(), Error> {
     match args.subcommand() {
         ("parse", Some(args)) => run_parse(args),
+        ("get", Some(args)) => run_get(args),
+        ("diff", Some(args)) => run_diff(args),
+        ("tokens", Some(args)) => run_tokens(args),
         (unknown, _) => Err(Error::UnknownCommand(unknown.to_string())),
     }
 }
@@ -59,6 +67,97 @@ fn run_parse(args: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
+fn run_get(args: &ArgMatches) -> Result<(), Error> {
+    let path = args
+        .value_of("path")
+        .expect("Required argument is provided");
+    let input = args
+        .value_of("input")
+        .expect("Required argument is provided");
+    let output = args
+        .value_of("output")
+        .expect("Required argument is provided");
+    let format = args
+        .value_of("format")
+        .expect("Required argument is provided");
+
+    let selector: Selector = path
+        .parse()
+        .map_err(|e: ferrous_chloride::Error| Error::InvalidQuery(e.to_string()))?;
+    let body = read_document(input_reader(input)?)?;
+    let nodes = selector.select(body.body());
+    if nodes.is_empty() {
+        return Err(Error::QueryNotFound(selector.to_string()));
+    }
+
+    let formatted = match format {
+        "json" => format!(
+            "{}\n",
+            serde_json::to_string_pretty(&output::nodes_to_json(&nodes))?
+        ),
+        _ => output::nodes_to_hcl(&nodes),
+    };
+
+    // Write
+    {
+        let mut output = output_writer(output)?;
+        output.write_all(formatted.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn run_diff(args: &ArgMatches) -> Result<(), Error> {
+    let old = args.value_of("old").expect("Required argument is provided");
+    let new = args.value_of("new").expect("Required argument is provided");
+    let output = args
+        .value_of("output")
+        .expect("Required argument is provided");
+
+    let old = read_document(input_reader(old)?)?;
+    let new = read_document(input_reader(new)?)?;
+    let formatted = if args.is_present("json") {
+        format!(
+            "{}\n",
+            serde_json::to_string_pretty(&output::json_patch(old.body(), new.body()))?
+        )
+    } else {
+        output::differences_to_report(&diff(old.body(), new.body()))
+    };
+
+    // Write
+    {
+        let mut output = output_writer(output)?;
+        output.write_all(formatted.as_bytes())?;
+    }
+
+    Ok(())
+}
+
+fn run_tokens(args: &ArgMatches) -> Result<(), Error> {
+    let input = args
+        .value_of("input")
+        .expect("Required argument is provided");
+    let output = args
+        .value_of("output")
+        .expect("Required argument is provided");
+
+    let mut source = String::new();
+    input_reader(input)?.read_to_string(&mut source)?;
+
+    let formatted: String = tokenize(&source)
+        .map(|token| format!("{:?} {:?} {:?}\n", token.range(), token.kind, token.text))
+        .collect();
+
+    // Write
+    {
+        let mut output = output_writer(output)?;
+        output.write_all(formatted.as_bytes())?;
+    }
+
+    Ok(())
+}
+
 /// Gets a `Read` depending on the path. If the path is `-`, read from STDIN
 fn input_reader(path: &str) -> Result<Box<dyn Read>, Error> {
     match path {
@@ -118,6 +217,128 @@ where
                 .default_value("-"),
         );
 
+    let get = SubCommand::with_name("get")
+        .about("Select values from a HCL file using a path and print them")
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .short("f")
+                .help("Output format of the selected values")
+                .takes_value(true)
+                .possible_values(&["hcl", "json"])
+                .default_value("hcl"),
+        )
+        .arg(
+            Arg::with_name("path")
+                .index(1)
+                .help(
+                    "Path to the value to select, such as \
+                     `resource.\"security/group\".foobar.allow[0].cidrs`. \
+                     Names select attributes, blocks, block labels or object keys. \
+                     Indices select tuple elements or one of multiple blocks. \
+                     `*` and `[*]` select everything at a level and `..` selects at any depth.",
+                )
+                .takes_value(true)
+                .value_name("path")
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("input")
+                .index(2)
+                .help(
+                    "Specifies the path to read the HCL from. \
+                     Use - to refer to STDIN",
+                )
+                .takes_value(true)
+                .value_name("input_path")
+                .empty_values(false)
+                .default_value("-"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .index(3)
+                .help(
+                    "Specifies the path to write the selected values to. \
+                     Use - to refer to STDOUT",
+                )
+                .takes_value(true)
+                .value_name("output_path")
+                .empty_values(false)
+                .default_value("-"),
+        );
+
+    let diff = SubCommand::with_name("diff")
+        .about("Compare the structure of two HCL files and print the differences")
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .help("Print the differences as a JSON Patch instead of a report")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("old")
+                .index(1)
+                .help(
+                    "Specifies the path to read the old HCL from. \
+                     Use - to refer to STDIN",
+                )
+                .takes_value(true)
+                .value_name("old_path")
+                .empty_values(false)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("new")
+                .index(2)
+                .help(
+                    "Specifies the path to read the new HCL from. \
+                     Use - to refer to STDIN",
+                )
+                .takes_value(true)
+                .value_name("new_path")
+                .empty_values(false)
+                .required(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .index(3)
+                .help(
+                    "Specifies the path to write the differences to. \
+                     Use - to refer to STDOUT",
+                )
+                .takes_value(true)
+                .value_name("output_path")
+                .empty_values(false)
+                .default_value("-"),
+        );
+
+    let tokens = SubCommand::with_name("tokens")
+        .about("Split a HCL file into tokens and print their byte ranges, kinds and text")
+        .arg(
+            Arg::with_name("input")
+                .index(1)
+                .help(
+                    "Specifies the path to read the HCL from. \
+                     Use - to refer to STDIN",
+                )
+                .takes_value(true)
+                .value_name("input_path")
+                .empty_values(false)
+                .default_value("-"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .index(2)
+                .help(
+                    "Specifies the path to write the tokens to. \
+                     Use - to refer to STDOUT",
+                )
+                .takes_value(true)
+                .value_name("output_path")
+                .empty_values(false)
+                .default_value("-"),
+        );
+
     App::new(crate_name!())
         .version(crate_version!())
         .author(crate_authors!())
@@ -128,4 +349,7 @@ where
         .global_setting(AppSettings::NextLineHelp)
         .about("HCL Parser")
         .subcommand(parse)
+        .subcommand(get)
+        .subcommand(diff)
+        .subcommand(tokens)
 }
@@ -0,0 +1,359 @@
+//! Output formats for parsed HCL
+use ferrous_chloride::diff::{Change, Difference};
+use ferrous_chloride::format;
+use ferrous_chloride::parser::block::{Block, BlockBody, BlockLabel};
+use ferrous_chloride::parser::body::{Body, BodyElement};
+use ferrous_chloride::parser::expression::Expression;
+use ferrous_chloride::parser::object::ObjectElementIdentifier;
+use ferrous_chloride::parser::string::TemplatePart;
+use ferrous_chloride::select::Node;
+use serde_json::{Map, Number, Value};
+
+/// Format selected nodes as HCL
+pub fn nodes_to_hcl(nodes: &[Node]) -> String {
+    let mut output = String::new();
+    for node in nodes {
+        match node {
+            Node::Body(body) => output.push_str(&format::body(body)),
+            Node::Block(block) => output.push_str(&format!("{}\n", block)),
+            Node::Expression(expr) => output.push_str(&format!("{}\n", expr)),
+        }
+    }
+    output
+}
+
+/// Convert selected nodes into JSON
+///
+/// A single node is converted as is, while multiple nodes are converted into an array.
+pub fn nodes_to_json(nodes: &[Node]) -> Value {
+    match nodes {
+        [node] => node_to_json(node),
+        nodes => Value::Array(nodes.iter().map(node_to_json).collect()),
+    }
+}
+
+fn node_to_json(node: &Node) -> Value {
+    match node {
+        Node::Body(body) => body_to_json(body),
+        Node::Block(block) => block_to_json(block),
+        Node::Expression(expr) => expression_to_json(expr),
+    }
+}
+
+/// Convert a block into a JSON object, nested by its type and labels
+pub fn block_to_json(block: &Block) -> Value {
+    let mut value = body_to_json(&block.body);
+    for key in block
+        .labels
+        .iter()
+        .rev()
+        .map(BlockLabel::as_str)
+        .chain(std::iter::once(block.r#type.as_ref()))
+    {
+        let mut map = Map::new();
+        map.insert(key.to_string(), value);
+        value = Value::Object(map);
+    }
+    value
+}
+
+/// Format a node as HCL, without a trailing newline
+fn node_to_hcl(node: &Node) -> String {
+    match node {
+        Node::Body(body) => format::body(body).trim_end().to_string(),
+        Node::Block(block) => block.to_string(),
+        Node::Expression(expr) => expr.to_string(),
+    }
+}
+
+/// Format differences as a human readable report
+///
+/// Each difference starts with a line containing `+` (added), `-` (removed) or `~` (modified)
+/// and the path, followed by the old and new values.
+pub fn differences_to_report(differences: &[Difference]) -> String {
+    let mut output = String::new();
+    let write_node = |output: &mut String, sign: char, node: &Node| {
+        for line in node_to_hcl(node).lines() {
+            output.push_str(&format!("  {} {}\n", sign, line));
+        }
+    };
+
+    for difference in differences {
+        let sign = match difference.change {
+            Change::Added(_) => '+',
+            Change::Removed(_) => '-',
+            Change::Modified(_, _) => '~',
+        };
+        output.push_str(&format!("{} {}\n", sign, difference.path));
+        if let Some(old) = difference.change.old_node() {
+            write_node(&mut output, '-', &old);
+        }
+        if let Some(new) = difference.change.new_node() {
+            write_node(&mut output, '+', &new);
+        }
+    }
+    output
+}
+
+/// Compute a [JSON Patch](https://tools.ietf.org/html/rfc6902) from the old body to the new one
+///
+/// The patch turns the JSON produced by `body_to_json` for the old body into the JSON for the
+/// new body when its operations are applied in order. It is computed from the JSON rather than
+/// from the differences between the bodies, so that the paths follow the shape of the JSON: a
+/// single block is an object rather than an array, blocks without labels next to blocks with
+/// labels are under an empty key, and only the last of the attributes with the same name is
+/// kept.
+pub fn json_patch(old: &Body, new: &Body) -> Value {
+    let mut operations = vec![];
+    diff_json(
+        &body_to_json(old),
+        &body_to_json(new),
+        &mut String::new(),
+        &mut operations,
+    );
+    Value::Array(operations)
+}
+
+/// A JSON Patch operation on the value at `pointer`
+fn patch_operation(op: &str, pointer: &str, value: Option<&Value>) -> Value {
+    let mut operation = Map::new();
+    operation.insert("op".to_string(), Value::String(op.to_string()));
+    operation.insert("path".to_string(), Value::String(pointer.to_string()));
+    if let Some(value) = value {
+        operation.insert("value".to_string(), value.clone());
+    }
+    Value::Object(operation)
+}
+
+/// Append a reference token to a [JSON Pointer](https://tools.ietf.org/html/rfc6901)
+fn push_reference_token(pointer: &mut String, token: &str) {
+    pointer.push('/');
+    pointer.push_str(&token.replace('~', "~0").replace('/', "~1"));
+}
+
+/// Push the operations which turn `old` into `new`, the values at `pointer`
+///
+/// Added keys and elements are added with their whole value, so their parents always exist.
+/// Elements are removed from the end of arrays first, so that the indices of the elements which
+/// are still to be removed do not change.
+fn diff_json(old: &Value, new: &Value, pointer: &mut String, operations: &mut Vec<Value>) {
+    let len = pointer.len();
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, old_value) in old {
+                push_reference_token(pointer, key);
+                match new.get(key) {
+                    Some(new_value) => diff_json(old_value, new_value, pointer, operations),
+                    None => operations.push(patch_operation("remove", pointer, None)),
+                }
+                pointer.truncate(len);
+            }
+            for (key, new_value) in new {
+                if !old.contains_key(key) {
+                    push_reference_token(pointer, key);
+                    operations.push(patch_operation("add", pointer, Some(new_value)));
+                    pointer.truncate(len);
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            let common = std::cmp::min(old.len(), new.len());
+            for (index, (old_value, new_value)) in old.iter().zip(new).enumerate() {
+                push_reference_token(pointer, &index.to_string());
+                diff_json(old_value, new_value, pointer, operations);
+                pointer.truncate(len);
+            }
+            for index in (common..old.len()).rev() {
+                push_reference_token(pointer, &index.to_string());
+                operations.push(patch_operation("remove", pointer, None));
+                pointer.truncate(len);
+            }
+            for (index, new_value) in new.iter().enumerate().skip(common) {
+                push_reference_token(pointer, &index.to_string());
+                operations.push(patch_operation("add", pointer, Some(new_value)));
+                pointer.truncate(len);
+            }
+        }
+        (old, new) => {
+            if old != new {
+                operations.push(patch_operation("replace", pointer, Some(new)));
+            }
+        }
+    }
+}
+
+/// Convert an expression into JSON
+pub fn expression_to_json(expr: &Expression) -> Value {
+    match expr {
+        Expression::Null => Value::Null,
+        Expression::Boolean(boolean) => Value::Bool(*boolean),
+        Expression::String(string) => Value::String(string.to_string()),
+        Expression::Number(number) => {
+            let json_number = if let Ok(integer) = number.as_u64() {
+                Some(Number::from(integer))
+            } else if let Ok(integer) = number.as_i64() {
+                Some(Number::from(integer))
+            } else if number.is_float() {
+                number.as_f64().ok().and_then(Number::from_f64)
+            } else {
+                None
+            };
+            // Numbers that do not fit are written out as strings to preserve them
+            json_number
+                .map(Value::Number)
+                .unwrap_or_else(|| Value::String(number.to_string()))
+        }
+        Expression::Tuple(tuple) => Value::Array(tuple.iter().map(expression_to_json).collect()),
+        Expression::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, expr)| {
+                    let key = match key {
+                        ObjectElementIdentifier::Identifier(ident) => ident.to_string(),
+                        ObjectElementIdentifier::Expression(expr) => expr.to_string(),
+                    };
+                    (key, expression_to_json(expr))
+                })
+                .collect(),
+        ),
+        // Expressions that have to be evaluated are written as templates, like in the HCL JSON
+        // syntax
+        Expression::Template(template) => Value::String(
+            template
+                .iter()
+                .map(|part| match part {
+                    TemplatePart::Literal(literal) => literal.replace("${", "$${"),
+                    TemplatePart::Interpolation(expr) => format!("${{{}}}", expr),
+                })
+                .collect(),
+        ),
+        expr => Value::String(format!("${{{}}}", expr)),
+    }
+}
+
+/// Convert a body into a JSON object
+///
+/// Blocks are nested as objects keyed by their type and then their labels, similar to the
+/// HCL JSON syntax. Multiple blocks with the same type and labels are written as an array.
+pub fn body_to_json(body: &Body) -> Value {
+    let mut map = Map::new();
+    let mut blocks: Vec<(&str, BlockBody)> = vec![];
+
+    for element in body {
+        match element {
+            BodyElement::Attribute((ident, expr)) => {
+                map.insert(ident.to_string(), expression_to_json(expr));
+            }
+            BodyElement::Block(block) => {
+                let block_type: &str = &block.r#type;
+                let position = blocks.iter().position(|(t, _)| *t == block_type);
+                let index = match position {
+                    Some(index) => index,
+                    None => {
+                        blocks.push((block_type, BlockBody::new()));
+                        blocks.len() - 1
+                    }
+                };
+                blocks[index]
+                    .1
+                    .append(block.labels.clone(), block.body.clone());
+            }
+        }
+    }
+
+    for (block_type, block_body) in blocks {
+        map.insert(block_type.to_string(), block_body_to_json(&block_body));
+    }
+    Value::Object(map)
+}
+
+fn block_body_to_json(blocks: &BlockBody) -> Value {
+    let bodies = |bodies: &[Body]| -> Value {
+        if bodies.len() == 1 {
+            body_to_json(&bodies[0])
+        } else {
+            Value::Array(bodies.iter().map(body_to_json).collect())
+        }
+    };
+
+    match blocks.get_labels() {
+        None => bodies(blocks.get_empty()),
+        Some(labels) => {
+            let mut map: Map<String, Value> = labels
+                .iter()
+                .map(|(label, nested)| (label.as_str().to_string(), block_body_to_json(nested)))
+                .collect();
+            if !blocks.get_empty().is_empty() {
+                // Blocks without further labels are placed under an empty key
+                map.insert(String::new(), bodies(blocks.get_empty()));
+            }
+            Value::Object(map)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ferrous_chloride::parse_str;
+
+    #[test]
+    fn json_patches_turn_the_old_json_into_the_new_json() {
+        let block = include_str!("../../lib/fixtures/block.hcl");
+        let cases = [
+            // Elements are removed from the end first
+            ("a = [1, 2, 3]\n", "a = [1]\n"),
+            ("a = [1, [2, 3], 4]\n", "a = [[2], 5]\n"),
+            // Blocks with a new type or new labels
+            ("", "resource \"new\" \"z\" {\n  a = 1\n}\n"),
+            (
+                "resource \"old\" \"y\" {}\n",
+                "resource \"old\" \"y\" {}\nresource \"new\" \"z\" {}\n",
+            ),
+            // A single block is an object, and multiple blocks are an array
+            ("x {\n  a = 1\n}\n", "x {\n  a = 1\n}\nx {\n  a = 2\n}\n"),
+            ("x {\n  a = 1\n}\nx {\n  a = 2\n}\n", "x {\n  a = 2\n}\n"),
+            // Blocks without labels next to blocks with labels are under an empty key
+            (
+                "x \"y\" {}\nx {\n  a = 1\n}\n",
+                "x \"y\" {}\nx {\n  a = 2\n}\n",
+            ),
+            ("x {\n  a = 1\n}\n", "x \"y\" {}\nx {\n  a = 1\n}\n"),
+            // Only the last attribute with the same name is kept
+            ("b = 2\nb = 3\n", "b = 2\n"),
+            // Names with characters that have to be escaped in pointers
+            (
+                "x \"a/b\" {\n  c = { \"d~e\" = 1 }\n}\n",
+                "x \"a/b\" {\n  c = { \"d~e\" = 2 }\n}\n",
+            ),
+            (block, ""),
+            ("", block),
+            (block, "simple_map {\n  foo = \"baz\"\n}\n"),
+        ];
+
+        for (old, new) in cases.iter() {
+            let old = parse_str(old).unwrap();
+            let new = parse_str(new).unwrap();
+            let patch: json_patch::Patch = serde_json::from_value(json_patch(&old, &new)).unwrap();
+
+            let mut patched = body_to_json(&old);
+            json_patch::patch(&mut patched, &patch).unwrap();
+            assert_eq!(patched, body_to_json(&new), "{:?}", patch);
+        }
+    }
+
+    #[test]
+    fn json_patches_remove_elements_from_the_end_first() {
+        let old = parse_str("a = [1, 2, 3]\n").unwrap();
+        let new = parse_str("a = [1]\n").unwrap();
+        let paths: Vec<_> = match json_patch(&old, &new) {
+            Value::Array(operations) => operations
+                .iter()
+                .map(|operation| operation["path"].as_str().unwrap().to_string())
+                .collect(),
+            patch => panic!("unexpected {}", patch),
+        };
+        assert_eq!(paths, ["/a/2", "/a/1"]);
+    }
+}
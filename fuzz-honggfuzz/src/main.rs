@@ -1,14 +1,86 @@
 #[cfg(not(target_os = "windows"))]
 mod non_windows {
-    use ferrous_chloride::parse_str;
+    use ferrous_chloride::parser::body::LazyBodyElement;
+    use ferrous_chloride::parser::events::EventReader;
+    use ferrous_chloride::parser::incremental::{Edit, Tree};
+    use ferrous_chloride::parser::{parse_str_lazy, Body, Expression};
+    use ferrous_chloride::schema::{AttributeSchema, BlockSchema, BodySchema, Nesting, Type};
+    use ferrous_chloride::select::Selector;
+    use ferrous_chloride::serde::RawExpression;
+    use ferrous_chloride::{from_str, parse_str, value, MergeBehaviour, Value};
     use honggfuzz::fuzz;
 
+    /// Parse and deserialize the input in every way the library supports
+    ///
+    /// Invalid input must be rejected with an error, so any panic is a crash.
+    fn parse_and_deserialize(s: &str) {
+        let schema = BodySchema::new()
+            .attribute(AttributeSchema::optional("name", Type::String))
+            .block(
+                BlockSchema::new("resource", Nesting::Map)
+                    .labels(&["type", "name"])
+                    .body(
+                        BodySchema::new()
+                            .attribute(AttributeSchema::required("count", Type::Number)),
+                    ),
+            );
+        let recursive: Selector = "..*".parse().expect("to be a valid selector");
+
+        if let Ok(body) = parse_str(s) {
+            let _ = ferrous_chloride::format::body(&body);
+            let _ = schema.decode(&body);
+            let _ = recursive.select(&body);
+        }
+        let _ = schema.validate(s);
+        if let Ok(selector) = s.parse::<Selector>() {
+            let _ = selector.to_string();
+            if let Ok(body) = parse_str("a = [1, { b = 2 }]\nc \"d\" { e = 3 }\n") {
+                let _ = selector.select(&body);
+            }
+        }
+
+        let _ = Expression::parse(s);
+        let _ = RawExpression::new(s);
+
+        // Insert the input in the middle of itself, and then remove it again
+        if let Ok(mut tree) = Tree::parse(s) {
+            let mut middle = s.len() / 2;
+            while !s.is_char_boundary(middle) {
+                middle -= 1;
+            }
+            if tree.edit(&Edit::new(middle..middle, s)).is_ok() {
+                let _ = tree.edit(&Edit::new(middle..middle + s.len(), ""));
+            }
+            let _ = tree.to_body();
+        }
+
+        if let Ok(body) = parse_str_lazy(s) {
+            for element in &body {
+                if let LazyBodyElement::Block(block) = element {
+                    let _ = block.body();
+                }
+            }
+        }
+        for _ in EventReader::new(s.as_bytes()) {}
+
+        let _ = from_str::<Body>(s);
+        let _ = from_str::<Value>(s);
+        for merge in &[
+            None,
+            Some(MergeBehaviour::Error),
+            Some(MergeBehaviour::TakeFirst),
+            Some(MergeBehaviour::TakeLast),
+        ] {
+            let _ = value::from_str(s, *merge);
+        }
+    }
+
     #[rustfmt::skip]
     pub fn main() {
         loop {
             fuzz!(|data: &[u8]| {
                 if let Ok(s) = std::str::from_utf8(data) {
-                    let _ = parse_str(s);
+                    parse_and_deserialize(s);
                 }
             });
         }
@@ -0,0 +1,63 @@
+//! Compare the nom parser with the hand-written parser behind `parse_str`
+//!
+//! Run with `cargo bench -p ferrous-chloride --bench parse`.
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use nom::types::CompleteStr;
+
+use ferrous_chloride::parser;
+
+static FIXTURES: &[(&str, &str)] = &[
+    ("block", include_str!("../fixtures/block.hcl")),
+    ("list", include_str!("../fixtures/list.hcl")),
+    (
+        "no_newline_terminating",
+        include_str!("../fixtures/no_newline_terminating.hcl"),
+    ),
+    ("scalar", include_str!("../fixtures/scalar.hcl")),
+    ("simple_block", include_str!("../fixtures/simple_block.hcl")),
+    ("single", include_str!("../fixtures/single.hcl")),
+    ("strings", include_str!("../fixtures/strings.hcl")),
+];
+
+/// A larger document made of all the fixtures repeated `times` times
+fn repeated_fixtures(times: usize) -> String {
+    let mut document = String::new();
+    for _ in 0..times {
+        for (_, fixture) in FIXTURES {
+            document.push_str(fixture);
+            document.push('\n');
+        }
+    }
+    document
+}
+
+fn parse(c: &mut Criterion) {
+    let large = repeated_fixtures(100);
+    let inputs = FIXTURES
+        .iter()
+        .map(|(name, input)| (*name, *input))
+        .chain(std::iter::once(("all_fixtures_100_times", large.as_str())));
+
+    let mut group = c.benchmark_group("parse");
+    for (name, input) in inputs {
+        // Both parsers have to accept the input for the comparison to mean anything
+        let (remaining, nom_parsed) = parser::config_file(CompleteStr(input)).unwrap();
+        assert!(remaining.is_empty(), "{} was not consumed", name);
+        assert_eq!(parser::parse_str(input).unwrap(), nom_parsed, "{}", name);
+
+        group.throughput(Throughput::Bytes(input.len() as u64));
+        group.bench_with_input(BenchmarkId::new("nom", name), input, |b, input| {
+            b.iter(|| parser::config_file(CompleteStr(input)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("descent", name), input, |b, input| {
+            b.iter(|| parser::parse_str(input).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("lazy", name), input, |b, input| {
+            b.iter(|| parser::parse_str_lazy(input).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -8,9 +8,20 @@ pub static LIST: &str = "List";
 pub static TUPLE: &str = "Tuple";
 pub static OBJECT: &str = "Object";
 pub static BLOCK: &str = "Block";
+pub static TEMPLATE: &str = "Template";
+pub static VARIABLE: &str = "Variable";
+pub static GET_ATTR: &str = "GetAttr";
+pub static INDEX: &str = "Index";
+pub static FUNCTION_CALL: &str = "FunctionCall";
+pub static OPERATION: &str = "Operation";
+pub static CONDITIONAL: &str = "Conditional";
 
 pub static MERGED: &str = "Merged";
 pub static UNMERGED: &str = "UNMERGED";
+pub static ONE: &str = "One";
+pub static MANY: &str = "Many";
 
 pub static VALUE: &str = "Value";
 pub static MAP_VALUES: &str = "MapValues";
+pub static ONE_OR_MANY: &str = "OneOrMany";
+pub static KEY_VALUE_PAIRS: &str = "KeyValuePairs";
@@ -0,0 +1,425 @@
+//! Structural differences between two parsed HCL documents
+//!
+//! Unlike a textual diff, formatting and comments are ignored. Attributes and object elements are
+//! matched by their names, blocks are matched by their type and labels, and tuples are compared
+//! element by element.
+//!
+//! The path of each difference is a [`Selector`](crate::select::Selector) that selects the
+//! changed node. When there are multiple blocks with the same type and labels, they are matched
+//! in the order they appear and the path contains the index of the block. Attributes with the
+//! same name are matched in the order they appear as well.
+//!
+//! ```rust
+//! use ferrous_chloride::diff::{diff, Change};
+//! use ferrous_chloride::parse_str;
+//!
+//! let old = parse_str(r#"
+//! resource "instance" "foo" {
+//!   image = "ubuntu:16.04"
+//! }
+//! "#).unwrap();
+//! let new = parse_str(r#"
+//! resource "instance" "foo" {
+//!   image = "ubuntu:18.04"
+//! }
+//! "#).unwrap();
+//!
+//! let differences = diff(&old, &new);
+//! assert_eq!(differences.len(), 1);
+//! assert_eq!(differences[0].path.to_string(), "resource.instance.foo.image");
+//! match differences[0].change {
+//!     Change::Modified(_, _) => {}
+//!     _ => panic!("Unexpected change"),
+//! }
+//! ```
+use crate::parser::block::{Block, BlockLabel};
+use crate::parser::body::{Body, BodyElement};
+use crate::parser::expression::Expression;
+use crate::parser::object::ObjectElementIdentifier;
+use crate::select::{Node, Segment, Selector};
+
+/// A difference between two documents
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference<'s, 'a> {
+    /// Path to the node that was changed
+    pub path: Selector,
+    pub change: Change<'s, 'a>,
+}
+
+/// The kind of change made to a node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change<'s, 'a> {
+    /// The node only exists in the new document
+    Added(Node<'s, 'a>),
+    /// The node only exists in the old document
+    Removed(Node<'s, 'a>),
+    /// The node exists in both documents with different values. Old value first.
+    Modified(Node<'s, 'a>, Node<'s, 'a>),
+}
+
+impl<'s, 'a> Change<'s, 'a> {
+    /// The node in the old document, if any
+    pub fn old_node(&self) -> Option<Node<'s, 'a>> {
+        match self {
+            Change::Added(_) => None,
+            Change::Removed(old) | Change::Modified(old, _) => Some(*old),
+        }
+    }
+
+    /// The node in the new document, if any
+    pub fn new_node(&self) -> Option<Node<'s, 'a>> {
+        match self {
+            Change::Removed(_) => None,
+            Change::Added(new) | Change::Modified(_, new) => Some(*new),
+        }
+    }
+}
+
+/// Compute the differences from the `old` body to the `new` body, in document order
+pub fn diff<'s, 'a>(old: &'s Body<'a>, new: &'s Body<'a>) -> Vec<Difference<'s, 'a>> {
+    let mut differences = vec![];
+    diff_bodies(old, new, &mut vec![], &mut differences);
+    differences
+}
+
+/// Blocks of a body grouped by their type and labels, in the order they first appear
+type BlockIndex<'s, 'a> = Vec<((&'s str, &'s [BlockLabel<'a>]), Vec<&'s Block<'a>>)>;
+
+fn index_blocks<'s, 'a>(body: &'s Body<'a>) -> BlockIndex<'s, 'a> {
+    let mut index: BlockIndex = vec![];
+    for element in body {
+        if let BodyElement::Block(block) = element {
+            let key = (block.r#type.as_ref(), block.labels.as_slice());
+            match index.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, blocks)) => blocks.push(block),
+                None => index.push((key, vec![block])),
+            }
+        }
+    }
+    index
+}
+
+/// Attributes of a body grouped by their name, in the order they first appear
+type AttributeIndex<'s, 'a> = Vec<(&'s str, Vec<&'s Expression<'a>>)>;
+
+fn index_attributes<'s, 'a>(body: &'s Body<'a>) -> AttributeIndex<'s, 'a> {
+    let mut index: AttributeIndex = vec![];
+    for element in body {
+        if let BodyElement::Attribute((ident, expr)) = element {
+            match index.iter_mut().find(|(name, _)| *name == ident) {
+                Some((_, exprs)) => exprs.push(expr),
+                None => index.push((ident, vec![expr])),
+            }
+        }
+    }
+    index
+}
+
+fn push<'s, 'a>(
+    path: &[Segment],
+    change: Change<'s, 'a>,
+    differences: &mut Vec<Difference<'s, 'a>>,
+) {
+    differences.push(Difference {
+        path: Selector::new(path.to_vec()),
+        change,
+    });
+}
+
+fn diff_bodies<'s, 'a>(
+    old: &'s Body<'a>,
+    new: &'s Body<'a>,
+    path: &mut Vec<Segment>,
+    differences: &mut Vec<Difference<'s, 'a>>,
+) {
+    let old_attributes = index_attributes(old);
+    let new_attributes = index_attributes(new);
+
+    for (name, old_group) in &old_attributes {
+        let new_group = new_attributes
+            .iter()
+            .find(|(new_name, _)| new_name == name)
+            .map(|(_, group)| group.as_slice())
+            .unwrap_or(&[]);
+        diff_attribute_groups(name, old_group, new_group, path, differences);
+    }
+    for (name, new_group) in &new_attributes {
+        if old_attributes.iter().all(|(old_name, _)| old_name != name) {
+            diff_attribute_groups(name, &[], new_group, path, differences);
+        }
+    }
+
+    let old_blocks = index_blocks(old);
+    let new_blocks = index_blocks(new);
+    let empty = vec![];
+
+    for (key, old_group) in &old_blocks {
+        let new_group = new_blocks
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, group)| group)
+            .unwrap_or(&empty);
+        diff_block_groups(key, old_group, new_group, path, differences);
+    }
+    for (key, new_group) in &new_blocks {
+        if old_blocks.iter().all(|(k, _)| k != key) {
+            diff_block_groups(key, &empty, new_group, path, differences);
+        }
+    }
+}
+
+/// Compare attributes with the same name, pairing them up in order
+fn diff_attribute_groups<'s, 'a>(
+    name: &str,
+    old: &[&'s Expression<'a>],
+    new: &[&'s Expression<'a>],
+    path: &mut Vec<Segment>,
+    differences: &mut Vec<Difference<'s, 'a>>,
+) {
+    path.push(Segment::Name(name.to_string()));
+    for i in 0..std::cmp::max(old.len(), new.len()) {
+        match (old.get(i), new.get(i)) {
+            (Some(old), Some(new)) => diff_expressions(old, new, path, differences),
+            (Some(old), None) => push(path, Change::Removed(Node::Expression(old)), differences),
+            (None, Some(new)) => push(path, Change::Added(Node::Expression(new)), differences),
+            (None, None) => unreachable!("index is less than the length of one of the groups"),
+        }
+    }
+    path.pop();
+}
+
+/// Compare blocks with the same type and labels, pairing them up in order
+fn diff_block_groups<'s, 'a>(
+    (block_type, labels): &(&'s str, &'s [BlockLabel<'a>]),
+    old: &[&'s Block<'a>],
+    new: &[&'s Block<'a>],
+    path: &mut Vec<Segment>,
+    differences: &mut Vec<Difference<'s, 'a>>,
+) {
+    let depth = path.len();
+    path.push(Segment::Name(block_type.to_string()));
+    path.extend(
+        labels
+            .iter()
+            .map(|label| Segment::Name(label.as_str().to_string())),
+    );
+    let indexed = old.len() > 1 || new.len() > 1;
+
+    for i in 0..std::cmp::max(old.len(), new.len()) {
+        if indexed {
+            path.push(Segment::Index(i));
+        }
+        match (old.get(i), new.get(i)) {
+            (Some(old), Some(new)) => diff_bodies(&old.body, &new.body, path, differences),
+            (Some(old), None) => push(path, Change::Removed(Node::Block(old)), differences),
+            (None, Some(new)) => push(path, Change::Added(Node::Block(new)), differences),
+            (None, None) => unreachable!("index is less than the length of one of the groups"),
+        }
+        if indexed {
+            path.pop();
+        }
+    }
+
+    path.truncate(depth);
+}
+
+fn object_key<'s>(key: &'s ObjectElementIdentifier) -> &'s str {
+    match key {
+        ObjectElementIdentifier::Identifier(ident) => ident,
+        ObjectElementIdentifier::Expression(expr) => expr,
+    }
+}
+
+fn diff_expressions<'s, 'a>(
+    old: &'s Expression<'a>,
+    new: &'s Expression<'a>,
+    path: &mut Vec<Segment>,
+    differences: &mut Vec<Difference<'s, 'a>>,
+) {
+    match (old, new) {
+        (Expression::Tuple(old), Expression::Tuple(new)) => {
+            for i in 0..std::cmp::max(old.len(), new.len()) {
+                path.push(Segment::Index(i));
+                match (old.get(i), new.get(i)) {
+                    (Some(old), Some(new)) => diff_expressions(old, new, path, differences),
+                    (Some(old), None) => {
+                        push(path, Change::Removed(Node::Expression(old)), differences)
+                    }
+                    (None, Some(new)) => {
+                        push(path, Change::Added(Node::Expression(new)), differences)
+                    }
+                    (None, None) => unreachable!("index is less than the length of one tuple"),
+                }
+                path.pop();
+            }
+        }
+        (Expression::Object(old), Expression::Object(new)) => {
+            for (key, old_expr) in old {
+                path.push(Segment::Name(object_key(key).to_string()));
+                match new.iter().find(|(new_key, _)| new_key == key) {
+                    Some((_, new_expr)) => diff_expressions(old_expr, new_expr, path, differences),
+                    None => push(
+                        path,
+                        Change::Removed(Node::Expression(old_expr)),
+                        differences,
+                    ),
+                }
+                path.pop();
+            }
+            for (key, new_expr) in new {
+                if old.iter().all(|(old_key, _)| old_key != key) {
+                    path.push(Segment::Name(object_key(key).to_string()));
+                    push(path, Change::Added(Node::Expression(new_expr)), differences);
+                    path.pop();
+                }
+            }
+        }
+        (old, new) => {
+            if old != new {
+                push(
+                    path,
+                    Change::Modified(Node::Expression(old), Node::Expression(new)),
+                    differences,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixtures;
+    use crate::parser::parse_str;
+
+    /// Summarise differences as `(path, sign)` where sign is one of `+`, `-` or `~`
+    fn summarise(differences: &[Difference]) -> Vec<(String, &'static str)> {
+        differences
+            .iter()
+            .map(|difference| {
+                let sign = match difference.change {
+                    Change::Added(_) => "+",
+                    Change::Removed(_) => "-",
+                    Change::Modified(_, _) => "~",
+                };
+                (difference.path.to_string(), sign)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_documents_have_no_differences() {
+        for hcl in fixtures::ALL {
+            let body = parse_str(hcl).unwrap();
+            assert!(diff(&body, &body).is_empty());
+        }
+    }
+
+    #[test]
+    fn formatting_is_ignored() {
+        let old = parse_str("a = [1, 2]\nb { c = 3 }\n").unwrap();
+        let new = parse_str("b {\n  c = 3.0\n}\n\na = [\n  1,\n  2,\n]\n").unwrap();
+        assert!(diff(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn attribute_changes_are_found() {
+        let old = parse_str("a = 1\nb = \"b\"\nc = [1, 2]\nd = { e = 1 }\n").unwrap();
+        let new = parse_str("b = \"c\"\nc = [1, 3, 4]\nd = { f = 1 }\nz = true\n").unwrap();
+
+        assert_eq!(
+            summarise(&diff(&old, &new)),
+            [
+                ("a".to_string(), "-"),
+                ("b".to_string(), "~"),
+                ("c[1]".to_string(), "~"),
+                ("c[2]".to_string(), "+"),
+                ("d.e".to_string(), "-"),
+                ("d.f".to_string(), "+"),
+                ("z".to_string(), "+"),
+            ]
+        );
+    }
+
+    #[test]
+    fn block_changes_are_found() {
+        let old = parse_str(fixtures::BLOCK).unwrap();
+        let new = parse_str(
+            r#"
+simple_map {
+  foo   = "bar"
+  bar   = "baz"
+  index = 1
+}
+
+resource "security/group" "foobar" {
+  name = "foobar"
+
+  allow {
+    name = "localhost"
+    cidrs = ["127.0.0.1/32"]
+  }
+
+  deny {
+    name = "internet"
+    cidrs = ["0.0.0.0/0"]
+  }
+}
+
+resource "instance" "an_instance" {
+  name = "an_instance"
+  image = "ubuntu:18.10"
+
+  user "test" {
+    root = true
+  }
+}
+
+resource "instance" "another" {
+  name = "another"
+}
+"#,
+        )
+        .unwrap();
+
+        let differences = diff(&old, &new);
+        assert_eq!(
+            summarise(&differences),
+            [
+                ("simple_map[1]".to_string(), "-"),
+                (
+                    r#"resource."security/group".foobar.allow[1]"#.to_string(),
+                    "-"
+                ),
+                (r#"resource."security/group".second"#.to_string(), "-"),
+                ("resource.instance.an_instance.image".to_string(), "~"),
+                ("resource.instance.another".to_string(), "+"),
+            ]
+        );
+
+        // Paths select the changed nodes
+        let removed = differences[1].path.select(&old);
+        assert_eq!(removed, [differences[1].change.old_node().unwrap()]);
+        let modified = differences[3].path.select(&new);
+        assert_eq!(modified, [differences[3].change.new_node().unwrap()]);
+    }
+
+    #[test]
+    fn duplicate_attributes_are_paired_in_order() {
+        let old = parse_str("b = 2\nb = 3\n").unwrap();
+        let new = parse_str("b = 2\n").unwrap();
+        let differences = diff(&old, &new);
+        assert_eq!(summarise(&differences), [("b".to_string(), "-")]);
+        assert_eq!(
+            differences[0].change.old_node(),
+            Some(Node::Expression(&Expression::from(3)))
+        );
+
+        let new = parse_str("b = 2\nb = 4\nb = 5\n").unwrap();
+        assert_eq!(
+            summarise(&diff(&old, &new)),
+            [("b".to_string(), "~"), ("b".to_string(), "+")]
+        );
+    }
+}
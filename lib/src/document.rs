@@ -0,0 +1,256 @@
+//! Parsed documents which own their source
+//!
+//! The parser borrows strings from the input wherever possible, so a parsed body cannot outlive
+//! the text it was parsed from. A [`Document`] keeps the text read from an IO stream together
+//! with the body parsed from it, so that the body does not have to be copied with
+//! [`AsOwned`](crate::AsOwned) before the buffer is dropped.
+//!
+//! ```rust
+//! use ferrous_chloride::parser::body::BodyElement;
+//! use ferrous_chloride::parser::read_document;
+//!
+//! let document = read_document(&b"name = \"foo\"\n"[..]).unwrap();
+//! assert_eq!(document.source(), "name = \"foo\"\n");
+//!
+//! match &document.body()[0] {
+//!     BodyElement::Attribute((name, _)) => assert_eq!(name, "name"),
+//!     element => panic!("unexpected {:?}", element),
+//! }
+//! ```
+use std::fmt;
+use std::mem::ManuallyDrop;
+use std::ptr::NonNull;
+
+use crate::parser::ConfigFile;
+use crate::{value, Error};
+
+/// A parsed body together with the source it borrows from
+///
+/// The body is only handed out with its lifetime tied to a borrow of the document.
+pub struct Document<T> {
+    // SAFETY: `parsed` borrows from `source` with a lifetime made `'static`, which is sound
+    // because:
+    // - `parsed` is dropped before `source` is freed, in `Drop` and `into_source`;
+    // - `source` is private and never mutated or reallocated while `parsed` exists;
+    // - `source` is a raw pointer rather than a `Box`, so moving the document does not assert
+    //   unique access to the text and invalidate the borrows of `parsed`;
+    // - `parsed` is only handed out with its lifetime shortened to a borrow of the document.
+    parsed: ManuallyDrop<T>,
+    /// Text allocated as a `Box<str>`, which is owned by the document
+    source: NonNull<str>,
+}
+
+// SAFETY: The document owns its source like a `Box<str>` would, and only hands out shared
+// references to it.
+unsafe impl<T: Send> Send for Document<T> {}
+unsafe impl<T: Sync> Sync for Document<T> {}
+
+impl<T> Document<T> {
+    /// Read `reader` to the end and parse the text with `parse`
+    ///
+    /// `parse` is handed the text with a `'static` lifetime, but must only borrow from it in the
+    /// value it returns. Accessors for `T` have to shorten the lifetime of the value to that of
+    /// the document.
+    pub(crate) fn read<R, F>(mut reader: R, parse: F) -> Result<Self, Error>
+    where
+        R: std::io::Read,
+        F: FnOnce(&'static str) -> Result<T, Error>,
+    {
+        let mut source = String::new();
+        reader.read_to_string(&mut source)?;
+        // Shrinking the buffer might move the text, so it has to happen before it is borrowed
+        let source = NonNull::from(Box::leak(source.into_boxed_str()));
+
+        // SAFETY: The text is not freed until `parsed` is dropped. See the fields of `Document`.
+        let text: &'static str = unsafe { source.as_ref() };
+        match parse(text) {
+            Ok(parsed) => Ok(Document {
+                parsed: ManuallyDrop::new(parsed),
+                source,
+            }),
+            Err(error) => {
+                // SAFETY: Nothing borrows from the text after parsing failed
+                drop(unsafe { Box::from_raw(source.as_ptr()) });
+                Err(error)
+            }
+        }
+    }
+
+    /// The source of the document
+    pub fn source(&self) -> &str {
+        // SAFETY: The text lives as long as the document
+        unsafe { self.source.as_ref() }
+    }
+
+    /// Drop the parsed body and return the source of the document
+    pub fn into_source(self) -> String {
+        let mut document = ManuallyDrop::new(self);
+        // SAFETY: `document` is not dropped, so `parsed` is dropped and the text is taken back
+        // exactly once, in that order
+        unsafe {
+            ManuallyDrop::drop(&mut document.parsed);
+            Box::from_raw(document.source.as_ptr()).into_string()
+        }
+    }
+}
+
+impl<T> Drop for Document<T> {
+    fn drop(&mut self) {
+        // SAFETY: `parsed` is dropped before the text it borrows from is freed, and neither is
+        // used again
+        unsafe {
+            ManuallyDrop::drop(&mut self.parsed);
+            drop(Box::from_raw(self.source.as_ptr()));
+        }
+    }
+}
+
+impl Document<ConfigFile<'static>> {
+    /// The parsed body
+    pub fn body(&self) -> &ConfigFile<'_> {
+        &self.parsed
+    }
+}
+
+impl Document<value::Body<'static>> {
+    /// The parsed body
+    pub fn body(&self) -> &value::Body<'_> {
+        &self.parsed
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Document<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.parsed.fmt(f)
+    }
+}
+
+impl<T: PartialEq> PartialEq for Document<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.parsed == other.parsed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::borrow::Cow;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::fixtures;
+    use crate::parser::body::BodyElement;
+    use crate::parser::expression::Expression;
+    use crate::MergeBehaviour;
+
+    #[test]
+    fn documents_borrow_from_their_source() {
+        let document = crate::parser::read_document(fixtures::SINGLE.as_bytes()).unwrap();
+        assert_eq!(
+            document.body(),
+            &crate::parse_str(fixtures::SINGLE).unwrap()
+        );
+
+        let source = document.source().as_bytes().as_ptr_range();
+        let strings = document.body().iter().filter_map(|element| match element {
+            BodyElement::Attribute((_, Expression::String(Cow::Borrowed(string)))) => Some(string),
+            _ => None,
+        });
+        let mut count = 0;
+        for string in strings {
+            assert!(source.contains(&string.as_ptr()));
+            count += 1;
+        }
+        assert!(count > 0);
+    }
+
+    #[test]
+    fn value_documents_borrow_from_their_source() {
+        let document =
+            crate::value::read_document(fixtures::SINGLE.as_bytes(), Some(MergeBehaviour::Error))
+                .unwrap();
+        assert_eq!(
+            document.body(),
+            &crate::value::from_str(fixtures::SINGLE, Some(MergeBehaviour::Error)).unwrap()
+        );
+
+        let source = document.source().as_bytes().as_ptr_range();
+        let borrowed = document.body().iter().any(|(key, _)| match key {
+            crate::parser::literals::Key::Identifier(Cow::Borrowed(key)) => {
+                source.contains(&key.as_ptr())
+            }
+            _ => false,
+        });
+        assert!(borrowed);
+    }
+
+    /// Borrows the source of a document, and checks that it is intact when it is dropped
+    ///
+    /// Reading the source after it was freed is caught reliably when the tests are run with
+    /// `cargo miri test`.
+    struct ChecksSourceOnDrop {
+        source: &'static str,
+        intact: Rc<Cell<Option<bool>>>,
+    }
+
+    impl Drop for ChecksSourceOnDrop {
+        fn drop(&mut self) {
+            let intact = self.source.bytes().all(|byte| byte == b'x');
+            self.intact.set(Some(intact));
+        }
+    }
+
+    #[test]
+    fn the_parsed_body_is_dropped_before_the_source() {
+        let intact = Rc::new(Cell::new(None));
+        let text = "x".repeat(256);
+        let document = Document::read(text.as_bytes(), |source| {
+            Ok(ChecksSourceOnDrop {
+                source,
+                intact: Rc::clone(&intact),
+            })
+        })
+        .unwrap();
+        assert_eq!(document.source(), text);
+
+        drop(document);
+        assert_eq!(intact.get(), Some(true));
+    }
+
+    #[test]
+    fn the_source_is_returned_after_dropping_the_parsed_body() {
+        let intact = Rc::new(Cell::new(None));
+        let document = Document::read(&b"xxxx"[..], |source| {
+            Ok(ChecksSourceOnDrop {
+                source,
+                intact: Rc::clone(&intact),
+            })
+        })
+        .unwrap();
+
+        assert_eq!(document.into_source(), "xxxx");
+        assert_eq!(intact.get(), Some(true));
+    }
+
+    #[test]
+    fn documents_can_be_moved_and_sent_to_other_threads() {
+        let document = crate::parser::read_document(fixtures::SINGLE.as_bytes()).unwrap();
+        let documents = vec![document];
+        let document = std::thread::spawn(move || documents.into_iter().next().unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(
+            document.body(),
+            &crate::parse_str(fixtures::SINGLE).unwrap()
+        );
+    }
+
+    #[test]
+    fn documents_which_cannot_be_parsed_free_their_source() {
+        let result = Document::read(&b"xxxx"[..], |_| -> Result<(), _> {
+            Err(Error::InvalidEdit("invalid".to_string()))
+        });
+        assert!(result.is_err());
+    }
+}
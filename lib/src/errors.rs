@@ -5,7 +5,8 @@ use failure_derive::Fail;
 use nom::verbose_errors::Context;
 use nom::ErrorKind;
 
-use crate::OneOrMany;
+use crate::span::Position;
+use crate::{MergeBehaviour, OneOrMany};
 
 /// Error type for parsing
 #[derive(Debug, Fail)]
@@ -16,6 +17,15 @@ pub enum Error {
     #[fail(display = "Invalid Number {}", _0)]
     InvalidNumber(String),
 
+    #[fail(display = "Syntax error at {}: {}", position, message)]
+    SyntaxError { position: Position, message: String },
+
+    #[fail(display = "Limit exceeded at {}: {}", position, message)]
+    LimitExceeded { position: Position, message: String },
+
+    #[fail(display = "Input is larger than the limit of {} bytes", _0)]
+    InputTooLarge(usize),
+
     #[fail(display = "Bytes contain invalid unicode: {:#?}", _0)]
     InvalidUnicode(Vec<u8>),
 
@@ -61,6 +71,36 @@ pub enum Error {
     )]
     UnexpectedRemainingInput(String),
 
+    #[fail(display = "Invalid selector {}", _0)]
+    InvalidSelector(String),
+
+    #[fail(display = "Invalid edit: {}", _0)]
+    InvalidEdit(String),
+
+    #[fail(
+        display = "Object key {} is an expression which is not a string literal",
+        _0
+    )]
+    UnsupportedObjectKey(String),
+
+    #[fail(
+        display = "Merge behaviour {:?} is not supported when parsing into a `Value`",
+        _0
+    )]
+    UnsupportedMergeBehaviour(MergeBehaviour),
+
+    #[fail(display = "Unknown variable {}", _0)]
+    UnknownVariable(String),
+
+    #[fail(display = "Unknown function {}", _0)]
+    UnknownFunction(String),
+
+    #[fail(display = "Error calling function {}: {}", name, message)]
+    FunctionError { name: String, message: String },
+
+    #[fail(display = "Invalid operation: {}", _0)]
+    InvalidOperation(String),
+
     #[fail(
         display = "Possible bug with the library encountered: {}; Please report to \
                    https://github.com/lawliet89/ferrous-chloride/issues",
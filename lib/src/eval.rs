@@ -0,0 +1,578 @@
+//! Evaluation of expressions
+//!
+//! Expressions that refer to variables or call functions are kept as they are parsed. A
+//! [`Context`] provides the variables and functions to evaluate them with:
+//!
+//! ```rust
+//! use ferrous_chloride::eval::Context;
+//! use ferrous_chloride::parser::Expression;
+//!
+//! let context = Context::new()
+//!     .variable("var", Expression::new_object(vec![("base_port", Expression::from(8000))]))
+//!     .function("max", |arguments| {
+//!         arguments
+//!             .into_iter()
+//!             .filter_map(|argument| match argument {
+//!                 Expression::Number(number) => number.as_i64().ok(),
+//!                 _ => None,
+//!             })
+//!             .max()
+//!             .map(Expression::from)
+//!             .ok_or_else(|| "expected at least one number".to_string())
+//!     });
+//!
+//! let expression = Expression::parse("max(var.base_port + 1, 80)").unwrap();
+//! assert_eq!(context.evaluate(&expression).unwrap(), Expression::from(8001));
+//! ```
+//!
+//! Arithmetic on integers is done with 128 bit integers, and falls back to 64 bit floats for
+//! floats and divisions that do not have an integral result.
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::parser::expression::Expression;
+use crate::parser::number::Number;
+use crate::parser::operation::{BinaryOperator, Operation, UnaryOperator};
+use crate::parser::string::TemplatePart;
+use crate::Error;
+
+/// A function that can be called from expressions
+pub type Function =
+    Box<dyn Fn(Vec<Expression<'static>>) -> Result<Expression<'static>, String> + Send + Sync>;
+
+/// Variables and functions to evaluate expressions with
+#[derive(Default)]
+pub struct Context {
+    variables: HashMap<String, Expression<'static>>,
+    functions: HashMap<String, Function>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a variable
+    pub fn variable<S, E>(mut self, name: S, value: E) -> Self
+    where
+        S: Into<String>,
+        E: Into<Expression<'static>>,
+    {
+        let _ = self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Add a function. Functions are called with their evaluated arguments, and return a message
+    /// if the call fails.
+    pub fn function<S, F>(mut self, name: S, function: F) -> Self
+    where
+        S: Into<String>,
+        F: Fn(Vec<Expression<'static>>) -> Result<Expression<'static>, String>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let _ = self.functions.insert(name.into(), Box::new(function));
+        self
+    }
+
+    /// Get the value of a variable
+    pub fn get_variable(&self, name: &str) -> Option<&Expression<'static>> {
+        self.variables.get(name)
+    }
+
+    /// Evaluate an expression to a literal value or a collection of literal values
+    pub fn evaluate<'a>(&self, expression: &Expression<'a>) -> Result<Expression<'a>, Error> {
+        match expression {
+            Expression::Null
+            | Expression::Number(_)
+            | Expression::Boolean(_)
+            | Expression::String(_) => Ok(expression.clone()),
+            Expression::Tuple(tuple) => Ok(Expression::Tuple(
+                tuple
+                    .iter()
+                    .map(|expr| self.evaluate(expr))
+                    .collect::<Result<_, _>>()?,
+            )),
+            Expression::Object(object) => Ok(Expression::Object(
+                object
+                    .iter()
+                    .map(|(key, expr)| Ok((key.clone(), self.evaluate(expr)?)))
+                    .collect::<Result<_, Error>>()?,
+            )),
+            Expression::Template(template) => self.evaluate_template(template),
+            Expression::Variable(name) => self
+                .get_variable(name)
+                .cloned()
+                .ok_or_else(|| Error::UnknownVariable(name.to_string())),
+            Expression::GetAttr(expr, name) => match self.evaluate(expr)? {
+                Expression::Object(object) => object
+                    .into_iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| {
+                        Error::InvalidOperation(format!("Object has no attribute {}", name))
+                    }),
+                other => Err(Error::InvalidOperation(format!(
+                    "Cannot get attribute {} of a {}",
+                    name,
+                    other.variant_name()
+                ))),
+            },
+            Expression::Index(expr, index) => match (self.evaluate(expr)?, self.evaluate(index)?) {
+                (Expression::Tuple(mut tuple), Expression::Number(number)) => {
+                    let index = number
+                        .as_u64()
+                        .ok()
+                        .map(|index| index as usize)
+                        .filter(|index| *index < tuple.len())
+                        .ok_or_else(|| {
+                            Error::InvalidOperation(format!(
+                                "Index {} is out of bounds for a Tuple of length {}",
+                                &*number,
+                                tuple.len()
+                            ))
+                        })?;
+                    Ok(tuple.swap_remove(index))
+                }
+                (Expression::Object(object), Expression::String(key)) => object
+                    .into_iter()
+                    .find(|(element, _)| *element == key)
+                    .map(|(_, value)| value)
+                    .ok_or_else(|| Error::InvalidOperation(format!("Object has no key {}", key))),
+                (expr, index) => Err(Error::InvalidOperation(format!(
+                    "Cannot index a {} with a {}",
+                    expr.variant_name(),
+                    index.variant_name()
+                ))),
+            },
+            Expression::FunctionCall(name, arguments) => {
+                let function = self
+                    .functions
+                    .get(&**name)
+                    .ok_or_else(|| Error::UnknownFunction(name.to_string()))?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| Ok(crate::AsOwned::as_owned(&self.evaluate(argument)?)))
+                    .collect::<Result<_, Error>>()?;
+                function(arguments).map_err(|message| Error::FunctionError {
+                    name: name.to_string(),
+                    message,
+                })
+            }
+            Expression::Operation(operation) => match &**operation {
+                Operation::Unary(operator, operand) => unary(*operator, self.evaluate(operand)?),
+                Operation::Binary(operator, lhs, rhs) => {
+                    binary(*operator, self.evaluate(lhs)?, self.evaluate(rhs)?)
+                }
+            },
+            Expression::Conditional(conditional) => {
+                let (condition, true_result, false_result) = &**conditional;
+                match self.evaluate(condition)? {
+                    Expression::Boolean(true) => self.evaluate(true_result),
+                    Expression::Boolean(false) => self.evaluate(false_result),
+                    other => Err(Error::InvalidOperation(format!(
+                        "Condition must be a Boolean, got {}",
+                        other.variant_name()
+                    ))),
+                }
+            }
+        }
+    }
+
+    /// A template that is a single interpolation evaluates to the value of the interpolation.
+    /// Otherwise, the interpolations are converted to strings and concatenated.
+    fn evaluate_template<'a>(
+        &self,
+        template: &[TemplatePart<'a>],
+    ) -> Result<Expression<'a>, Error> {
+        if let [TemplatePart::Interpolation(expr)] = template {
+            return self.evaluate(expr);
+        }
+
+        let mut string = String::new();
+        for part in template {
+            match part {
+                TemplatePart::Literal(literal) => string.push_str(literal),
+                TemplatePart::Interpolation(expr) => match self.evaluate(expr)? {
+                    Expression::String(value) => string.push_str(&value),
+                    Expression::Number(number) => string.push_str(&number),
+                    Expression::Boolean(boolean) => string.push_str(&boolean.to_string()),
+                    other => {
+                        return Err(Error::InvalidOperation(format!(
+                            "Cannot interpolate {} into a template",
+                            other.variant_name()
+                        )))
+                    }
+                },
+            }
+        }
+        Ok(Expression::from(string))
+    }
+}
+
+impl fmt::Debug for Context {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Context")
+            .field("variables", &self.variables)
+            .field("functions", &self.functions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// A number as either an integer or a float, for arithmetic
+///
+/// With the `bigdecimal` feature, numbers that are not integers and integers that overflow are
+/// exact decimals instead of floats.
+#[derive(Clone, Debug, PartialEq)]
+enum Numeric {
+    Integer(i128),
+    #[cfg(not(feature = "bigdecimal"))]
+    Float(f64),
+    #[cfg(feature = "bigdecimal")]
+    Decimal(bigdecimal::BigDecimal),
+}
+
+impl Numeric {
+    fn from_number(number: &Number) -> Result<Self, Error> {
+        if let Ok(integer) = number.as_i128() {
+            return Ok(Numeric::Integer(integer));
+        }
+        #[cfg(not(feature = "bigdecimal"))]
+        let numeric = number.as_f64().ok().map(Numeric::Float);
+        #[cfg(feature = "bigdecimal")]
+        let numeric = number.as_big_decimal().ok().map(Numeric::Decimal);
+        numeric.ok_or_else(|| Error::InvalidNumber(number.to_string()))
+    }
+
+    fn is_zero(&self) -> bool {
+        match self {
+            Numeric::Integer(integer) => *integer == 0,
+            #[cfg(not(feature = "bigdecimal"))]
+            Numeric::Float(float) => *float == 0.0,
+            #[cfg(feature = "bigdecimal")]
+            Numeric::Decimal(decimal) => bigdecimal::Zero::is_zero(decimal),
+        }
+    }
+
+    #[cfg(not(feature = "bigdecimal"))]
+    fn as_f64(&self) -> f64 {
+        match self {
+            Numeric::Integer(integer) => *integer as f64,
+            Numeric::Float(float) => *float,
+        }
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    fn into_decimal(self) -> bigdecimal::BigDecimal {
+        match self {
+            Numeric::Integer(integer) => bigdecimal::BigDecimal::from(integer),
+            Numeric::Decimal(decimal) => decimal,
+        }
+    }
+}
+
+impl<'a> From<Numeric> for Expression<'a> {
+    fn from(numeric: Numeric) -> Self {
+        match numeric {
+            Numeric::Integer(integer) => Expression::from(integer),
+            #[cfg(not(feature = "bigdecimal"))]
+            Numeric::Float(float) => Expression::from(float),
+            #[cfg(feature = "bigdecimal")]
+            Numeric::Decimal(decimal) => Expression::Number(Number::from(decimal)),
+        }
+    }
+}
+
+fn operand_error(operator: impl fmt::Display, operands: &[&Expression]) -> Error {
+    let operands: Vec<_> = operands.iter().map(|expr| expr.variant_name()).collect();
+    Error::InvalidOperation(format!(
+        "Operator {} cannot be applied to {}",
+        operator,
+        operands.join(" and ")
+    ))
+}
+
+fn unary<'a>(operator: UnaryOperator, operand: Expression<'a>) -> Result<Expression<'a>, Error> {
+    match (operator, &operand) {
+        (UnaryOperator::Not, Expression::Boolean(boolean)) => Ok(Expression::Boolean(!boolean)),
+        (UnaryOperator::Negate, Expression::Number(number)) => {
+            match Numeric::from_number(number)? {
+                Numeric::Integer(integer) => match integer.checked_neg() {
+                    Some(integer) => Ok(Expression::from(integer)),
+                    #[cfg(not(feature = "bigdecimal"))]
+                    None => Err(overflow(operator)),
+                    #[cfg(feature = "bigdecimal")]
+                    None => Ok(Numeric::Decimal(-bigdecimal::BigDecimal::from(integer)).into()),
+                },
+                #[cfg(not(feature = "bigdecimal"))]
+                Numeric::Float(float) => Ok(Expression::from(-float)),
+                #[cfg(feature = "bigdecimal")]
+                Numeric::Decimal(decimal) => Ok(Numeric::Decimal(-decimal).into()),
+            }
+        }
+        _ => Err(operand_error(operator, &[&operand])),
+    }
+}
+
+#[cfg(not(feature = "bigdecimal"))]
+fn overflow(operator: impl fmt::Display) -> Error {
+    Error::InvalidOperation(format!("Integer overflow in operator {}", operator))
+}
+
+fn binary<'a>(
+    operator: BinaryOperator,
+    lhs: Expression<'a>,
+    rhs: Expression<'a>,
+) -> Result<Expression<'a>, Error> {
+    use BinaryOperator::*;
+
+    match (operator, &lhs, &rhs) {
+        (Equal, _, _) => Ok(Expression::Boolean(lhs == rhs)),
+        (NotEqual, _, _) => Ok(Expression::Boolean(lhs != rhs)),
+        (And, Expression::Boolean(lhs), Expression::Boolean(rhs)) => {
+            Ok(Expression::Boolean(*lhs && *rhs))
+        }
+        (Or, Expression::Boolean(lhs), Expression::Boolean(rhs)) => {
+            Ok(Expression::Boolean(*lhs || *rhs))
+        }
+        (And, _, _) | (Or, _, _) => Err(operand_error(operator, &[&lhs, &rhs])),
+        (_, Expression::Number(lhs), Expression::Number(rhs)) => {
+            let lhs = Numeric::from_number(lhs)?;
+            let rhs = Numeric::from_number(rhs)?;
+            match operator {
+                Greater | GreaterEqual | Less | LessEqual => {
+                    Ok(Expression::Boolean(compare(operator, lhs, rhs)))
+                }
+                _ => arithmetic(operator, lhs, rhs).map(Expression::from),
+            }
+        }
+        _ => Err(operand_error(operator, &[&lhs, &rhs])),
+    }
+}
+
+fn compare(operator: BinaryOperator, lhs: Numeric, rhs: Numeric) -> bool {
+    let ordering = match (lhs, rhs) {
+        (Numeric::Integer(lhs), Numeric::Integer(rhs)) => lhs.partial_cmp(&rhs),
+        #[cfg(not(feature = "bigdecimal"))]
+        (lhs, rhs) => lhs.as_f64().partial_cmp(&rhs.as_f64()),
+        #[cfg(feature = "bigdecimal")]
+        (lhs, rhs) => lhs.into_decimal().partial_cmp(&rhs.into_decimal()),
+    };
+    match (operator, ordering) {
+        (_, None) => false,
+        (BinaryOperator::Greater, Some(ordering)) => ordering.is_gt(),
+        (BinaryOperator::GreaterEqual, Some(ordering)) => ordering.is_ge(),
+        (BinaryOperator::Less, Some(ordering)) => ordering.is_lt(),
+        (BinaryOperator::LessEqual, Some(ordering)) => ordering.is_le(),
+        _ => unreachable!("only comparison operators are compared"),
+    }
+}
+
+fn arithmetic(operator: BinaryOperator, lhs: Numeric, rhs: Numeric) -> Result<Numeric, Error> {
+    use BinaryOperator::*;
+
+    if (operator == Divide || operator == Modulo) && rhs.is_zero() {
+        return Err(Error::InvalidOperation("Division by zero".to_string()));
+    }
+
+    if let (Numeric::Integer(lhs), Numeric::Integer(rhs)) = (&lhs, &rhs) {
+        if operator != Divide || lhs.checked_rem(*rhs) == Some(0) {
+            let result = match operator {
+                Add => lhs.checked_add(*rhs),
+                Subtract => lhs.checked_sub(*rhs),
+                Multiply => lhs.checked_mul(*rhs),
+                Divide => lhs.checked_div(*rhs),
+                Modulo => lhs.checked_rem(*rhs),
+                _ => unreachable!("only arithmetic operators are applied"),
+            };
+            match result {
+                Some(result) => return Ok(Numeric::Integer(result)),
+                // Integers that overflow are exact decimals instead
+                #[cfg(feature = "bigdecimal")]
+                None => {}
+                #[cfg(not(feature = "bigdecimal"))]
+                None => return Err(overflow(operator)),
+            }
+        }
+    }
+
+    Ok(inexact_arithmetic(operator, lhs, rhs))
+}
+
+/// Arithmetic on floats
+#[cfg(not(feature = "bigdecimal"))]
+fn inexact_arithmetic(operator: BinaryOperator, lhs: Numeric, rhs: Numeric) -> Numeric {
+    use BinaryOperator::*;
+
+    let (lhs, rhs) = (lhs.as_f64(), rhs.as_f64());
+    let result = match operator {
+        Add => lhs + rhs,
+        Subtract => lhs - rhs,
+        Multiply => lhs * rhs,
+        Divide => lhs / rhs,
+        Modulo => lhs % rhs,
+        _ => unreachable!("only arithmetic operators are applied"),
+    };
+    Numeric::Float(result)
+}
+
+/// Arithmetic on exact decimals. Division is rounded to the default precision of
+/// [`BigDecimal`](bigdecimal::BigDecimal).
+#[cfg(feature = "bigdecimal")]
+fn inexact_arithmetic(operator: BinaryOperator, lhs: Numeric, rhs: Numeric) -> Numeric {
+    use BinaryOperator::*;
+
+    let (lhs, rhs) = (lhs.into_decimal(), rhs.into_decimal());
+    let result = match operator {
+        Add => lhs + rhs,
+        Subtract => lhs - rhs,
+        Multiply => lhs * rhs,
+        Divide => lhs / rhs,
+        Modulo => lhs % rhs,
+        _ => unreachable!("only arithmetic operators are applied"),
+    };
+    Numeric::Decimal(result.normalized())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> Context {
+        Context::new()
+            .variable("port", 8000)
+            .variable("name", "server")
+            .variable("enabled", true)
+            .variable(
+                "var",
+                Expression::new_object(vec![
+                    (
+                        "list",
+                        Expression::new_tuple(vec![Expression::from(1), Expression::from(2)]),
+                    ),
+                    ("half", Expression::from(0.5)),
+                ]),
+            )
+            .function("upper", |arguments| match arguments.as_slice() {
+                [Expression::String(string)] => Ok(Expression::from(string.to_uppercase())),
+                _ => Err("expected a single string".to_string()),
+            })
+    }
+
+    #[test]
+    fn expressions_are_evaluated() {
+        let context = context();
+        let test_cases = [
+            ("port + 1", Expression::from(8001)),
+            ("port - 1 * 2", Expression::from(7998)),
+            ("(port - 1) % 7", Expression::from(7999 % 7)),
+            ("port / 16", Expression::from(500)),
+            ("7 / 2", Expression::from(3.5)),
+            ("1e3 + 1", Expression::from(1001)),
+            ("var.half * 3", Expression::from(1.5)),
+            ("-port", Expression::from(-8000)),
+            ("var.list[1]", Expression::from(2)),
+            ("var[\"half\"]", Expression::from(0.5)),
+            ("port >= 8000 && !enabled", Expression::from(false)),
+            ("port > 1.5 || false", Expression::from(true)),
+            ("name == \"server\"", Expression::from(true)),
+            ("enabled ? port : 80", Expression::from(8000)),
+            ("upper(name)", Expression::from("SERVER")),
+            ("\"${name}:${port}\"", Expression::from("server:8000")),
+            (
+                "\"${var.list}\"",
+                Expression::from(vec![Expression::from(1), Expression::from(2)]),
+            ),
+            ("\"$${name}\"", Expression::from("${name}")),
+            (
+                "[port, { a = name }]",
+                Expression::new_tuple(vec![
+                    Expression::from(8000),
+                    Expression::new_object(vec![("a", Expression::from("server"))]),
+                ]),
+            ),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            println!("Testing {}", input);
+            let expression = Expression::parse(input).unwrap();
+            assert_eq!(context.evaluate(&expression).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn invalid_expressions_are_errors() {
+        let context = context();
+        let test_cases = [
+            ("missing", "Unknown variable missing"),
+            ("missing(1)", "Unknown function missing"),
+            (
+                "upper(1)",
+                "Error calling function upper: expected a single string",
+            ),
+            (
+                "var.missing",
+                "Invalid operation: Object has no attribute missing",
+            ),
+            (
+                "var.list[2]",
+                "Invalid operation: Index 2 is out of bounds for a Tuple of length 2",
+            ),
+            ("port / 0", "Invalid operation: Division by zero"),
+            (
+                "port + name",
+                "Invalid operation: Operator + cannot be applied to Number and String",
+            ),
+            (
+                "port ? 1 : 2",
+                "Invalid operation: Condition must be a Boolean, got Number",
+            ),
+            (
+                "\"a${var}\"",
+                "Invalid operation: Cannot interpolate Object into a template",
+            ),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            println!("Testing {}", input);
+            let expression = Expression::parse(input).unwrap();
+            let error = context.evaluate(&expression).unwrap_err();
+            assert_eq!(error.to_string(), *expected);
+        }
+    }
+
+    #[cfg(not(feature = "bigdecimal"))]
+    #[test]
+    fn integer_overflow_is_an_error() {
+        let expression = Expression::parse("170141183460469231731687303715884105727 + 1").unwrap();
+        let error = Context::new().evaluate(&expression).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid operation: Integer overflow in operator +"
+        );
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    #[test]
+    fn arithmetic_is_exact_with_big_decimals() {
+        let test_cases = [
+            ("0.1 + 0.2", "0.3"),
+            ("0.1 * 3 == 0.3", "true"),
+            (
+                "170141183460469231731687303715884105727 + 1",
+                "170141183460469231731687303715884105728",
+            ),
+            ("-1e40 - 1", "-10000000000000000000000000000000000000001"),
+            ("1e-30 > 0", "true"),
+            ("7.5 % 2", "1.5"),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            println!("Testing {}", input);
+            let expression = Expression::parse(input).unwrap();
+            let expected = Expression::parse(expected).unwrap();
+            assert_eq!(Context::new().evaluate(&expression).unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,376 @@
+//! Format parsed HCL back into HCL
+//!
+//! The output is normalised: comments and the original whitespace are not preserved, nested
+//! bodies are indented with two spaces and strings are always written as quoted strings.
+use std::fmt::{self, Write};
+
+use crate::parser::block::{Block, BlockLabel};
+use crate::parser::body::{Body, BodyElement};
+use crate::parser::expression::Expression;
+use crate::parser::object::ObjectElementIdentifier;
+use crate::parser::operation::{Operation, UnaryOperator};
+use crate::parser::string::TemplatePart;
+
+const INDENT: &str = "  ";
+/// Precedence of unary operations, which bind tighter than any binary operator
+const UNARY_PRECEDENCE: u8 = 7;
+/// Precedence of expression terms, which never need parentheses
+const TERM_PRECEDENCE: u8 = 8;
+
+/// Format a [`Body`] as a HCL string
+pub fn body(body: &Body) -> String {
+    let mut output = String::new();
+    write_body(&mut output, body, 0).expect("writing to a String cannot fail");
+    output
+}
+
+/// Format a [`Block`] as a HCL string
+pub fn block(block: &Block) -> String {
+    let mut output = String::new();
+    write_block(&mut output, block, 0).expect("writing to a String cannot fail");
+    output
+}
+
+/// Format an [`Expression`] as a HCL string
+pub fn expression(expression: &Expression) -> String {
+    let mut output = String::new();
+    write_expression(&mut output, expression, 0).expect("writing to a String cannot fail");
+    output
+}
+
+/// Write a quoted HCL string, escaping characters as necessary
+pub fn write_quoted<W: Write>(writer: &mut W, string: &str) -> fmt::Result {
+    writer.write_char('"')?;
+    write_escaped(writer, string, false)?;
+    writer.write_char('"')
+}
+
+/// Write the escaped contents of a quoted string. In templates, `${` is escaped as `$${` so that
+/// it is not read back as an interpolation.
+fn write_escaped<W: Write>(writer: &mut W, string: &str, template: bool) -> fmt::Result {
+    let mut chars = string.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => writer.write_str("\\\"")?,
+            '\\' => writer.write_str("\\\\")?,
+            '\n' => writer.write_str("\\n")?,
+            '\r' => writer.write_str("\\r")?,
+            '\t' => writer.write_str("\\t")?,
+            '$' if template && chars.peek() == Some(&'{') => writer.write_str("$$")?,
+            c if c.is_control() => write!(writer, "\\u{:04X}", c as u32)?,
+            c => writer.write_char(c)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write a template as a quoted template
+pub fn write_template<W: Write>(writer: &mut W, template: &[TemplatePart]) -> fmt::Result {
+    writer.write_char('"')?;
+    for part in template {
+        match part {
+            TemplatePart::Literal(literal) => write_escaped(writer, literal, true)?,
+            TemplatePart::Interpolation(expr) => {
+                writer.write_str("${")?;
+                write_expression(writer, expr, 0)?;
+                writer.write_char('}')?;
+            }
+        }
+    }
+    writer.write_char('"')
+}
+
+/// Write an operand of an operator with the given precedence, in parentheses if the operand
+/// binds looser than the operator
+fn write_operand<W: Write>(
+    writer: &mut W,
+    operand: &Expression,
+    level: usize,
+    precedence: u8,
+) -> fmt::Result {
+    let operand_precedence = match operand {
+        Expression::Operation(operation) => match **operation {
+            Operation::Binary(operator, ..) => operator.precedence(),
+            Operation::Unary(..) => UNARY_PRECEDENCE,
+        },
+        Expression::Conditional(_) => 0,
+        _ => TERM_PRECEDENCE,
+    };
+    if operand_precedence < precedence {
+        writer.write_char('(')?;
+        write_expression(writer, operand, level)?;
+        writer.write_char(')')
+    } else {
+        write_expression(writer, operand, level)
+    }
+}
+
+fn write_indent<W: Write>(writer: &mut W, level: usize) -> fmt::Result {
+    for _ in 0..level {
+        writer.write_str(INDENT)?;
+    }
+    Ok(())
+}
+
+/// Write the elements of a body, each terminated by a newline
+pub fn write_body<W: Write>(writer: &mut W, body: &Body, level: usize) -> fmt::Result {
+    for element in body {
+        write_indent(writer, level)?;
+        match element {
+            BodyElement::Attribute((identifier, expr)) => {
+                write!(writer, "{} = ", identifier)?;
+                write_expression(writer, expr, level)?;
+            }
+            BodyElement::Block(blk) => write_block(writer, blk, level)?,
+        }
+        writer.write_char('\n')?;
+    }
+    Ok(())
+}
+
+/// Write a block. The block is not terminated with a newline
+pub fn write_block<W: Write>(writer: &mut W, block: &Block, level: usize) -> fmt::Result {
+    writer.write_str(&block.r#type)?;
+    write_labels(writer, &block.labels)?;
+    write_block_body(writer, &block.body, level)
+}
+
+/// Write block labels, each preceded by a space
+pub fn write_labels<W: Write>(writer: &mut W, labels: &[BlockLabel]) -> fmt::Result {
+    for label in labels {
+        writer.write_char(' ')?;
+        match label {
+            BlockLabel::StringLiteral(literal) => write_quoted(writer, literal)?,
+            BlockLabel::Identifier(ident) => writer.write_str(ident)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write the braces and the body of a block, starting with a space before the opening brace
+pub fn write_block_body<W: Write>(writer: &mut W, body: &Body, level: usize) -> fmt::Result {
+    if body.is_empty() {
+        return writer.write_str(" {}");
+    }
+
+    writer.write_str(" {\n")?;
+    write_body(writer, body, level + 1)?;
+    write_indent(writer, level)?;
+    writer.write_char('}')
+}
+
+/// Write an expression. `level` is the indentation level of the line the expression starts on.
+pub fn write_expression<W: Write>(
+    writer: &mut W,
+    expression: &Expression,
+    level: usize,
+) -> fmt::Result {
+    match expression {
+        Expression::Null => writer.write_str("null"),
+        Expression::Number(number) => writer.write_str(number),
+        Expression::Boolean(boolean) => write!(writer, "{}", boolean),
+        Expression::String(string) => {
+            writer.write_char('"')?;
+            write_escaped(writer, string, true)?;
+            writer.write_char('"')
+        }
+        Expression::Tuple(tuple) => {
+            if tuple.is_empty() {
+                return writer.write_str("[]");
+            }
+            writer.write_str("[\n")?;
+            for expr in tuple {
+                write_indent(writer, level + 1)?;
+                write_expression(writer, expr, level + 1)?;
+                writer.write_str(",\n")?;
+            }
+            write_indent(writer, level)?;
+            writer.write_char(']')
+        }
+        Expression::Object(object) => {
+            if object.is_empty() {
+                return writer.write_str("{}");
+            }
+            writer.write_str("{\n")?;
+            for (key, expr) in object {
+                write_indent(writer, level + 1)?;
+                match key {
+                    ObjectElementIdentifier::Identifier(ident) => writer.write_str(ident)?,
+                    ObjectElementIdentifier::Expression(expr) => writer.write_str(expr)?,
+                }
+                writer.write_str(" = ")?;
+                write_expression(writer, expr, level + 1)?;
+                writer.write_char('\n')?;
+            }
+            write_indent(writer, level)?;
+            writer.write_char('}')
+        }
+        Expression::Template(template) => write_template(writer, template),
+        Expression::Variable(name) => writer.write_str(name),
+        Expression::GetAttr(expr, name) => {
+            write_operand(writer, expr, level, TERM_PRECEDENCE)?;
+            write!(writer, ".{}", name)
+        }
+        Expression::Index(expr, index) => {
+            write_operand(writer, expr, level, TERM_PRECEDENCE)?;
+            writer.write_char('[')?;
+            write_expression(writer, index, level)?;
+            writer.write_char(']')
+        }
+        Expression::FunctionCall(name, arguments) => {
+            write!(writer, "{}(", name)?;
+            for (i, argument) in arguments.iter().enumerate() {
+                if i > 0 {
+                    writer.write_str(", ")?;
+                }
+                write_expression(writer, argument, level)?;
+            }
+            writer.write_char(')')
+        }
+        Expression::Operation(operation) => match &**operation {
+            Operation::Unary(operator, operand) => {
+                write!(writer, "{}", operator)?;
+                match (operator, operand) {
+                    // `-` directly followed by a number is read back as a negative number
+                    (UnaryOperator::Negate, Expression::Number(_)) => {
+                        writer.write_char('(')?;
+                        write_expression(writer, operand, level)?;
+                        writer.write_char(')')
+                    }
+                    _ => write_operand(writer, operand, level, UNARY_PRECEDENCE),
+                }
+            }
+            Operation::Binary(operator, lhs, rhs) => {
+                write_operand(writer, lhs, level, operator.precedence())?;
+                write!(writer, " {} ", operator)?;
+                write_operand(writer, rhs, level, operator.precedence() + 1)
+            }
+        },
+        Expression::Conditional(conditional) => {
+            let (condition, true_result, false_result) = &**conditional;
+            write_operand(writer, condition, level, 1)?;
+            writer.write_str(" ? ")?;
+            write_expression(writer, true_result, level)?;
+            writer.write_str(" : ")?;
+            write_expression(writer, false_result, level)
+        }
+    }
+}
+
+impl<'a> fmt::Display for Expression<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_expression(f, self, 0)
+    }
+}
+
+impl<'a> fmt::Display for Block<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_block(f, self, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixtures;
+    use crate::parser::parse_str;
+
+    #[test]
+    fn expressions_are_formatted_correctly() {
+        let test_cases = [
+            (Expression::Null, "null"),
+            (Expression::from(123), "123"),
+            (Expression::from(-1.5), "-1.5"),
+            (Expression::from(true), "true"),
+            (
+                Expression::from("a \"quoted\"\nstring"),
+                r#""a \"quoted\"\nstring""#,
+            ),
+            (Expression::new_tuple(vec![]), "[]"),
+            (
+                Expression::new_tuple(vec![Expression::from(1), Expression::from("two")]),
+                "[\n  1,\n  \"two\",\n]",
+            ),
+            (
+                Expression::new_object(vec![(
+                    "nested",
+                    Expression::new_object(vec![("foo", Expression::from("bar"))]),
+                )]),
+                "{\n  nested = {\n    foo = \"bar\"\n  }\n}",
+            ),
+        ];
+
+        for (expression, expected) in test_cases.iter() {
+            assert_eq!(expression.to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn expressions_that_have_to_be_evaluated_are_formatted_correctly() {
+        let test_cases = [
+            ("var.list[0]", "var.list[0]"),
+            ("max(1,2 , x)", "max(1, 2, x)"),
+            ("(1 + 2) * -(3 - 4)", "(1 + 2) * -(3 - 4)"),
+            ("1 - (2 - 3)", "1 - (2 - 3)"),
+            ("1 - 2 - 3", "1 - 2 - 3"),
+            ("a || b && !c", "a || b && !c"),
+            ("(a ? b : c) ? d : e", "(a ? b : c) ? d : e"),
+            (r#""${name}-$${literal}\n""#, r#""${name}-$${literal}\n""#),
+            (r#""$${literal}""#, r#""$${literal}""#),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            let parsed = Expression::parse(input).unwrap();
+            let formatted = expression(&parsed);
+            assert_eq!(formatted, *expected);
+            assert_eq!(Expression::parse(&formatted).unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn formatted_operations_are_parsed_to_the_same_expression() {
+        use crate::parser::body::BodyElement;
+        use crate::parser::operation::BinaryOperator;
+
+        let negate = |operand| {
+            Expression::Operation(Box::new(Operation::Unary(UnaryOperator::Negate, operand)))
+        };
+        let subtract = |lhs, rhs| {
+            Expression::Operation(Box::new(Operation::Binary(
+                BinaryOperator::Subtract,
+                lhs,
+                rhs,
+            )))
+        };
+        let expressions = [
+            negate(Expression::from(1)),
+            negate(Expression::from(-1)),
+            negate(Expression::from(1.5)),
+            negate(negate(Expression::from(1))),
+            negate(Expression::from("a")),
+            subtract(Expression::from(1), Expression::from(-1)),
+            subtract(Expression::from(1), negate(Expression::from(1))),
+        ];
+
+        for expected in expressions.iter() {
+            let formatted = expression(expected);
+            assert_eq!(&Expression::parse(&formatted).unwrap(), expected);
+
+            let source = format!("a = {}\n", formatted);
+            let parsed = parse_str(&source).unwrap();
+            let expected = BodyElement::Attribute((From::from("a"), expected.clone()));
+            assert_eq!(parsed, [expected]);
+        }
+    }
+
+    #[test]
+    fn formatted_fixtures_are_parsed_to_the_same_body() {
+        for hcl in fixtures::ALL {
+            let parsed = parse_str(hcl).unwrap();
+            let formatted = body(&parsed);
+            let reparsed = parse_str(&formatted).unwrap();
+            assert_eq!(parsed, reparsed);
+        }
+    }
+}
@@ -138,7 +138,7 @@ where
 }
 
 pub enum KeyValuePairsIterator<'a, K: 'a, V: 'a> {
-    Merged(std::collections::hash_map::Iter<'a, K, V>),
+    Merged(indexmap::map::Iter<'a, K, V>),
     Unmerged(std::slice::Iter<'a, (K, V)>),
 }
 
@@ -166,7 +166,7 @@ impl<'a, K: 'a, V: 'a> Iterator for KeyValuePairsIterator<'a, K, V> {
 impl<'a, K: 'a, V: 'a> ExactSizeIterator for KeyValuePairsIterator<'a, K, V> {}
 
 pub enum KeyValuePairsIntoIterator<K, V> {
-    Merged(std::collections::hash_map::IntoIter<K, V>),
+    Merged(indexmap::map::IntoIter<K, V>),
     Unmerged(std::vec::IntoIter<(K, V)>),
 }
 
@@ -194,7 +194,7 @@ impl<K, V> Iterator for KeyValuePairsIntoIterator<K, V> {
 impl<K, V> ExactSizeIterator for KeyValuePairsIntoIterator<K, V> {}
 
 pub enum KeyIterator<'a, K: 'a, V: 'a> {
-    Merged(std::collections::hash_map::Keys<'a, K, V>),
+    Merged(indexmap::map::Keys<'a, K, V>),
     // Can we do better?
     // `std::iter::Map<std::slice::Iter<'_, (K, V)>, [closure@lib/src/lib.rs:228:88: 228:98]>`
     // is not really writable...
@@ -220,7 +220,7 @@ impl<'a, K: 'a, V: 'a> Iterator for KeyIterator<'a, K, V> {
 }
 
 pub enum ValueIterator<'a, K: 'a, V: 'a> {
-    Merged(std::collections::hash_map::Values<'a, K, V>),
+    Merged(indexmap::map::Values<'a, K, V>),
     // Can we do better?
     Unmerged(Box<dyn Iterator<Item = &'a V> + 'a>),
 }
@@ -3,11 +3,18 @@ mod macros;
 mod errors;
 
 pub mod constants;
+pub mod diff;
+pub mod document;
+pub mod eval;
+pub mod format;
 pub mod iter;
 #[macro_use]
 pub mod utils;
 #[macro_use]
 pub mod parser;
+pub mod schema;
+pub mod select;
+pub mod span;
 pub mod value;
 
 #[cfg(feature = "serde")]
@@ -15,7 +22,9 @@ pub mod serde;
 
 #[cfg(feature = "serde")]
 #[doc(inline)]
-pub use crate::serde::from_str;
+pub use crate::serde::{from_reader, from_slice, from_str, from_str_with_context};
+#[doc(inline)]
+pub use document::Document;
 #[doc(inline)]
 pub use errors::Error;
 #[doc(inline)]
@@ -29,6 +38,8 @@ use std::borrow::Cow;
 use std::collections::HashMap;
 use std::hash::{BuildHasher, Hash};
 
+use indexmap::IndexMap;
+
 /// Has scalar length
 pub trait ScalarLength {
     /// Recursively count the number of scalars
@@ -86,12 +97,12 @@ pub enum MergeBehaviour {
     /// Take the first value seen on duplicate identifiers in a map or duplicate labels
     /// between block with the same identifier
     ///
-    /// __Unimplemented__
+    /// __Unsupported__ when parsing into a [`Value`]
     TakeFirst,
     /// Take the last value seen on duplicate identifiers in a map or duplicate labels
     /// between block with the same identifier
     ///
-    /// __Unimplemented__
+    /// __Unsupported__ when parsing into a [`Value`]
     TakeLast,
 }
 
@@ -126,32 +137,51 @@ impl<T> OneOrMany<T> {
         }
     }
 
+    pub fn one(self) -> Result<T, Error> {
+        match self {
+            OneOrMany::One(one) => Ok(one),
+            OneOrMany::Many(_) => Err(Error::UnexpectedVariant {
+                enum_type: constants::ONE_OR_MANY,
+                expected: constants::ONE,
+                actual: constants::MANY,
+            }),
+        }
+    }
+
+    /// # Panics
+    /// Panics if the variant is not `One`
     pub fn unwrap_one(self) -> T {
-        if let OneOrMany::One(one) = self {
-            one
-        } else {
-            panic!("unwrapping a many")
+        self.one().unwrap()
+    }
+
+    pub fn many(self) -> Result<Vec<T>, Error> {
+        match self {
+            OneOrMany::Many(many) => Ok(many),
+            OneOrMany::One(_) => Err(Error::UnexpectedVariant {
+                enum_type: constants::ONE_OR_MANY,
+                expected: constants::MANY,
+                actual: constants::ONE,
+            }),
         }
     }
 
+    /// # Panics
+    /// Panics if the variant is not `Many`
     pub fn unwrap_many(self) -> Vec<T> {
-        if let OneOrMany::Many(many) = self {
-            many
-        } else {
-            panic!("unwrapping a one")
-        }
+        self.many().unwrap()
     }
 }
 
 /// A set of `(Key, Value)` pairs which can exist in a merged or unmerged variant
 ///
-/// A merged variant can only have unique keys, where the unmerged variant may have duplicate keys
+/// A merged variant can only have unique keys, where the unmerged variant may have duplicate keys.
+/// Both variants keep their keys in the order they were inserted.
 #[derive(Debug, PartialEq, Clone, Eq)]
 pub enum KeyValuePairs<K, V>
 where
     K: Hash + Eq,
 {
-    Merged(HashMap<K, V>),
+    Merged(IndexMap<K, V>),
     Unmerged(Vec<(K, V)>),
 }
 
@@ -170,20 +200,40 @@ where
         self.len() == 0
     }
 
-    pub fn unwrap_merged(self) -> HashMap<K, V> {
+    pub fn merged(self) -> Result<IndexMap<K, V>, Error> {
         match self {
-            KeyValuePairs::Merged(hashmap) => hashmap,
-            KeyValuePairs::Unmerged(_) => panic!("Unwrapping an unmerged KeyValuePairs"),
+            KeyValuePairs::Merged(hashmap) => Ok(hashmap),
+            KeyValuePairs::Unmerged(_) => Err(Error::UnexpectedVariant {
+                enum_type: constants::KEY_VALUE_PAIRS,
+                expected: constants::MERGED,
+                actual: constants::UNMERGED,
+            }),
         }
     }
 
-    pub fn unwrap_unmerged(self) -> Vec<(K, V)> {
+    /// # Panics
+    /// Panics if the variant is not merged
+    pub fn unwrap_merged(self) -> IndexMap<K, V> {
+        self.merged().unwrap()
+    }
+
+    pub fn unmerged(self) -> Result<Vec<(K, V)>, Error> {
         match self {
-            KeyValuePairs::Merged(_) => panic!("Unwrapping an unmerged KeyValuePairs"),
-            KeyValuePairs::Unmerged(vec) => vec,
+            KeyValuePairs::Unmerged(vec) => Ok(vec),
+            KeyValuePairs::Merged(_) => Err(Error::UnexpectedVariant {
+                enum_type: constants::KEY_VALUE_PAIRS,
+                expected: constants::UNMERGED,
+                actual: constants::MERGED,
+            }),
         }
     }
 
+    /// # Panics
+    /// Panics if the variant is not unmerged
+    pub fn unwrap_unmerged(self) -> Vec<(K, V)> {
+        self.unmerged().unwrap()
+    }
+
     pub fn iter(&self) -> iter::KeyValuePairsIterator<K, V> {
         match self {
             KeyValuePairs::Merged(hashmap) => iter::KeyValuePairsIterator::Merged(hashmap.iter()),
@@ -309,6 +359,17 @@ where
     }
 }
 
+impl<K, V, S> ScalarLength for IndexMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: ScalarLength,
+    S: BuildHasher,
+{
+    fn len_scalar(&self) -> usize {
+        self.iter().fold(0, |acc, (_, v)| acc + v.len_scalar())
+    }
+}
+
 macro_rules! array_impls {
     ($($N:expr)+) => {
         $(
@@ -397,6 +458,21 @@ where
     }
 }
 
+impl<K, V, S> Mergeable for IndexMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Mergeable,
+    S: BuildHasher,
+{
+    fn is_merged(&self) -> bool {
+        self.iter().all(|(_, v)| v.is_merged())
+    }
+
+    fn is_unmerged(&self) -> bool {
+        self.iter().all(|(_, v)| v.is_unmerged())
+    }
+}
+
 impl<T1, T2> Mergeable for (T1, T2)
 where
     T2: Mergeable,
@@ -493,6 +569,21 @@ where
     }
 }
 
+impl<K, V, S, KO, VO> AsOwned for IndexMap<K, V, S>
+where
+    K: Hash + Eq + AsOwned<Output = KO>,
+    V: AsOwned<Output = VO>,
+    S: BuildHasher + Default + 'static,
+    KO: Hash + Eq + 'static,
+    VO: 'static,
+{
+    type Output = IndexMap<KO, VO, S>;
+
+    fn as_owned(&self) -> Self::Output {
+        self.iter().map(|pair| pair.as_owned()).collect()
+    }
+}
+
 impl AsOwned for String {
     type Output = String;
     fn as_owned(&self) -> Self::Output {
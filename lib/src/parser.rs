@@ -6,11 +6,17 @@ pub mod attribute;
 pub mod block;
 pub mod body;
 pub mod boolean;
+pub(crate) mod descent;
+pub mod events;
 pub mod expression;
 pub mod identifier;
+pub mod incremental;
+pub mod lexer;
 pub mod null;
 pub mod number;
 pub mod object;
+pub mod operation;
+pub mod options;
 pub mod string;
 pub mod tuple;
 
@@ -22,17 +28,19 @@ pub use block::{Block, Blocks};
 pub use body::Body;
 #[doc(inline)]
 pub use expression::Expression;
+#[doc(inline)]
+pub use options::ParseOptions;
 
 use std::borrow::Cow;
 
 use crate::value::{self, MapValues, Value};
-use crate::{AsOwned, Error};
+use crate::{AsOwned, Document, Error};
 use literals::Key;
 use whitespace::newline;
 
 use nom::types::CompleteStr;
 use nom::{
-    alt, alt_complete, call, char, complete, do_parse, eof, exact, named, named_attr, opt,
+    alt, alt_complete, call, char, complete, do_parse, eof, exact, map, named, named_attr, opt,
     preceded, tag, terminated,
 };
 
@@ -120,7 +128,7 @@ named!(
             )
             | do_parse!(
                 identifier: call!(identifier::identifier)
-                >> keys: many0!(string::string_literal)
+                >> keys: many0!(map!(string::string_literal, Cow::into_owned))
                 >> values: call!(map_expression)
                 >> (Key::Identifier(identifier), Value::Block(vec![(keys, values)].into_iter().collect()))
             )
@@ -170,6 +178,9 @@ functions in the [`parser`](crate::parser) module instead.
 
 When parsing the file, all input must be consumed or a parse error will be raised.
 
+The depth of nesting is not limited, so deeply nested input can overflow the stack. Use
+[`parse_str_with_options`] for untrusted input.
+
 See the [HCL specification](https://github.com/hashicorp/hcl2/blob/master/hcl/hclsyntax/spec.md)
 for more information on the file format.
 "#],
@@ -179,36 +190,99 @@ for more information on the file format.
 
 /// Parse a HCL string into a [`ConfigFile`] which is close to an abstract syntax tree of the
 /// HCL string.
+///
+/// The input is parsed with the default [`ParseOptions`].
 pub fn parse_str(input: &str) -> Result<ConfigFile, Error> {
-    let (remaining_input, body) =
-        config_file(CompleteStr(input)).map_err(|e| Error::from_err_str(&e))?;
-
-    if !remaining_input.is_empty() {
-        return Err(Error::Bug(format!(
-            r#"Input was not completely parsed:
-Input: {},
-Remaining: {}
-"#,
-            input, remaining_input
-        )));
-    }
+    parse_str_with_options(input, &Default::default())
+}
 
-    Ok(body)
+/// Parse a HCL string into a [`ConfigFile`] within the limits of `options`
+///
+/// Parsing fails with [`Error::LimitExceeded`] or [`Error::InputTooLarge`] when the input
+/// exceeds the limits.
+pub fn parse_str_with_options<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> Result<ConfigFile<'a>, Error> {
+    descent::parse_str(input, options)
+}
+
+/// Parse a HCL string into a [`LazyBody`](body::LazyBody) without parsing the bodies of blocks
+///
+/// The bodies of blocks are parsed when they are accessed, which is faster when only some of the
+/// blocks in a large document are needed. The bodies are skipped by tokenizing them, without
+/// keeping their tokens.
+///
+/// ```rust
+/// use ferrous_chloride::parser::body::LazyBodyElement;
+/// use ferrous_chloride::parser::parse_str_lazy;
+///
+/// let body = parse_str_lazy(r#"
+/// resource "instance" "foo" {
+///   name = "foo"
+/// }
+/// "#).unwrap();
+///
+/// match &body[0] {
+///     LazyBodyElement::Block(block) => {
+///         assert_eq!(block.r#type, "resource");
+///         assert_eq!(block.body_source(), "\n  name = \"foo\"\n");
+///         assert_eq!(block.body().unwrap().len(), 1);
+///     }
+///     element => panic!("unexpected {:?}", element),
+/// }
+/// ```
+pub fn parse_str_lazy(input: &str) -> Result<body::LazyBody<'_>, Error> {
+    descent::parse_str_lazy(input)
 }
 
 /// Parse a HCL string from a IO stream reader
 ///
-/// The entire IO stream has to be buffered in memory first before parsing can occur.
+/// The entire IO stream has to be buffered in memory first before parsing can occur. The parsed
+/// body is copied out of the buffer, see [`read_document`] to keep the buffer instead.
 ///
 /// When reading from a source against which short reads are not efficient, such as a
 /// [`File`](std::fs::File), you will want to apply your own buffering because the library
 /// will not buffer the input. See [`std::io::BufReader`].
-pub fn parse_reader<R: std::io::Read>(mut reader: R) -> Result<ConfigFile<'static>, Error> {
-    let mut buffer = String::new();
-    reader.read_to_string(&mut buffer)?;
+pub fn parse_reader<R: std::io::Read>(reader: R) -> Result<ConfigFile<'static>, Error> {
+    parse_reader_with_options(reader, &Default::default())
+}
+
+/// Parse a HCL string from a IO stream reader within the limits of `options`
+///
+/// No more of the stream than the maximum input size is read.
+pub fn parse_reader_with_options<R: std::io::Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<ConfigFile<'static>, Error> {
+    let document = read_document_with_options(reader, options)?;
+    Ok(document.body().as_owned())
+}
 
-    let parsed = parse_str(&buffer)?;
-    Ok(parsed.as_owned())
+/// Parse a HCL string from a IO stream reader into a [`Document`]
+///
+/// The entire IO stream has to be buffered in memory first before parsing can occur. The
+/// returned [`Document`] keeps the buffer, which the parsed body borrows from, so that nothing
+/// has to be copied out of it.
+pub fn read_document<R: std::io::Read>(reader: R) -> Result<Document<ConfigFile<'static>>, Error> {
+    read_document_with_options(reader, &Default::default())
+}
+
+/// Parse a HCL string from a IO stream reader into a [`Document`] within the limits of `options`
+///
+/// No more of the stream than the maximum input size is read.
+pub fn read_document_with_options<R: std::io::Read>(
+    reader: R,
+    options: &ParseOptions,
+) -> Result<Document<ConfigFile<'static>>, Error> {
+    let parse = |input| parse_str_with_options(input, options);
+    match options.max_input_size {
+        Some(limit) => {
+            let limit = (limit as u64).saturating_add(1);
+            Document::read(reader.take(limit), parse)
+        }
+        None => Document::read(reader, parse),
+    }
 }
 
 /// Parse a HCL string from a slice of bytes
@@ -7,20 +7,25 @@
 //! OneLineBlock = Identifier (StringLit|Identifier)* "{" (Identifier "=" Expression)? "}" Newline;
 //! ```
 use std::borrow::{Borrow, Cow};
-use std::collections::hash_map::{self, Entry};
-use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
 use std::hash::{Hash, Hasher};
 use std::iter::{Extend, FromIterator};
+use std::ops::Range;
+use std::sync::OnceLock;
 
-use itertools::Itertools;
+use indexmap::map::{self, Entry};
+use indexmap::IndexMap;
 use nom::types::CompleteStr;
 use nom::{alt, call, many0, named, opt, tag};
 
 use crate::parser::attribute::{attribute, Attribute};
-use crate::parser::body::{body, Body};
+use crate::parser::body::{body, Body, LazyBody};
+use crate::parser::descent;
 use crate::parser::identifier::{identifier, Identifier};
 use crate::parser::string::{string_literal, StringLiteral};
 use crate::parser::whitespace::newline;
+use crate::Error;
 
 /// HCL Block
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -57,9 +62,98 @@ impl<'a> Block<'a> {
     }
 }
 
+/// HCL Block whose body is parsed when it is accessed
+///
+/// Only the type and labels of the block are parsed. The body is checked for balanced braces,
+/// so syntax errors in it are only found when it is parsed.
+#[derive(Clone)]
+pub struct LazyBlock<'a> {
+    pub r#type: Identifier<'a>,
+    pub labels: Vec<BlockLabel<'a>>,
+    source: &'a str,
+    body: Range<usize>,
+    /// The body, once it was parsed successfully
+    parsed: OnceLock<Body<'a>>,
+}
+
+impl<'a> LazyBlock<'a> {
+    pub(crate) fn new(
+        r#type: Identifier<'a>,
+        labels: Vec<BlockLabel<'a>>,
+        source: &'a str,
+        body: Range<usize>,
+    ) -> Self {
+        Self {
+            r#type,
+            labels,
+            source,
+            body,
+            parsed: OnceLock::new(),
+        }
+    }
+
+    /// Byte range of the body in the source, between the braces
+    pub fn body_range(&self) -> Range<usize> {
+        self.body.clone()
+    }
+
+    /// Source of the body, between the braces
+    pub fn body_source(&self) -> &'a str {
+        self.source.get(self.body.clone()).unwrap_or_default()
+    }
+
+    /// Parse the body
+    ///
+    /// The body is parsed when it is first accessed, and kept for later accesses. A body which
+    /// cannot be parsed is parsed again every time.
+    pub fn body(&self) -> Result<&Body<'a>, Error> {
+        if let Some(body) = self.parsed.get() {
+            return Ok(body);
+        }
+        let body = descent::parse_block_body(self.source, self.body_range())?;
+        Ok(self.parsed.get_or_init(|| body))
+    }
+
+    /// Parse the body without parsing the bodies of the blocks in it
+    ///
+    /// Unlike [`body`](Self::body), the body is parsed again on every call.
+    pub fn lazy_body(&self) -> Result<LazyBody<'a>, Error> {
+        descent::parse_block_body_lazy(self.source, self.body_range())
+    }
+
+    /// Parse the body into a [`Block`]
+    pub fn to_block(&self) -> Result<Block<'a>, Error> {
+        Ok(Block::new(
+            self.r#type.clone(),
+            self.labels.clone(),
+            self.body()?.clone(),
+        ))
+    }
+}
+
+impl<'a> fmt::Debug for LazyBlock<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LazyBlock")
+            .field("type", &self.r#type)
+            .field("labels", &self.labels)
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+impl<'a> PartialEq for LazyBlock<'a> {
+    fn eq(&self, other: &Self) -> bool {
+        self.r#type == other.r#type
+            && self.labels == other.labels
+            && self.body_source() == other.body_source()
+    }
+}
+
+impl<'a> Eq for LazyBlock<'a> {}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BlockLabel<'a> {
-    StringLiteral(StringLiteral),
+    StringLiteral(StringLiteral<'a>),
     Identifier(Identifier<'a>),
 }
 
@@ -70,7 +164,7 @@ impl<'a> BlockLabel<'a> {
 
     pub fn as_cow(&self) -> Cow<'a, str> {
         match self {
-            BlockLabel::StringLiteral(literal) => Cow::Owned(literal.clone()),
+            BlockLabel::StringLiteral(literal) => literal.clone(),
             BlockLabel::Identifier(ident) => ident.clone(),
         }
     }
@@ -114,7 +208,9 @@ impl<'a> crate::AsOwned for BlockLabel<'a> {
 
     fn as_owned(&self) -> Self::Output {
         match self {
-            BlockLabel::StringLiteral(string) => BlockLabel::StringLiteral(string.clone()),
+            BlockLabel::StringLiteral(string) => {
+                BlockLabel::StringLiteral(Cow::Owned(string.as_owned()))
+            }
             BlockLabel::Identifier(ident) => BlockLabel::Identifier(Cow::Owned(ident.as_owned())),
         }
     }
@@ -184,9 +280,11 @@ named!(
 );
 
 /// Blocks in a body indexed by their type and labels
+///
+/// Block types and labels are iterated in the order they first appear in the document.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Blocks<'a> {
-    blocks: HashMap<Identifier<'a>, BlockBody<'a>>,
+    blocks: IndexMap<Identifier<'a>, BlockBody<'a>>,
 }
 
 impl<'a> Blocks<'a> {
@@ -194,16 +292,11 @@ impl<'a> Blocks<'a> {
     where
         T: IntoIterator<Item = Block<'a>>,
     {
-        let mut hashmap = HashMap::new();
-        for (block_type, blocks) in blocks
-            .into_iter()
-            .group_by(|block| block.r#type.clone())
-            .into_iter()
-        {
-            let blocks = BlockBody::from_iter(blocks.map(|block| (block.labels, block.body)));
-            hashmap.insert(block_type, blocks);
-        }
-        Self { blocks: hashmap }
+        let mut result = Self {
+            blocks: IndexMap::new(),
+        };
+        result.extend(blocks);
+        result
     }
 
     pub fn append(&mut self, block: Block<'a>) {
@@ -242,11 +335,11 @@ impl<'a> Blocks<'a> {
     }
 
     /// Get an iterator over the types of blocks
-    pub fn iter(&self) -> hash_map::Iter<Identifier<'a>, BlockBody<'a>> {
+    pub fn iter(&self) -> map::Iter<Identifier<'a>, BlockBody<'a>> {
         self.blocks.iter()
     }
 
-    pub fn iter_mut(&mut self) -> hash_map::IterMut<Identifier<'a>, BlockBody<'a>> {
+    pub fn iter_mut(&mut self) -> map::IterMut<Identifier<'a>, BlockBody<'a>> {
         self.blocks.iter_mut()
     }
 
@@ -323,21 +416,21 @@ impl<'a> Blocks<'a> {
     }
 
     /// Get a set of label lengths for this body
-    pub fn label_lens(&self) -> HashMap<&str, BTreeSet<usize>> {
+    pub fn label_lens(&self) -> IndexMap<&str, BTreeSet<usize>> {
         self.blocks
             .iter()
             .map(|(ident, bodies)| (ident.borrow(), bodies.label_lens()))
             .collect()
     }
 
-    pub fn label_lens_uniform(&self) -> HashMap<&str, Option<usize>> {
+    pub fn label_lens_uniform(&self) -> IndexMap<&str, Option<usize>> {
         self.blocks
             .iter()
             .map(|(ident, bodies)| (ident.borrow(), bodies.label_lens_uniform()))
             .collect()
     }
 
-    pub fn is_label_lens_uniform(&self) -> HashMap<&str, bool> {
+    pub fn is_label_lens_uniform(&self) -> IndexMap<&str, bool> {
         self.blocks
             .iter()
             .map(|(ident, bodies)| (ident.borrow(), bodies.is_label_lens_uniform()))
@@ -347,7 +440,7 @@ impl<'a> Blocks<'a> {
 
 impl<'a> IntoIterator for Blocks<'a> {
     type Item = (Identifier<'a>, BlockBody<'a>);
-    type IntoIter = hash_map::IntoIter<Identifier<'a>, BlockBody<'a>>;
+    type IntoIter = map::IntoIter<Identifier<'a>, BlockBody<'a>>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.blocks.into_iter()
@@ -356,7 +449,7 @@ impl<'a> IntoIterator for Blocks<'a> {
 
 impl<'a, 'b> IntoIterator for &'b Blocks<'a> {
     type Item = (&'b Identifier<'a>, &'b BlockBody<'a>);
-    type IntoIter = hash_map::Iter<'b, Identifier<'a>, BlockBody<'a>>;
+    type IntoIter = map::Iter<'b, Identifier<'a>, BlockBody<'a>>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
@@ -365,7 +458,7 @@ impl<'a, 'b> IntoIterator for &'b Blocks<'a> {
 
 impl<'a, 'b> IntoIterator for &'b mut Blocks<'a> {
     type Item = (&'b Identifier<'a>, &'b mut BlockBody<'a>);
-    type IntoIter = hash_map::IterMut<'b, Identifier<'a>, BlockBody<'a>>;
+    type IntoIter = map::IterMut<'b, Identifier<'a>, BlockBody<'a>>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter_mut()
@@ -397,12 +490,12 @@ pub enum BlockBody<'a> {
     Body(Vec<Body<'a>>),
     /// Variant where there are block bodies with at least one label
     ///
-    /// You should not construct this enum by hand. It is a logic error for the `labels` HashMap
+    /// You should not construct this enum by hand. It is a logic error for the `labels` map
     /// to be empty and you can expect panics or unexpected behaviour from other functions in this
     /// library.
     Labels {
         empty: Vec<Body<'a>>,
-        labels: HashMap<BlockLabel<'a>, BlockBody<'a>>,
+        labels: IndexMap<BlockLabel<'a>, BlockBody<'a>>,
     },
 }
 
@@ -422,15 +515,15 @@ impl<'a> BlockBody<'a> {
             }
             BlockBody::Labels {
                 ref mut empty,
-                labels: ref mut hashmap,
+                labels: ref mut label_map,
             } => {
                 if labels.is_empty() {
                     empty.push(body);
                 } else {
                     let label = labels.drain(0..1).next().expect("to be some");
-                    match hashmap.entry(label) {
+                    match label_map.entry(label) {
                         Entry::Vacant(vacant) => {
-                            vacant.insert(BlockBody::Body(vec![body]));
+                            vacant.insert(BlockBody::with_labels(labels, body));
                         }
                         Entry::Occupied(mut occupied) => {
                             occupied.get_mut().append(labels, body);
@@ -450,9 +543,9 @@ impl<'a> BlockBody<'a> {
             Some((first, rest)) => match self {
                 BlockBody::Body(_) => None,
                 BlockBody::Labels {
-                    labels: ref hashmap,
+                    labels: ref label_map,
                     ..
-                } => match hashmap.get(first.as_ref()) {
+                } => match label_map.get(first.as_ref()) {
                     None => None,
                     Some(inner) => inner.get(rest),
                 },
@@ -469,9 +562,9 @@ impl<'a> BlockBody<'a> {
             Some((first, rest)) => match self {
                 BlockBody::Body(_) => None,
                 BlockBody::Labels {
-                    labels: ref mut hashmap,
+                    labels: ref mut label_map,
                     ..
-                } => match hashmap.get_mut(first.as_ref()) {
+                } => match label_map.get_mut(first.as_ref()) {
                     None => None,
                     Some(inner) => inner.get_mut(rest),
                 },
@@ -593,7 +686,7 @@ impl<'a> BlockBody<'a> {
     }
 
     /// Borrow the bodies with additional labels
-    pub fn get_labels(&self) -> Option<&HashMap<BlockLabel<'a>, BlockBody<'a>>> {
+    pub fn get_labels(&self) -> Option<&IndexMap<BlockLabel<'a>, BlockBody<'a>>> {
         match self {
             BlockBody::Body(_) => None,
             BlockBody::Labels { ref labels, .. } => Some(labels),
@@ -653,6 +746,13 @@ impl<'a> BlockBody<'a> {
         self.label_lens_uniform().is_some()
     }
 
+    /// A block body with `body` under the rest of its `labels`
+    fn with_labels(labels: Vec<BlockLabel<'a>>, body: Body<'a>) -> Self {
+        let mut block_body = Self::default();
+        block_body.append(labels, body);
+        block_body
+    }
+
     /// In place transmute of Body to Labels
     ///
     /// Must only be called when `labels` are not empty and the enum is of Body type
@@ -662,13 +762,11 @@ impl<'a> BlockBody<'a> {
             if let BlockBody::Body(bodies) = current {
                 let label = labels.drain(0..1).next().expect("to be some");
 
-                let mut hashmap = HashMap::new();
-                let mut new_body = BlockBody::default();
-                new_body.append(labels, body);
-                hashmap.insert(label, new_body);
+                let mut label_map = IndexMap::new();
+                label_map.insert(label, BlockBody::with_labels(labels, body));
                 BlockBody::Labels {
                     empty: bodies,
-                    labels: hashmap,
+                    labels: label_map,
                 }
             } else {
                 panic!("Unexpected enum variant")
@@ -709,7 +807,9 @@ impl<'a> Default for BlockBody<'a> {
 mod tests {
     use super::*;
 
-    use crate::parser::body::BodyElement;
+    use itertools::Itertools;
+
+    use crate::parser::body::{BodyElement, LazyBodyElement};
     use crate::parser::expression::Expression;
     use crate::utils::ResultUtilsString;
 
@@ -1065,4 +1165,185 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn appending_blocks_with_different_labels_keeps_all_labels() {
+        let hcl = [
+            r#"resource "security/group" "foobar" { name = "foobar" }"#,
+            r#"resource "instance" "an_instance" { name = "an_instance" }"#,
+        ];
+        let blocks: Blocks = hcl
+            .iter()
+            .map(|hcl| one_line_block(CompleteStr(hcl)).unwrap_output())
+            .collect();
+
+        let resource = blocks.get::<_, &str>("resource", &[]).unwrap();
+        assert_list_eq!(resource.label_lens(), [&2usize]);
+
+        let instance = blocks
+            .get("resource", &["instance", "an_instance"])
+            .unwrap();
+        assert!(!instance.has_further_labels());
+        assert_eq!(instance.len_blocks(), 1);
+    }
+
+    #[test]
+    fn new_labels_keep_the_rest_of_the_labels() {
+        let first = vec![BodyElement::Attribute((
+            From::from("a"),
+            Expression::from(1),
+        ))];
+        let second = vec![BodyElement::Attribute((
+            From::from("b"),
+            Expression::from(2),
+        ))];
+
+        let mut block_body = BlockBody::new();
+        block_body.append(vec![BlockLabel::from("x")], first.clone());
+        block_body.append(
+            vec![BlockLabel::from("y"), BlockLabel::from("z")],
+            second.clone(),
+        );
+
+        // The body under a new first label used to be `BlockBody::Body(vec![second])`, which
+        // dropped the label `z`
+        let mut expected = IndexMap::new();
+        expected.insert(BlockLabel::from("x"), BlockBody::Body(vec![first]));
+        expected.insert(
+            BlockLabel::from("y"),
+            BlockBody::Labels {
+                empty: vec![],
+                labels: std::iter::once((BlockLabel::from("z"), BlockBody::Body(vec![second])))
+                    .collect(),
+            },
+        );
+        assert_eq!(
+            block_body,
+            BlockBody::Labels {
+                empty: vec![],
+                labels: expected,
+            }
+        );
+        assert!(block_body.get(&["y", "z"]).is_some());
+    }
+
+    #[test]
+    fn blocks_are_iterated_in_document_order() {
+        let hcl = [
+            r#"variable "zone" { }"#,
+            r#"resource "instance" "b" { }"#,
+            r#"resource "disk" "a" { }"#,
+            r#"variable "region" { }"#,
+            r#"resource "instance" "a" { }"#,
+        ];
+        let blocks: Blocks = hcl
+            .iter()
+            .map(|hcl| one_line_block(CompleteStr(hcl)).unwrap_output())
+            .collect();
+
+        // Blocks of the same type are grouped even if they are not consecutive
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks.len_blocks(), 5);
+
+        let types: Vec<_> = blocks.iter().map(|(block_type, _)| block_type).collect();
+        assert_eq!(types, ["variable", "resource"]);
+
+        let flattened: Vec<_> = blocks
+            .flat_iter()
+            .map(|(block_type, labels, _body)| (block_type, Vec::from(labels)))
+            .collect();
+        assert_eq!(
+            flattened,
+            [
+                ("variable", vec!["zone"]),
+                ("variable", vec!["region"]),
+                ("resource", vec!["instance", "b"]),
+                ("resource", vec!["instance", "a"]),
+                ("resource", vec!["disk", "a"]),
+            ]
+        );
+    }
+
+    /// Parse the bodies of all blocks in a lazily parsed body
+    fn parse_lazy_body(body: LazyBody) -> Body {
+        body.into_iter()
+            .map(|element| match element {
+                LazyBodyElement::Attribute(attribute) => BodyElement::Attribute(attribute),
+                LazyBodyElement::Block(block) => BodyElement::Block(Block::new(
+                    block.r#type.clone(),
+                    block.labels.clone(),
+                    parse_lazy_body(block.lazy_body().unwrap()),
+                )),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lazy_blocks_are_parsed_like_blocks() {
+        let mut inputs = crate::fixtures::ALL.to_vec();
+        inputs.extend(&[
+            "a { b = 1 }\nc {}\n",
+            "a \"${b\" {\n  c = \"${ {d = 1} }\"\n  e = <<EOF\n}\nEOF\n  f {\n  }\n}\n",
+        ]);
+
+        for input in inputs {
+            let expected = crate::parse_str(input).unwrap();
+            let lazy = crate::parser::parse_str_lazy(input).unwrap();
+            for (lazy, expected) in lazy.iter().zip(&expected) {
+                if let (LazyBodyElement::Block(lazy), BodyElement::Block(expected)) =
+                    (lazy, expected)
+                {
+                    assert_eq!(&lazy.to_block().unwrap(), expected);
+                }
+            }
+            assert_eq!(parse_lazy_body(lazy), expected);
+        }
+    }
+
+    #[test]
+    fn lazy_block_bodies_are_not_parsed() {
+        let input = "a = 1\nb \"c\" {\n  d = [\n}\ne {\n  f = 2\n}\n";
+        let body = crate::parser::parse_str_lazy(input).unwrap();
+        assert_eq!(body.len(), 3);
+
+        let block = match &body[1] {
+            LazyBodyElement::Block(block) => block,
+            element => panic!("unexpected {:?}", element),
+        };
+        assert_eq!(block.r#type, "b");
+        assert_eq!(block.labels, ["c"]);
+        assert_eq!(block.body_range(), 13..22);
+        assert_eq!(block.body_source(), "\n  d = [\n");
+        assert_eq!(
+            block.body().unwrap_err().to_string(),
+            crate::parse_str(input).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn lazy_block_bodies_are_parsed_once() {
+        let input = "a {\n  b = 1\n}\n";
+        let body = crate::parser::parse_str_lazy(input).unwrap();
+        let block = match &body[0] {
+            LazyBodyElement::Block(block) => block,
+            element => panic!("unexpected {:?}", element),
+        };
+
+        let first = block.body().unwrap();
+        let second = block.body().unwrap();
+        assert!(std::ptr::eq(first, second));
+        match &crate::parse_str(input).unwrap()[0] {
+            BodyElement::Block(block) => assert_eq!(first, &block.body),
+            element => panic!("unexpected {:?}", element),
+        }
+    }
+
+    #[test]
+    fn lazy_blocks_must_be_closed() {
+        let error = crate::parser::parse_str_lazy("a {\n  b {\n}\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Syntax error at 4:1: expected `}`, found end of input"
+        );
+    }
 }
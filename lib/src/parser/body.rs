@@ -5,7 +5,7 @@ use nom::types::CompleteStr;
 use nom::{alt, call, do_parse, eof, named_attr, terminated};
 
 use crate::parser::attribute::{attribute, Attribute};
-use crate::parser::block::{block, one_line_block, Block};
+use crate::parser::block::{block, one_line_block, Block, LazyBlock};
 use crate::parser::whitespace::newline;
 
 /// A HCL document body
@@ -86,6 +86,30 @@ impl<'a> crate::AsOwned for BodyElement<'a> {
     }
 }
 
+/// A HCL document body whose blocks are parsed when they are accessed
+///
+/// See [`LazyBlock`].
+pub type LazyBody<'a> = Vec<LazyBodyElement<'a>>;
+
+/// An element of `LazyBody`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LazyBodyElement<'a> {
+    Attribute(Attribute<'a>),
+    Block(LazyBlock<'a>),
+}
+
+impl<'a> From<Attribute<'a>> for LazyBodyElement<'a> {
+    fn from(attr: Attribute<'a>) -> Self {
+        LazyBodyElement::Attribute(attr)
+    }
+}
+
+impl<'a> From<LazyBlock<'a>> for LazyBodyElement<'a> {
+    fn from(blk: LazyBlock<'a>) -> Self {
+        LazyBodyElement::Block(blk)
+    }
+}
+
 named_attr!(
     #[doc = r#"Parses a `Body` element
 
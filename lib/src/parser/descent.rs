@@ -0,0 +1,1491 @@
+//! Recursive descent parser
+//!
+//! The parser works on the [tokens](super::lexer) of the source and builds the same syntax tree
+//! as the [`nom`] parsers in the sibling modules, which are kept for parsing fragments of HCL.
+//!
+//! Newlines are significant in bodies and objects, where they terminate elements, but not in
+//! tuples, function arguments, parentheses and interpolation sequences. Comments are ignored.
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::parser::attribute::Attribute;
+use crate::parser::block::{Block, BlockLabel, LazyBlock};
+use crate::parser::body::{Body, BodyElement, LazyBody, LazyBodyElement};
+use crate::parser::events::Event;
+use crate::parser::expression::Expression;
+use crate::parser::lexer::{tokenize, Lexer, Token, TokenKind};
+use crate::parser::number::Number;
+use crate::parser::object::{Object, ObjectElementIdentifier};
+use crate::parser::operation::{BinaryOperator, Operation, UnaryOperator};
+use crate::parser::options::ParseOptions;
+use crate::parser::string::{Template, TemplatePart};
+use crate::span::{Extents, Position};
+use crate::Error;
+
+use TokenKind::*;
+
+/// Parse a HCL configuration file within the limits of `options`
+pub(crate) fn parse_str<'a>(source: &'a str, options: &ParseOptions) -> Result<Body<'a>, Error> {
+    options.check_input_size(source.len())?;
+    Parser::with_options(source, *options).body(false)
+}
+
+/// Parse a HCL configuration file within the limits of `options`, recording the byte ranges of its
+/// attributes and blocks
+pub(crate) fn parse_str_with_extents<'a>(
+    source: &'a str,
+    options: &ParseOptions,
+) -> Result<(Body<'a>, Extents), Error> {
+    options.check_input_size(source.len())?;
+    let mut parser = Parser::with_options(source, *options);
+    parser.extents = Some(Default::default());
+    let body = parser.body(false)?;
+    Ok((body, parser.extents.unwrap_or_default()))
+}
+
+/// Parse the attribute or block at `offset`, returning it and the offset after it
+pub(crate) fn parse_body_element(
+    source: &str,
+    offset: usize,
+) -> Result<(BodyElement<'_>, usize), Error> {
+    let mut parser = Parser::starting_at(source, offset);
+    let element = parser.body_element()?;
+    Ok((element, parser.last_end))
+}
+
+/// Parse a HCL configuration file without parsing the bodies of its blocks
+pub(crate) fn parse_str_lazy(source: &str) -> Result<LazyBody<'_>, Error> {
+    Parser::new(source).lazy_body(false)
+}
+
+/// Parse an expression, which must make up all of the `source`, within the limits of `options`
+pub(crate) fn parse_expression<'a>(
+    source: &'a str,
+    options: &ParseOptions,
+) -> Result<Expression<'a>, Error> {
+    options.check_input_size(source.len())?;
+    let mut parser = Parser::with_options(source, *options);
+    parser.with_newlines(false, |parser| {
+        let expression = parser.expression()?;
+        match parser.peek() {
+            None => Ok(expression),
+            Some(_) => Err(parser.unexpected("end of input")),
+        }
+    })
+}
+
+/// Parse the body of a block, which is `range` of the `source` between the braces
+pub(crate) fn parse_block_body(source: &str, range: Range<usize>) -> Result<Body<'_>, Error> {
+    block_body(source, range, |parser| parser.body(true))
+}
+
+/// Parse the body of a block without parsing the bodies of the blocks in it
+pub(crate) fn parse_block_body_lazy(
+    source: &str,
+    range: Range<usize>,
+) -> Result<LazyBody<'_>, Error> {
+    block_body(source, range, |parser| parser.lazy_body(true))
+}
+
+fn block_body<'a, T, F>(source: &'a str, range: Range<usize>, body: F) -> Result<Vec<T>, Error>
+where
+    T: From<Attribute<'a>>,
+    F: FnOnce(&mut Parser<'a>) -> Result<Vec<T>, Error>,
+{
+    let mut parser = Parser::starting_at(source, range.start);
+    if parser.eat(Newline).is_some() {
+        let body = body(&mut parser)?;
+        parser.expect(CloseBrace, "`}`")?;
+        return Ok(body);
+    }
+
+    let attribute = parser.one_line_block_attribute()?;
+    Ok(attribute.into_iter().map(T::from).collect())
+}
+
+/// Check that brackets, braces, parentheses and interpolation sequences are not nested deeper than
+/// `max_depth`, before parsing `source` with the recursive [`nom`] parsers
+pub(crate) fn check_depth(source: &str, max_depth: usize) -> Result<(), Error> {
+    let mut depth = 0usize;
+    for token in tokenize(source) {
+        match token.kind {
+            OpenBrace | OpenBracket | OpenParenthesis | TemplateInterpolation => {
+                if depth >= max_depth {
+                    let position = Position::from_offset(source, token.offset)
+                        .expect("tokens to start at character boundaries");
+                    let message = format!("nesting is deeper than {} levels", max_depth);
+                    return Err(Error::LimitExceeded { position, message });
+                }
+                depth += 1;
+            }
+            CloseBrace | CloseBracket | CloseParenthesis | TemplateSequenceEnd => {
+                depth = depth.saturating_sub(1);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Parse the body elements of a HCL configuration file one at a time
+///
+/// The source may be a prefix of the input, in which case elements which could continue past the
+/// end of the source are left to be parsed once more input is available.
+pub(crate) struct Events<'a> {
+    parser: Parser<'a>,
+    /// The number of blocks which are open
+    depth: usize,
+    /// Whether the source is the rest of the input
+    complete: bool,
+    /// Offset after the elements parsed so far
+    offset: usize,
+}
+
+impl<'a> Events<'a> {
+    pub(crate) fn new(source: &'a str, depth: usize, complete: bool) -> Self {
+        Self {
+            parser: Parser::new(source),
+            depth,
+            complete,
+            offset: 0,
+        }
+    }
+
+    /// The number of blocks which are open after the elements parsed so far
+    pub(crate) fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Offset after the elements parsed so far
+    pub(crate) fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Parse the events of the next body element
+    ///
+    /// Returns `None` at the end of the source, or if the next element might need more input.
+    pub(crate) fn next(&mut self) -> Result<Option<Vec<Event<'a>>>, Error> {
+        match self.element() {
+            Ok(Some((events, depth))) => {
+                self.depth = depth;
+                self.offset = self.parser.last_end;
+                Ok(Some(events))
+            }
+            Ok(None) => Ok(None),
+            Err(Error::SyntaxError { position, .. })
+                if !self.complete && self.might_be_cut_off(position.offset) =>
+            {
+                Ok(None)
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Whether an error at `offset` might be caused by the end of the source cutting off the
+    /// element. Newlines may be skipped before an expression, and heredocs and comments without an
+    /// end are invalid tokens which span lines.
+    fn might_be_cut_off(&mut self, offset: usize) -> bool {
+        let rest = self.parser.source.get(offset..).unwrap_or_default();
+        let parser = &mut self.parser;
+        let followed = (0..)
+            .map(|index| parser.token(index))
+            .take_while(Option::is_some)
+            .flatten()
+            .any(|token| token.offset > offset && token.kind != Newline);
+        !followed || rest.starts_with("<<") || rest.starts_with("/*")
+    }
+
+    /// The events of the next body element and the depth after it
+    fn element(&mut self) -> Result<Option<(Vec<Event<'a>>, usize)>, Error> {
+        let parser = &mut self.parser;
+        parser.skip_newlines();
+        let (events, depth) = match parser.peek_kind() {
+            None if self.depth > 0 && self.complete => return Err(parser.unexpected("`}`")),
+            None => return Ok(None),
+            Some(CloseBrace) if self.depth > 0 => {
+                parser.next();
+                (vec![Event::BlockEnd], self.depth - 1)
+            }
+            _ => {
+                let name = parser.expect(Identifier, "an attribute or a block")?.text;
+                let name = Cow::Borrowed(name);
+                if parser.eat(Equal).is_some() {
+                    let expr = parser.expression()?;
+                    (vec![Event::Attribute { name, expr }], self.depth)
+                } else {
+                    let labels = parser.block_labels()?;
+                    let start = Event::BlockStart {
+                        r#type: name,
+                        labels,
+                    };
+                    if parser.eat(Newline).is_some() {
+                        return Ok(Some((vec![start], self.depth + 1)));
+                    }
+
+                    let mut events = vec![start];
+                    if let Some((name, expr)) = parser.one_line_block_attribute()? {
+                        events.push(Event::Attribute { name, expr });
+                    }
+                    events.push(Event::BlockEnd);
+                    (events, self.depth)
+                }
+            }
+        };
+
+        match parser.peek_kind() {
+            Some(Newline) => {
+                parser.next();
+            }
+            None if !self.complete => return Ok(None),
+            None => {}
+            _ => return Err(parser.unexpected("a newline")),
+        }
+        Ok(Some((events, depth)))
+    }
+}
+
+/// Parse the top level elements of a HCL configuration file one at a time
+pub(crate) struct Elements<'a> {
+    parser: Parser<'a>,
+    /// Whether the last element parsed still has to be followed by a newline
+    after_element: bool,
+}
+
+impl<'a> Elements<'a> {
+    /// Parse the elements from `offset`, which is the start of the source or the end of an element
+    pub(crate) fn starting_at(source: &'a str, offset: usize) -> Self {
+        Self {
+            parser: Parser::starting_at(source, offset),
+            after_element: offset > 0,
+        }
+    }
+
+    /// Offset of the next element, or `None` at the end of the source
+    pub(crate) fn next_offset(&mut self) -> Result<Option<usize>, Error> {
+        let parser = &mut self.parser;
+        if self.after_element {
+            match parser.peek_kind() {
+                None | Some(Newline) => self.after_element = false,
+                _ => return Err(parser.unexpected("a newline")),
+            }
+        }
+        parser.skip_newlines();
+        Ok(parser.peek().map(|token| token.offset))
+    }
+
+    /// The next element and its byte range
+    pub(crate) fn next(&mut self) -> Result<Option<(Range<usize>, BodyElement<'a>)>, Error> {
+        let start = match self.next_offset()? {
+            Some(start) => start,
+            None => return Ok(None),
+        };
+        let element = self.parser.body_element()?;
+        self.after_element = true;
+        Ok(Some((start..self.parser.last_end, element)))
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    lexer: Lexer<'a>,
+    /// Tokens other than comments, which are tokenized as they are needed
+    tokens: Vec<Token<'a>>,
+    position: usize,
+    /// Whether newlines are returned by `peek` and `next`, or skipped
+    newlines: bool,
+    /// Offset after the last token returned by `next`
+    last_end: usize,
+    options: ParseOptions,
+    /// Depth of nesting of the element being parsed
+    depth: usize,
+    /// Number of body elements parsed
+    elements: usize,
+    /// Byte ranges of the attributes and blocks parsed, if they are recorded
+    extents: Option<Extents>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self::starting_at(source, 0)
+    }
+
+    fn with_options(source: &'a str, options: ParseOptions) -> Self {
+        Self {
+            options,
+            ..Self::new(source)
+        }
+    }
+
+    /// Parse `source` from `offset`, which must not be inside a template
+    fn starting_at(source: &'a str, offset: usize) -> Self {
+        Self {
+            source,
+            lexer: Lexer::starting_at(source, offset),
+            tokens: vec![],
+            position: 0,
+            newlines: true,
+            last_end: offset,
+            options: Default::default(),
+            depth: 0,
+            elements: 0,
+            extents: None,
+        }
+    }
+
+    /// The token at `index`, tokenizing the source up to it
+    fn token(&mut self, index: usize) -> Option<Token<'a>> {
+        while self.tokens.len() <= index {
+            let token = self.lexer.find(|token| token.kind != Comment)?;
+            self.tokens.push(token);
+        }
+        self.tokens.get(index).copied()
+    }
+
+    fn peek(&mut self) -> Option<Token<'a>> {
+        if !self.newlines {
+            self.skip_newlines();
+        }
+        self.token(self.position)
+    }
+
+    fn peek_kind(&mut self) -> Option<TokenKind> {
+        self.peek().map(|token| token.kind)
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.peek()?;
+        self.position += 1;
+        self.last_end = token.end();
+        Some(token)
+    }
+
+    /// Consume the next token if it is of the `kind`
+    fn eat(&mut self, kind: TokenKind) -> Option<Token<'a>> {
+        if self.peek_kind() == Some(kind) {
+            self.next()
+        } else {
+            None
+        }
+    }
+
+    /// Consume the next token if it is of the `kind` and follows the last token without any
+    /// whitespace in between
+    fn eat_adjacent(&mut self, kind: TokenKind) -> Option<Token<'a>> {
+        match self.token(self.position) {
+            Some(token) if token.kind == kind && token.offset == self.last_end => self.next(),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> Result<Token<'a>, Error> {
+        match self.eat(kind) {
+            Some(token) => Ok(token),
+            None => Err(self.unexpected(expected)),
+        }
+    }
+
+    fn skip_newlines(&mut self) {
+        while let Some(Token { kind: Newline, .. }) = self.token(self.position) {
+            self.position += 1;
+        }
+    }
+
+    /// Run `parse` with newlines made significant or not
+    fn with_newlines<T, F>(&mut self, newlines: bool, parse: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        let previous = std::mem::replace(&mut self.newlines, newlines);
+        let result = parse(self);
+        self.newlines = previous;
+        result
+    }
+
+    fn error(&self, offset: usize, message: String) -> Error {
+        let position = Position::from_offset(self.source, offset)
+            .expect("tokens to start at character boundaries");
+        Error::SyntaxError { position, message }
+    }
+
+    fn limit_exceeded(&self, offset: usize, message: String) -> Error {
+        let position = Position::from_offset(self.source, offset)
+            .expect("tokens to start at character boundaries");
+        Error::LimitExceeded { position, message }
+    }
+
+    /// Run `parse` one level of nesting deeper, after the token which opens the level
+    fn nested<T, F>(&mut self, parse: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        self.chained(|parser| {
+            parser.deepen()?;
+            parse(parser)
+        })
+    }
+
+    /// Run `parse`, which goes one level deeper for every link of a chain, and return to the
+    /// current depth after it
+    ///
+    /// Chains of operations and traversals are parsed in a loop, but make up trees which are as
+    /// deep as the chains are long.
+    fn chained<T, F>(&mut self, parse: F) -> Result<T, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Error>,
+    {
+        let depth = self.depth;
+        let result = parse(self);
+        self.depth = depth;
+        result
+    }
+
+    /// Go one level of nesting deeper, after the token which opens the level
+    fn deepen(&mut self) -> Result<(), Error> {
+        match self.options.max_depth {
+            Some(max_depth) if self.depth >= max_depth => {
+                let offset = match self.position.checked_sub(1) {
+                    Some(index) => self.tokens[index].offset,
+                    None => self.last_end,
+                };
+                let message = format!("nesting is deeper than {} levels", max_depth);
+                Err(self.limit_exceeded(offset, message))
+            }
+            _ => {
+                self.depth += 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Record the byte range of the element which starts at `start` and was just parsed
+    fn record_extent(&mut self, start: usize) {
+        let end = self.last_end;
+        if let Some(extents) = &mut self.extents {
+            extents.insert(start..end);
+        }
+    }
+
+    /// An error for the next token, which is not what was `expected`
+    fn unexpected(&mut self, expected: &str) -> Error {
+        let (offset, found) = match self.peek() {
+            None => (self.source.len(), "end of input".to_string()),
+            Some(Token {
+                kind: Newline,
+                offset,
+                ..
+            }) => (offset, "a newline".to_string()),
+            Some(token) => (token.offset, format!("`{}`", token.text.trim_end())),
+        };
+        self.error(offset, format!("expected {}, found {}", expected, found))
+    }
+
+    /// ```ebnf
+    /// Body = (Attribute | Block | OneLineBlock)*;
+    /// ```
+    fn body(&mut self, nested: bool) -> Result<Body<'a>, Error> {
+        self.elements(nested, Self::body_element)
+    }
+
+    /// A body whose blocks are not parsed beyond their labels
+    fn lazy_body(&mut self, nested: bool) -> Result<LazyBody<'a>, Error> {
+        self.elements(nested, Self::lazy_body_element)
+    }
+
+    /// The elements of a body, each parsed by `element`
+    fn elements<T>(
+        &mut self,
+        nested: bool,
+        element: fn(&mut Self) -> Result<T, Error>,
+    ) -> Result<Vec<T>, Error> {
+        let mut body = vec![];
+        loop {
+            self.skip_newlines();
+            match self.peek_kind() {
+                None if nested => return Err(self.unexpected("`}`")),
+                None => break,
+                Some(CloseBrace) if nested => break,
+                _ => {}
+            }
+
+            if let Some(max_body_elements) = self.options.max_body_elements {
+                if self.elements >= max_body_elements {
+                    let offset = self.peek().map_or(self.last_end, |token| token.offset);
+                    let message = format!("more than {} body elements", max_body_elements);
+                    return Err(self.limit_exceeded(offset, message));
+                }
+            }
+            self.elements += 1;
+            let start = self.peek().map_or(self.last_end, |token| token.offset);
+            body.push(element(self)?);
+            self.record_extent(start);
+            match self.peek_kind() {
+                None => {}
+                Some(Newline) => {
+                    self.next();
+                }
+                _ => return Err(self.unexpected("a newline")),
+            }
+        }
+        Ok(body)
+    }
+
+    /// ```ebnf
+    /// Attribute    = Identifier "=" Expression Newline;
+    /// Block        = Identifier (StringLit|Identifier)* "{" Newline Body "}" Newline;
+    /// OneLineBlock = Identifier (StringLit|Identifier)* "{" (Identifier "=" Expression)? "}" Newline;
+    /// ```
+    fn body_element(&mut self) -> Result<BodyElement<'a>, Error> {
+        let name = Cow::Borrowed(self.expect(Identifier, "an attribute or a block")?.text);
+        if self.eat(Equal).is_some() {
+            return Ok(BodyElement::Attribute((name, self.expression()?)));
+        }
+
+        let labels = self.block_labels()?;
+        if self.eat(Newline).is_some() {
+            let body = self.nested(|parser| parser.body(true))?;
+            self.expect(CloseBrace, "`}`")?;
+            return Ok(BodyElement::Block(Block::new(name, labels, body)));
+        }
+
+        let attribute = self.one_line_block_attribute()?;
+        Ok(BodyElement::Block(Block::new_one_line(
+            name, labels, attribute,
+        )))
+    }
+
+    /// An attribute, or a block whose body is skipped
+    fn lazy_body_element(&mut self) -> Result<LazyBodyElement<'a>, Error> {
+        let name = Cow::Borrowed(self.expect(Identifier, "an attribute or a block")?.text);
+        if self.eat(Equal).is_some() {
+            return Ok(LazyBodyElement::Attribute((name, self.expression()?)));
+        }
+
+        let labels = self.block_labels()?;
+        let start = self.last_end;
+        let end = self.skip_block_body()?;
+        self.discard_tokens();
+        Ok(LazyBodyElement::Block(LazyBlock::new(
+            name,
+            labels,
+            self.source,
+            start..end,
+        )))
+    }
+
+    /// Skip the tokens of a block body up to and including the `}`, returning its offset
+    ///
+    /// Only braces are matched, except that the labels of the blocks in the body are scanned like
+    /// the labels of any other block, since they are tokenized as templates.
+    fn skip_block_body(&mut self) -> Result<usize, Error> {
+        /// What an open brace, bracket, parenthesis or interpolation sequence belongs to
+        #[derive(PartialEq)]
+        enum Open {
+            Block,
+            Object,
+            Other,
+        }
+
+        let open_brace = self.tokens[self.position - 1];
+        let mut open = vec![Open::Block];
+        let mut element_start = false;
+        loop {
+            let token = match self.token(self.position) {
+                Some(token) => token,
+                None => return Err(self.unclosed_block_body_error(open_brace)),
+            };
+            if element_start && token.kind == Identifier && !self.at_attribute() {
+                self.next();
+                self.block_labels()?;
+                open.push(Open::Block);
+                element_start = false;
+                continue;
+            }
+
+            self.position += 1;
+            self.last_end = token.end();
+            match token.kind {
+                OpenBrace => open.push(Open::Object),
+                OpenBracket | OpenParenthesis | TemplateInterpolation => open.push(Open::Other),
+                CloseBracket | CloseParenthesis | TemplateSequenceEnd
+                    if open.last() == Some(&Open::Other) =>
+                {
+                    open.pop();
+                }
+                CloseBrace => {
+                    // Unclosed brackets and parentheses before the brace are closed with it
+                    while open.last() == Some(&Open::Other) {
+                        open.pop();
+                    }
+                    open.pop();
+                    if open.is_empty() {
+                        return Ok(token.offset);
+                    }
+                }
+                _ => {}
+            }
+            element_start = token.kind == Newline && open.last() == Some(&Open::Block);
+            if element_start {
+                self.discard_tokens();
+            }
+        }
+    }
+
+    /// The error of a block body whose `}` was not found, which is found by parsing the body
+    fn unclosed_block_body_error(&mut self, open_brace: Token<'a>) -> Error {
+        let unclosed = self.unexpected("`}`");
+        self.tokens = vec![open_brace];
+        self.position = 1;
+        self.lexer = Lexer::starting_at(self.source, open_brace.end());
+        self.last_end = open_brace.end();
+
+        let result = if self.eat(Newline).is_some() {
+            self.nested(|parser| parser.body(true))
+                .and_then(|_| self.expect(CloseBrace, "`}`"))
+                .map(|_| ())
+        } else {
+            self.one_line_block_attribute().map(|_| ())
+        };
+        result.err().unwrap_or(unclosed)
+    }
+
+    /// Whether the identifier at the next token is followed by `=`
+    fn at_attribute(&mut self) -> bool {
+        let next = self.token(self.position + 1);
+        next.map(|token| token.kind) == Some(Equal)
+    }
+
+    /// Forget the tokens which have been consumed except for the last one, so that skipping
+    /// large bodies does not keep their tokens
+    fn discard_tokens(&mut self) {
+        let consumed = self.position.saturating_sub(1);
+        self.tokens.drain(..consumed);
+        self.position -= consumed;
+    }
+
+    /// The labels of a block up to and including the `{`
+    fn block_labels(&mut self) -> Result<Vec<BlockLabel<'a>>, Error> {
+        let mut labels = vec![];
+        loop {
+            match self.peek_kind() {
+                Some(Identifier) => {
+                    let label = self.next().expect("token to be peeked").text;
+                    labels.push(BlockLabel::Identifier(Cow::Borrowed(label)));
+                }
+                Some(OpenQuote) => labels.push(BlockLabel::StringLiteral(self.string_literal()?)),
+                Some(OpenBrace) => break,
+                _ => return Err(self.unexpected("`=`, a block label or `{`")),
+            }
+        }
+        self.next();
+        Ok(labels)
+    }
+
+    /// The optional attribute of a one line block up to and including the `}`
+    fn one_line_block_attribute(&mut self) -> Result<Option<Attribute<'a>>, Error> {
+        let attribute = match self.eat(Identifier) {
+            Some(attribute) => {
+                self.expect(Equal, "`=`")?;
+                let expression = self.expression()?;
+                self.record_extent(attribute.offset);
+                Some((Cow::Borrowed(attribute.text), expression))
+            }
+            None => None,
+        };
+        self.expect(CloseBrace, "`}`")?;
+        Ok(attribute)
+    }
+
+    /// A quoted string without interpolations, which cannot span multiple lines
+    ///
+    /// The lexer tokenizes the string as a quoted template. If the string contains `${`, the
+    /// tokens do not end where the string does, and the rest of the source is tokenized again.
+    fn string_literal(&mut self) -> Result<Cow<'a, str>, Error> {
+        let open = self.next().expect("token to be peeked");
+        let bytes = self.source.as_bytes();
+        let mut close = open.end();
+        loop {
+            match bytes.get(close) {
+                Some(b'"') => break,
+                Some(b'\\') => close += 2,
+                Some(b'\r') | Some(b'\n') | None => {
+                    let close = close.min(self.source.len());
+                    let found = if close == self.source.len() {
+                        "end of input"
+                    } else {
+                        "a newline"
+                    };
+                    let message = format!("expected `\"`, found {}", found);
+                    return Err(self.error(close, message));
+                }
+                Some(_) => close += 1,
+            }
+        }
+
+        let literal = &self.source[open.end()..close];
+        let literal = self.unescape(literal, open.end(), true, false)?;
+
+        let close_quote = (self.position..self.position + 2).find(|index| {
+            self.token(*index)
+                .is_some_and(|token| token.offset == close)
+        });
+        match close_quote {
+            Some(index) if self.tokens[index].kind == CloseQuote => self.position = index + 1,
+            _ => {
+                self.tokens.truncate(self.position);
+                self.lexer = Lexer::starting_at(self.source, close + 1);
+            }
+        }
+        self.last_end = close + 1;
+        Ok(literal)
+    }
+
+    /// ```ebnf
+    /// Expression = ExprTerm | Operation | Conditional;
+    /// Conditional = Expression "?" Expression ":" Expression;
+    /// ```
+    fn expression(&mut self) -> Result<Expression<'a>, Error> {
+        let condition = self.binary_expression(1)?;
+        if self.eat(Question).is_none() {
+            return Ok(condition);
+        }
+
+        let true_result = self.nested(Self::expression)?;
+        self.expect(Colon, "`:`")?;
+        let false_result = self.nested(Self::expression)?;
+        Ok(Expression::Conditional(Box::new((
+            condition,
+            true_result,
+            false_result,
+        ))))
+    }
+
+    /// Parse binary operations with operators of at least `min_precedence` by precedence climbing
+    ///
+    /// Every operator of a chain like `a + b + c` is one level of nesting.
+    fn binary_expression(&mut self, min_precedence: u8) -> Result<Expression<'a>, Error> {
+        self.chained(|parser| {
+            let mut lhs = parser.unary_expression()?;
+            loop {
+                let operator = match parser.peek_kind().and_then(binary_operator) {
+                    Some(operator) if operator.precedence() >= min_precedence => operator,
+                    _ => return Ok(lhs),
+                };
+                parser.next();
+                parser.deepen()?;
+
+                let rhs = parser.binary_expression(operator.precedence() + 1)?;
+                lhs = Expression::Operation(Box::new(Operation::Binary(operator, lhs, rhs)));
+            }
+        })
+    }
+
+    fn unary_expression(&mut self) -> Result<Expression<'a>, Error> {
+        let operator = match self.peek_kind() {
+            Some(Minus) if !self.at_signed_number() => UnaryOperator::Negate,
+            Some(Bang) => UnaryOperator::Not,
+            _ => return self.postfix_expression(),
+        };
+        self.next();
+
+        let operand = self.nested(Self::unary_expression)?;
+        Ok(Expression::Operation(Box::new(Operation::Unary(
+            operator, operand,
+        ))))
+    }
+
+    /// Whether the next tokens are a sign directly followed by a number, which make up a signed
+    /// number literal
+    fn at_signed_number(&mut self) -> bool {
+        match self.peek() {
+            Some(sign) if sign.kind == Minus || sign.kind == Plus => {
+                match self.token(self.position + 1) {
+                    Some(number) => number.kind == TokenKind::Number && number.offset == sign.end(),
+                    None => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// An expression term followed by any number of `Index` and `GetAttr` traversals
+    ///
+    /// Every traversal is one level of nesting.
+    fn postfix_expression(&mut self) -> Result<Expression<'a>, Error> {
+        self.chained(|parser| {
+            let mut expression = parser.expression_term()?;
+            loop {
+                if parser.eat_adjacent(Dot).is_some() {
+                    parser.deepen()?;
+                    let name = parser.expect(Identifier, "an attribute name")?;
+                    expression =
+                        Expression::GetAttr(Box::new(expression), Cow::Borrowed(name.text));
+                } else if parser.eat_adjacent(OpenBracket).is_some() {
+                    parser.deepen()?;
+                    let index = parser.with_newlines(false, |parser| {
+                        let index = parser.expression()?;
+                        parser.expect(CloseBracket, "`]`")?;
+                        Ok(index)
+                    })?;
+                    expression = Expression::Index(Box::new(expression), Box::new(index));
+                } else {
+                    return Ok(expression);
+                }
+            }
+        })
+    }
+
+    fn expression_term(&mut self) -> Result<Expression<'a>, Error> {
+        if self.at_signed_number() {
+            let sign = self.next().expect("token to be peeked");
+            let number = self.next().expect("token to be peeked");
+            let literal = &self.source[sign.offset..number.end()];
+            return Ok(Expression::Number(Number::from_literal(literal)));
+        }
+
+        // Objects and parenthesized expressions may start on the next line
+        let after_newlines = (self.position..)
+            .map(|index| self.token(index).map(|token| token.kind))
+            .find(|kind| *kind != Some(Newline))
+            .flatten();
+        if after_newlines == Some(OpenBrace) || after_newlines == Some(OpenParenthesis) {
+            self.skip_newlines();
+        }
+
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return Err(self.unexpected("an expression")),
+        };
+        match token.kind {
+            TokenKind::Number => {
+                self.next();
+                Ok(Expression::Number(Number::from_literal(token.text)))
+            }
+            Identifier => {
+                self.next();
+                match token.text {
+                    "null" => Ok(Expression::Null),
+                    "true" => Ok(Expression::Boolean(true)),
+                    "false" => Ok(Expression::Boolean(false)),
+                    name => {
+                        if self.eat_adjacent(OpenParenthesis).is_some() {
+                            let arguments = self.list(CloseParenthesis, "`)`")?;
+                            Ok(Expression::FunctionCall(Cow::Borrowed(name), arguments))
+                        } else {
+                            Ok(Expression::Variable(Cow::Borrowed(name)))
+                        }
+                    }
+                }
+            }
+            OpenQuote => {
+                self.next();
+                Ok(Expression::from_template(self.quoted_template()?))
+            }
+            HeredocStart => {
+                self.next();
+                Ok(Expression::from_template(self.heredoc_template(token)?))
+            }
+            OpenBracket => {
+                self.next();
+                Ok(Expression::Tuple(self.list(CloseBracket, "`]`")?))
+            }
+            OpenBrace => {
+                self.next();
+                Ok(Expression::Object(self.nested(Self::object)?))
+            }
+            OpenParenthesis => {
+                self.next();
+                self.nested(|parser| {
+                    parser.with_newlines(false, |parser| {
+                        let expression = parser.expression()?;
+                        parser.expect(CloseParenthesis, "`)`")?;
+                        Ok(expression)
+                    })
+                })
+            }
+            _ => Err(self.unexpected("an expression")),
+        }
+    }
+
+    /// Comma separated expressions, with an optional trailing comma, up to the `close` token
+    fn list(&mut self, close: TokenKind, expected: &str) -> Result<Vec<Expression<'a>>, Error> {
+        self.nested(|parser| {
+            parser.with_newlines(false, |parser| {
+                let mut list = vec![];
+                if parser.eat(Comma).is_some() {
+                    parser.expect(close, expected)?;
+                    return Ok(list);
+                }
+                while parser.eat(close).is_none() {
+                    list.push(parser.expression()?);
+                    if parser.eat(Comma).is_none() {
+                        parser.expect(close, &format!("`,` or {}", expected))?;
+                        break;
+                    }
+                }
+                Ok(list)
+            })
+        })
+    }
+
+    /// ```ebnf
+    /// object = "{" (
+    ///     (objectelem ("," objectelem)* ","?)?
+    /// ) "}";
+    /// objectelem = (Identifier | Expression) "=" Expression;
+    /// ```
+    ///
+    /// Elements may also be separated by newlines.
+    fn object(&mut self) -> Result<Object<'a>, Error> {
+        self.with_newlines(true, |parser| {
+            let mut object = vec![];
+            loop {
+                parser.skip_newlines();
+                if parser.eat(CloseBrace).is_some() {
+                    return Ok(object);
+                }
+
+                let next_is_equal = match parser.token(parser.position + 1) {
+                    Some(token) => token.kind == Equal,
+                    None => false,
+                };
+                let key = match parser.peek() {
+                    Some(Token {
+                        kind: Identifier,
+                        text,
+                        ..
+                    }) if next_is_equal => {
+                        parser.next();
+                        ObjectElementIdentifier::Identifier(Cow::Borrowed(text))
+                    }
+                    Some(token) => {
+                        parser.expression()?;
+                        let expression = &parser.source[token.offset..parser.last_end];
+                        ObjectElementIdentifier::Expression(Cow::Borrowed(expression))
+                    }
+                    None => return Err(parser.unexpected("`}`")),
+                };
+                parser.expect(Equal, "`=`")?;
+                object.push((key, parser.expression()?));
+
+                match parser.peek_kind() {
+                    Some(Comma) | Some(Newline) => {
+                        parser.next();
+                    }
+                    Some(CloseBrace) => {}
+                    _ => return Err(parser.unexpected("`,`, a newline or `}`")),
+                }
+            }
+        })
+    }
+
+    /// `"${" Expression "}"`, after the `${`
+    fn interpolation(&mut self) -> Result<Expression<'a>, Error> {
+        self.nested(|parser| {
+            parser.with_newlines(false, |parser| {
+                let expression = parser.expression()?;
+                parser.expect(TemplateSequenceEnd, "`}`")?;
+                Ok(expression)
+            })
+        })
+    }
+
+    /// A quoted template, after the opening quote
+    fn quoted_template(&mut self) -> Result<Template<'a>, Error> {
+        let mut template = vec![];
+        loop {
+            let token = match self.next() {
+                Some(token) => token,
+                None => return Err(self.unexpected("`\"`")),
+            };
+            match token.kind {
+                CloseQuote => return Ok(template),
+                TemplateLiteral => {
+                    let literal = self.unescape(token.text, token.offset, true, true)?;
+                    template.push(TemplatePart::Literal(literal));
+                }
+                TemplateInterpolation => {
+                    template.push(TemplatePart::Interpolation(self.interpolation()?));
+                }
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected("`\"`"));
+                }
+            }
+        }
+    }
+
+    /// A heredoc template, after the `start` token
+    ///
+    /// The content of the template does not include the newline before the end marker. The lines
+    /// of an indented heredoc, which starts with `<<-`, are unindented by up to as many spaces as
+    /// the end marker is indented.
+    fn heredoc_template(&mut self, start: Token<'a>) -> Result<Template<'a>, Error> {
+        let mut parts = vec![];
+        let end = loop {
+            let token = match self.token(self.position) {
+                Some(token) => token,
+                None => return Err(self.unexpected("the end of the heredoc")),
+            };
+            self.position += 1;
+            self.last_end = token.end();
+            match token.kind {
+                HeredocEnd => break token,
+                TemplateLiteral => parts.push(HeredocPart::Literal(token)),
+                TemplateInterpolation => {
+                    parts.push(HeredocPart::Interpolation(self.interpolation()?))
+                }
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected("the end of the heredoc"));
+                }
+            }
+        };
+
+        if let Some(max_heredoc_length) = self.options.max_heredoc_length {
+            let content = &self.source[start.end()..end.offset];
+            let content = content.strip_suffix('\n').unwrap_or(content);
+            let content = content.strip_suffix('\r').unwrap_or(content);
+            if content.len() > max_heredoc_length {
+                let message = format!("heredoc is longer than {} bytes", max_heredoc_length);
+                return Err(self.limit_exceeded(start.offset, message));
+            }
+        }
+
+        let indentation = if start.text.starts_with("<<-") {
+            end.text.len() - end.text.trim_start_matches([' ', '\t']).len()
+        } else {
+            0
+        };
+        let last = parts.len().wrapping_sub(1);
+        let mut template = Vec::with_capacity(parts.len());
+        for (index, part) in parts.into_iter().enumerate() {
+            let token = match part {
+                HeredocPart::Literal(token) => token,
+                HeredocPart::Interpolation(expression) => {
+                    template.push(TemplatePart::Interpolation(expression));
+                    continue;
+                }
+            };
+
+            let mut text = token.text;
+            if index == last {
+                text = text.strip_suffix('\n').unwrap_or(text);
+                text = text.strip_suffix('\r').unwrap_or(text);
+            }
+            let at_line_start =
+                token.offset == start.end() || self.source.as_bytes()[token.offset - 1] == b'\n';
+            let literal = unindent(text, indentation, at_line_start);
+            let literal = match literal {
+                Cow::Borrowed(literal) => self.unescape(literal, token.offset, false, true)?,
+                Cow::Owned(literal) => Cow::Owned(
+                    self.unescape(&literal, token.offset, false, true)?
+                        .into_owned(),
+                ),
+            };
+            if !literal.is_empty() {
+                template.push(TemplatePart::Literal(literal));
+            }
+        }
+        Ok(template)
+    }
+
+    /// Process the escape sequences of quoted strings if `escapes` is set, and replace `$${` with
+    /// `${` in templates if `template` is set. `offset` is the position of the literal in the
+    /// source, for errors.
+    fn unescape<'s>(
+        &self,
+        literal: &'s str,
+        offset: usize,
+        escapes: bool,
+        template: bool,
+    ) -> Result<Cow<'s, str>, Error> {
+        let special = |c: char| (template && c == '$') || (escapes && c == '\\');
+        let mut index = match literal.find(special) {
+            Some(index) => index,
+            None => return Ok(Cow::Borrowed(literal)),
+        };
+
+        let mut unescaped = String::with_capacity(literal.len());
+        let mut rest = literal;
+        loop {
+            unescaped.push_str(&rest[..index]);
+            rest = &rest[index..];
+            if rest.starts_with("$${") {
+                unescaped.push_str("${");
+                rest = &rest[3..];
+            } else if rest.starts_with('\\') {
+                let sequence = &rest[1..];
+                let escape_offset = offset + (literal.len() - rest.len());
+                let (character, len) = match escape(sequence) {
+                    Ok(escape) => escape,
+                    Err(EscapeError::InvalidCodePoint(len)) => {
+                        return Err(Error::InvalidUnicodeCodePoint(sequence[..len].to_string()));
+                    }
+                    Err(EscapeError::Invalid) => {
+                        let sequence = sequence.chars().next().map_or(0, char::len_utf8);
+                        let sequence = &rest[..1 + sequence];
+                        let message = format!("invalid escape sequence `{}`", sequence);
+                        return Err(self.error(escape_offset, message));
+                    }
+                };
+                unescaped.push(character);
+                rest = &sequence[len..];
+            } else {
+                unescaped.push('$');
+                rest = &rest[1..];
+            }
+
+            index = match rest.find(special) {
+                Some(index) => index,
+                None => {
+                    unescaped.push_str(rest);
+                    return Ok(Cow::Owned(unescaped));
+                }
+            };
+        }
+    }
+}
+
+fn binary_operator(kind: TokenKind) -> Option<BinaryOperator> {
+    let operator = match kind {
+        Star => BinaryOperator::Multiply,
+        Slash => BinaryOperator::Divide,
+        Percent => BinaryOperator::Modulo,
+        Plus => BinaryOperator::Add,
+        Minus => BinaryOperator::Subtract,
+        Greater => BinaryOperator::Greater,
+        GreaterEqual => BinaryOperator::GreaterEqual,
+        Less => BinaryOperator::Less,
+        LessEqual => BinaryOperator::LessEqual,
+        EqualEqual => BinaryOperator::Equal,
+        NotEqual => BinaryOperator::NotEqual,
+        And => BinaryOperator::And,
+        Or => BinaryOperator::Or,
+        _ => return None,
+    };
+    Some(operator)
+}
+
+/// Remove up to `indentation` spaces from the start of every line in `text`. The first line is
+/// only unindented if `text` starts at the beginning of a line.
+fn unindent(text: &str, indentation: usize, at_line_start: bool) -> Cow<'_, str> {
+    if indentation == 0 {
+        return Cow::Borrowed(text);
+    }
+
+    let mut unindented = String::with_capacity(text.len());
+    for (index, line) in text.split('\n').enumerate() {
+        if index > 0 {
+            unindented.push('\n');
+        }
+        if index > 0 || at_line_start {
+            let spaces = line.bytes().take(indentation).take_while(|c| *c == b' ');
+            unindented.push_str(&line[spaces.count()..]);
+        } else {
+            unindented.push_str(line);
+        }
+    }
+    Cow::Owned(unindented)
+}
+
+/// A part of a heredoc template before its literals are unindented
+enum HeredocPart<'a> {
+    Literal(Token<'a>),
+    Interpolation(Expression<'a>),
+}
+
+enum EscapeError {
+    /// The escape sequence is not recognized
+    Invalid,
+    /// The escape sequence of the length does not encode a Unicode scalar value
+    InvalidCodePoint(usize),
+}
+
+/// Decode the escape sequence at the start of `sequence`, which follows a backslash. Returns the
+/// character and the length of the sequence.
+///
+/// Escape sequences follow [C++](https://en.cppreference.com/w/cpp/language/escape), like the
+/// HashiCorp [Go implementation](https://github.com/hashicorp/hcl/blob/ef8a98b0bbce4a65b5aa4c368430a80ddc533168/hcl/scanner/scanner.go#L513).
+fn escape(sequence: &str) -> Result<(char, usize), EscapeError> {
+    let bytes = sequence.as_bytes();
+    let character = match bytes.first() {
+        Some(b'a') => '\x07',
+        Some(b'b') => '\x08',
+        Some(b'f') => '\x0c',
+        Some(b'n') => '\n',
+        Some(b'r') => '\r',
+        Some(b't') => '\t',
+        Some(b'v') => '\x0b',
+        Some(b'\\') => '\\',
+        Some(b'"') => '"',
+        Some(b'?') => '?',
+        Some(b'0'..=b'7') => {
+            let digits = bytes
+                .iter()
+                .take(3)
+                .take_while(|c| matches!(c, b'0'..=b'7'));
+            return code_point(&sequence[..digits.count()], 8, 0);
+        }
+        Some(b'x') => return hex_code_point(sequence, 2),
+        Some(b'u') => return hex_code_point(sequence, 4),
+        Some(b'U') => return hex_code_point(sequence, 8),
+        _ => return Err(EscapeError::Invalid),
+    };
+    Ok((character, 1))
+}
+
+/// Decode `\x`, `\u` or `\U` followed by up to `max_digits` hexadecimal digits
+fn hex_code_point(sequence: &str, max_digits: usize) -> Result<(char, usize), EscapeError> {
+    let digits = sequence[1..]
+        .bytes()
+        .take(max_digits)
+        .take_while(u8::is_ascii_hexdigit)
+        .count();
+    if digits == 0 {
+        return Err(EscapeError::InvalidCodePoint(1));
+    }
+    code_point(&sequence[1..=digits], 16, 1)
+}
+
+/// Decode `digits` in `radix`, which follow a `prefix` of that length in the escape sequence
+fn code_point(digits: &str, radix: u32, prefix: usize) -> Result<(char, usize), EscapeError> {
+    let len = prefix + digits.len();
+    u32::from_str_radix(digits, radix)
+        .ok()
+        .and_then(std::char::from_u32)
+        .map(|character| (character, len))
+        .ok_or(EscapeError::InvalidCodePoint(len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use nom::types::CompleteStr;
+
+    use crate::{fixtures, parse_str};
+
+    /// Valid snippets, which cover the syntax
+    const SNIPPETS: &[&str] = &[
+        "",
+        "\n\n",
+        "a = 1",
+        "a = 1\n",
+        "a = 1 /* comment */",
+        "# comment\na = 1 // comment\n",
+        "a-b = c-d\n",
+        "é = _a\n",
+        "a = -1\nb = +1.5e3\nc = - 1\nd = -x\ne = 1 -1\nf = 1 - -1\ng = .5\nh = 1.\n",
+        "a = !true\nb = ! true\nc = !a == b\n",
+        "a = 1 + 2 * 3 - 4 / 5 % 6\n",
+        "a = 1 < 2 && 3 >= 4 || 5 != 6 == !false\n",
+        "a = b ? c : d ? e : f\n",
+        "a = b ? c ? d : e : f\n",
+        "a = x.y[0].z[\"k\"][1 + 2]\n",
+        "a = f()\nb = f(1)\nc = f(1, 2,)\nd = f(1\n,2)\ne = f(,)\n",
+        "a = max(1, min(2, 3)).x\n",
+        "a = (1 + 2) * 3\nb = ((true))\n",
+        "a = null\nb = true\nc = false\n",
+        "a = []\nb = [,]\nc = [1,]\nd = [\n  1,\n  [2, 3],\n]\n",
+        "a = {}\nb = {b=1,}\nc = {\n  d = 1\n\n  e = 2,\n  f = 3\n}\n",
+        "a = { \"k\" = 1, (k) = 2, true = 3 }\n",
+        "a = { b = { c = [1, { d = 2 }] } }\n",
+        r#"a = "" "#,
+        r#"a = "b\"c\\d\n\t\?\xff\251\uD000\U29000""#,
+        "a = \"multi\nline\"\n",
+        r#"a = "${b} and ${c.d[0]}-$${e} %{f}""#,
+        r#"a = "${ "${b}" }""#,
+        r#"a = "${ { b = "}" }.b }""#,
+        "a = <<EOF\nnew\nline\nEOF\n",
+        "a = <<EOF\nEOF\n",
+        "a = <<EOF\n\nEOF\n",
+        "a = <<EOF\r\nwindows\r\nEOF\r\n",
+        "a = <<EOF\n${b} $${c} \\n\nEOF\n",
+        "a = <<-EOF\n  x\n   y\n  EOF\n",
+        "a = <<-EOF\n    x ${b} y\n    z\n    EOF\n",
+        "a = [<<EOF\nx\nEOF\n, 1]\n",
+        "test {}\ntest { }\ntest {\n}\n",
+        "test { a = 1 }\n",
+        "test \"\" {}\n",
+        "test \"${a}\" \"$${b}\" \"\\n\" c {\n  d = 1\n}\n",
+        "a {\n  b \"c\" {\n    d = 1\n  }\n  e = 2\n}\nf = 3\n",
+        "a { # comment\n  b = 1 // comment\n}\n",
+        "test \"${a\" \"b${\" {}\n",
+        "a =\n{ b =\n(1) }\n",
+    ];
+
+    /// Invalid snippets and the positions and messages of their errors
+    const INVALID_SNIPPETS: &[(&str, &str)] = &[
+        ("a = 1 b = 2", "1:7: expected a newline, found `b`"),
+        (
+            "a\n= 1",
+            "1:2: expected `=`, a block label or `{`, found a newline",
+        ),
+        ("a = \n1", "1:5: expected an expression, found a newline"),
+        ("a = 1 +\n2", "1:8: expected an expression, found a newline"),
+        ("a = [1 2]", "1:8: expected `,` or `]`, found `2`"),
+        (
+            "a = { b = 1 c = 2 }",
+            "1:13: expected `,`, a newline or `}`, found `c`",
+        ),
+        ("a = (1", "1:7: expected `)`, found end of input"),
+        ("a = true(1)", "1:9: expected a newline, found `(`"),
+        ("a = x .y", "1:7: expected a newline, found `.`"),
+        ("test {\n a = 1 }", "2:8: expected a newline, found `}`"),
+        ("test {\n a = 1\n", "3:1: expected `}`, found end of input"),
+        (
+            "test\n{}",
+            "1:5: expected `=`, a block label or `{`, found a newline",
+        ),
+        ("test \"a\nb\" {}", "1:8: expected `\"`, found a newline"),
+        ("a = \"b", "1:7: expected `\"`, found end of input"),
+        ("a = \"${b\"", "1:9: expected `}`, found `\"`"),
+        ("a = \"\\q\"", "1:6: invalid escape sequence `\\q`"),
+        (
+            "a = <<EOF\nb\nEOF",
+            "1:5: expected an expression, found `<<EOF`",
+        ),
+        ("a = 1 & 2", "1:7: expected a newline, found `&`"),
+        ("1 = 2", "1:1: expected an attribute or a block, found `1`"),
+    ];
+
+    /// Parse with the `nom` parsers
+    fn parse_with_nom(source: &str) -> Option<Body<'_>> {
+        match crate::parser::config_file(CompleteStr(source)) {
+            Ok((remaining, body)) if remaining.is_empty() => Some(body),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn fixtures_are_parsed_like_the_nom_parser() {
+        for fixture in fixtures::ALL {
+            let expected = parse_with_nom(fixture).expect("fixture to be valid");
+            assert_eq!(parse_str(fixture).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn snippets_are_parsed_like_the_nom_parser() {
+        for snippet in SNIPPETS {
+            println!("Testing {:?}", snippet);
+            let expected = parse_with_nom(snippet).expect("snippet to be valid");
+            assert_eq!(parse_str(snippet).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn numbers_are_split_like_the_nom_parser() {
+        for literal in &[
+            "1",
+            "-1",
+            "+3.14",
+            "1.",
+            ".5",
+            "-.5",
+            "1.e4",
+            "1.2E+4",
+            "-1.234e-12",
+        ] {
+            let expected = crate::parser::number::number(CompleteStr(literal))
+                .unwrap()
+                .1;
+            let source = format!("a = {}", literal);
+            let actual = match parse_str(&source).unwrap().remove(0) {
+                BodyElement::Attribute((_, Expression::Number(number))) => number,
+                element => panic!("unexpected {:?}", element),
+            };
+            assert_eq!(format!("{:?}", actual), format!("{:?}", expected));
+        }
+    }
+
+    #[test]
+    fn strings_without_escapes_are_borrowed() {
+        let source = "block \"label\" \"escaped\\t\" {\n  a = \"value\"\n}\n";
+        let block = match parse_str(source).unwrap().remove(0) {
+            BodyElement::Block(block) => block,
+            element => panic!("unexpected {:?}", element),
+        };
+        assert!(matches!(
+            block.labels[0],
+            BlockLabel::StringLiteral(Cow::Borrowed("label"))
+        ));
+        assert!(matches!(
+            block.labels[1],
+            BlockLabel::StringLiteral(Cow::Owned(_))
+        ));
+        assert_eq!(block.labels[1], "escaped\t");
+
+        assert!(matches!(
+            block.body[0],
+            BodyElement::Attribute((_, Expression::String(Cow::Borrowed("value"))))
+        ));
+    }
+
+    #[test]
+    fn invalid_snippets_are_rejected() {
+        for (snippet, message) in INVALID_SNIPPETS {
+            println!("Testing {:?}", snippet);
+            assert!(parse_with_nom(snippet).is_none());
+            let error = parse_str(snippet).unwrap_err();
+            assert_eq!(error.to_string(), format!("Syntax error at {}", message));
+        }
+    }
+
+    /// Parse lazily, and then parse the bodies of all blocks
+    fn parse_lazily(source: &str) -> Result<Body<'_>, Error> {
+        fn parse_bodies(body: LazyBody<'_>) -> Result<Body<'_>, Error> {
+            body.into_iter()
+                .map(|element| match element {
+                    LazyBodyElement::Attribute(attribute) => Ok(BodyElement::Attribute(attribute)),
+                    LazyBodyElement::Block(block) => Ok(BodyElement::Block(Block::new(
+                        block.r#type.clone(),
+                        block.labels.clone(),
+                        parse_bodies(block.lazy_body()?)?,
+                    ))),
+                })
+                .collect()
+        }
+        parse_bodies(parse_str_lazy(source)?)
+    }
+
+    #[test]
+    fn lazy_parses_agree_with_parses() {
+        let mut inputs: Vec<String> = fixtures::ALL.iter().map(|s| s.to_string()).collect();
+        inputs.push("a {\n  b \"${c\" {\n  }\n}\n".to_string());
+        let snippets = SNIPPETS
+            .iter()
+            .chain(INVALID_SNIPPETS.iter().map(|(snippet, _)| snippet));
+        for snippet in snippets {
+            inputs.push(snippet.to_string());
+            // Skip the snippet in the bodies of blocks with labels which look like templates
+            inputs.push(format!("a \"${{b\" {{\n{}\n}}\n", snippet));
+            inputs.push(format!("a {{\n  b \"${{c\" {{\n{}\n  }}\n}}\n", snippet));
+        }
+
+        for input in &inputs {
+            println!("Testing {:?}", input);
+            match parse_str(input) {
+                Ok(expected) => assert_eq!(parse_lazily(input).unwrap(), expected),
+                Err(expected) => assert_eq!(
+                    parse_lazily(input).unwrap_err().to_string(),
+                    expected.to_string()
+                ),
+            }
+        }
+    }
+
+    #[test]
+    fn skipped_block_bodies_do_not_keep_their_tokens() {
+        let source = format!("a {{\n{}}}\nb = 1\n", "  c = [1, 2, 3]\n".repeat(1000));
+        let mut parser = Parser::new(&source);
+        assert_eq!(parser.lazy_body(false).unwrap().len(), 2);
+        assert!(parser.tokens.len() < 10);
+    }
+
+    #[test]
+    fn invalid_unicode_code_points_are_rejected() {
+        let error = parse_str(r#"a = "\UD800""#).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid Unicode Code Points \\UD800");
+    }
+}
@@ -0,0 +1,315 @@
+//! Pull parser for large HCL documents
+//!
+//! [`EventReader`] reads a document incrementally from an IO stream and yields an [`Event`] for
+//! each attribute and for the start and end of each block, without building the body of the
+//! document. Only the text of the element being parsed is kept in memory, so that very large
+//! documents can be scanned for specific blocks.
+//!
+//! ```rust
+//! use ferrous_chloride::parser::events::{Event, EventReader};
+//!
+//! let input = r#"
+//! resource "instance" "foo" {
+//!   name = "foo"
+//! }
+//!
+//! resource "instance" "bar" {
+//!   name = "bar"
+//! }
+//! "#;
+//!
+//! let mut labels = vec![];
+//! for event in EventReader::new(input.as_bytes()) {
+//!     if let Event::BlockStart { r#type, labels: block_labels } = event.unwrap() {
+//!         if r#type == "resource" {
+//!             labels.push(block_labels[1].as_str().to_string());
+//!         }
+//!     }
+//! }
+//! assert_eq!(labels, ["foo", "bar"]);
+//! ```
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+
+use crate::parser::block::BlockLabel;
+use crate::parser::descent::Events;
+use crate::parser::expression::Expression;
+use crate::parser::identifier::Identifier;
+use crate::span::Position;
+use crate::{AsOwned, Error};
+
+/// Number of bytes read from the stream at a time
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A part of a HCL body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// The start of a block, which is followed by the events of its body and a
+    /// [`BlockEnd`](Event::BlockEnd)
+    BlockStart {
+        r#type: Identifier<'a>,
+        labels: Vec<BlockLabel<'a>>,
+    },
+    /// An attribute
+    Attribute {
+        name: Identifier<'a>,
+        expr: Expression<'a>,
+    },
+    /// The end of the innermost open block
+    BlockEnd,
+}
+
+impl<'a> AsOwned for Event<'a> {
+    type Output = Event<'static>;
+
+    fn as_owned(&self) -> Self::Output {
+        match self {
+            Event::BlockStart { r#type, labels } => Event::BlockStart {
+                r#type: Cow::Owned(r#type.as_owned()),
+                labels: labels.as_owned(),
+            },
+            Event::Attribute { name, expr } => Event::Attribute {
+                name: Cow::Owned(name.as_owned()),
+                expr: expr.as_owned(),
+            },
+            Event::BlockEnd => Event::BlockEnd,
+        }
+    }
+}
+
+/// Iterator over the events of a HCL document read from an IO stream
+///
+/// The stream is read in chunks, so you do not need to apply your own buffering. Iteration stops
+/// after the first error.
+pub struct EventReader<R> {
+    reader: R,
+    /// Input which has not been parsed yet
+    buffer: Vec<u8>,
+    chunk_size: usize,
+    /// Whether the stream has been read to the end
+    eof: bool,
+    /// Offset and number of lines of the input before the buffer
+    offset: usize,
+    lines: usize,
+    /// The number of blocks which are open
+    depth: usize,
+    events: VecDeque<Event<'static>>,
+    error: Option<Error>,
+    done: bool,
+}
+
+impl<R: Read> EventReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: vec![],
+            chunk_size: CHUNK_SIZE,
+            eof: false,
+            offset: 0,
+            lines: 0,
+            depth: 0,
+            events: VecDeque::new(),
+            error: None,
+            done: false,
+        }
+    }
+
+    /// Parse the elements in the buffer, or read more input if there are none
+    fn advance(&mut self) -> Result<(), Error> {
+        let complete = self.eof;
+        let source = match std::str::from_utf8(&self.buffer) {
+            Ok(source) => source,
+            // A character might be cut off at the end of the buffer
+            Err(error) if !complete && error.error_len().is_none() => {
+                std::str::from_utf8(&self.buffer[..error.valid_up_to()])?
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        let mut events = Events::new(source, self.depth, complete);
+        let result = loop {
+            match events.next() {
+                Ok(Some(parsed)) => self.events.extend(parsed.iter().map(AsOwned::as_owned)),
+                Ok(None) => break Ok(()),
+                Err(error) => break Err(self.relocate(error)),
+            }
+        };
+        let consumed = events.offset();
+        self.depth = events.depth();
+
+        self.lines += self.buffer[..consumed]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count();
+        self.offset += consumed;
+        self.buffer.drain(..consumed);
+
+        result?;
+        if consumed == 0 {
+            if complete {
+                self.done = true;
+            } else {
+                self.fill()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read at least a chunk of input, or as much as is in the buffer, until the end of the stream
+    fn fill(&mut self) -> Result<(), Error> {
+        let start = self.buffer.len();
+        let end = start + self.chunk_size.max(start);
+        self.buffer.resize(end, 0);
+
+        let mut length = start;
+        while length < end {
+            match self.reader.read(&mut self.buffer[length..]) {
+                Ok(0) => {
+                    self.eof = true;
+                    break;
+                }
+                Ok(read) => length += read,
+                Err(ref error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => {
+                    self.buffer.truncate(start);
+                    return Err(error.into());
+                }
+            }
+        }
+        self.buffer.truncate(length);
+        Ok(())
+    }
+
+    /// Make the position of an error in the buffer relative to the start of the stream
+    ///
+    /// The buffer always starts at the start of a line.
+    fn relocate(&self, error: Error) -> Error {
+        let relocate = |position: Position| Position {
+            offset: self.offset + position.offset,
+            line: self.lines + position.line,
+            column: position.column,
+        };
+        match error {
+            Error::SyntaxError { position, message } => Error::SyntaxError {
+                position: relocate(position),
+                message,
+            },
+            Error::LimitExceeded { position, message } => Error::LimitExceeded {
+                position: relocate(position),
+                message,
+            },
+            error => error,
+        }
+    }
+}
+
+impl<R: Read> Iterator for EventReader<R> {
+    type Item = Result<Event<'static>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                return Some(Ok(event));
+            }
+            if let Some(error) = self.error.take() {
+                return Some(Err(error));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(error) = self.advance() {
+                self.error = Some(error);
+                self.done = true;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixtures;
+    use crate::parser::body::{Body, BodyElement};
+
+    /// The events of a parsed body
+    fn body_events<'a>(body: &Body<'a>, events: &mut Vec<Event<'a>>) {
+        for element in body {
+            match element {
+                BodyElement::Attribute((name, expr)) => events.push(Event::Attribute {
+                    name: name.clone(),
+                    expr: expr.clone(),
+                }),
+                BodyElement::Block(block) => {
+                    events.push(Event::BlockStart {
+                        r#type: block.r#type.clone(),
+                        labels: block.labels.clone(),
+                    });
+                    body_events(&block.body, events);
+                    events.push(Event::BlockEnd);
+                }
+            }
+        }
+    }
+
+    fn read_events(input: &str, chunk_size: usize) -> Result<Vec<Event<'static>>, Error> {
+        let mut reader = EventReader::new(input.as_bytes());
+        reader.chunk_size = chunk_size;
+        reader.collect()
+    }
+
+    #[test]
+    fn events_are_read_like_the_parsed_body() {
+        let mut inputs = fixtures::ALL.to_vec();
+        inputs.extend(&[
+            "",
+            "a { b = 1 }\nc {}\n",
+            "a = <<EOF\n  foo\nEOF\nb = \"ß ${c} ∂\"\n",
+            "a = [\n  1,\n  2,\n]\nb = {\n  c = 3\n}\n",
+            "a \"b\" {\n  c {\n    d = 1\n  }\n}",
+        ]);
+
+        for input in inputs {
+            let mut expected = vec![];
+            body_events(&crate::parse_str(input).unwrap(), &mut expected);
+
+            for chunk_size in &[1, 2, 3, 7, 64, CHUNK_SIZE] {
+                println!("Testing {:?} in chunks of {}", input, chunk_size);
+                assert_eq!(read_events(input, *chunk_size).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn errors_are_positioned_in_the_stream() {
+        for input in &[
+            "a = 1\nb = 2 c\n",
+            "a {\n  b = 1\n  c = [\n    1\n    2\n  ]\n}\n",
+            "a {\n  b = 1\n",
+            "a = 1\n}\n",
+            "a = 1\nb = \"\\xff",
+        ] {
+            let expected = crate::parse_str(input).unwrap_err().to_string();
+            for chunk_size in &[1, 5, CHUNK_SIZE] {
+                let actual = read_events(input, *chunk_size).unwrap_err().to_string();
+                assert_eq!(actual, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn memory_is_bounded_by_the_size_of_elements() {
+        let block = "resource \"instance\" \"foo\" {\n  name = \"foo\"\n}\n";
+        let input = block.repeat(10_000);
+
+        let mut reader = EventReader::new(input.as_bytes());
+        let mut count = 0;
+        while let Some(event) = reader.next() {
+            event.unwrap();
+            assert!(reader.buffer.capacity() <= 2 * CHUNK_SIZE);
+            count += 1;
+        }
+        assert_eq!(count, 30_000);
+    }
+}
@@ -6,15 +6,17 @@ use std::borrow::Cow;
 use std::iter::FromIterator;
 
 use nom::types::CompleteStr;
-use nom::{alt_complete, call, do_parse, named, tag};
+use nom::{alt_complete, call, char, delimited, do_parse, named, opt, tag, terminated, IResult};
 
 use crate::constants::*;
-use crate::parser::boolean::boolean;
-use crate::parser::null::null;
+use crate::parser::identifier::{identifier, Identifier};
 use crate::parser::number::{number, Number};
 use crate::parser::object::{object, Object, ObjectElementIdentifier};
-use crate::parser::string::string;
+use crate::parser::operation::{operation, Operation};
+use crate::parser::string::{heredoc_template, quoted_template, Template, TemplatePart};
 use crate::parser::tuple::{tuple, Tuple};
+use crate::parser::whitespace::inline_whitespace;
+use crate::parser::{descent, ParseOptions};
 use crate::Error;
 
 /// An Expression
@@ -22,8 +24,8 @@ use crate::Error;
 /// ```enbf
 /// Expression = (
 ///     ExprTerm |
-///     Operation |  # Not supported
-///     Conditional # Not supported
+///     Operation |
+///     Conditional
 /// );
 ///
 /// ExprTerm = (
@@ -32,10 +34,10 @@ use crate::Error;
 ///     TemplateExpr |
 ///     VariableExpr |
 ///     FunctionCall |
-///     ForExpr |
+///     ForExpr | # Not supported
 ///     ExprTerm Index |
 ///     ExprTerm GetAttr |
-///     ExprTerm Splat |
+///     ExprTerm Splat | # Not supported
 ///     "(" Expression ")"
 /// );
 ///
@@ -45,9 +47,21 @@ use crate::Error;
 ///   "false" |
 ///   "null"
 /// );
+///
+/// VariableExpr = Identifier;
+/// FunctionCall = Identifier "(" Arguments ")";
+/// Arguments = (
+///     () |
+///     (Expression ("," Expression)* ("," | "...")?)
+/// );
+/// Index = "[" Expression "]";
+/// GetAttr = "." Identifier;
+/// Conditional = Expression "?" Expression ":" Expression;
 /// ```
 ///
 /// - Numeric literals represent values of type number.
+/// - Variables, function calls, operations and templates with interpolations are kept as they are
+///   parsed. They can be evaluated with an [evaluation context](crate::eval::Context).
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Expression<'a> {
     /// A `null` HCL expression, expressed literally
@@ -62,6 +76,23 @@ pub enum Expression<'a> {
     Tuple(Tuple<'a>),
     /// A HCL object (map)
     Object(Object<'a>),
+    /// A string template with interpolations, like `"${name}-suffix"`
+    ///
+    /// Templates without interpolations are parsed as [`Expression::String`].
+    Template(Template<'a>),
+    /// A reference to a variable, like `var`
+    Variable(Identifier<'a>),
+    /// An attribute of an object, like `var.name`
+    GetAttr(Box<Expression<'a>>, Identifier<'a>),
+    /// An element of a tuple or an object, like `list[0]`
+    Index(Box<Expression<'a>>, Box<Expression<'a>>),
+    /// A function call, like `max(1, 2)`
+    FunctionCall(Identifier<'a>, Vec<Expression<'a>>),
+    /// A unary or binary operation, like `1 + 2`
+    Operation(Box<Operation<'a>>),
+    /// A conditional expression with a condition, a true result and a false result, like
+    /// `enabled ? 1 : 0`
+    Conditional(Box<(Expression<'a>, Expression<'a>, Expression<'a>)>),
 }
 
 impl<'a> Expression<'a> {
@@ -71,12 +102,15 @@ impl<'a> Expression<'a> {
     ///
     /// In general, this method should not be used. Prefer to use
     /// [`parse_str`](crate::parser::parse_str) to parse a HCL configuration file instead.
+    ///
+    /// The string is parsed with the default [`ParseOptions`](crate::parser::ParseOptions).
     pub fn parse(s: &'a str) -> Result<Self, Error> {
-        let (remaining, expr) = expression(CompleteStr(s)).map_err(|e| Error::from_err_str(&e))?;;
-        if !remaining.is_empty() {
-            return Err(Error::UnexpectedRemainingInput(remaining.to_string()));
-        }
-        Ok(expr)
+        Self::parse_with_options(s, &Default::default())
+    }
+
+    /// Parse a string as a HCL expression within the limits of `options`
+    pub fn parse_with_options(s: &'a str, options: &ParseOptions) -> Result<Self, Error> {
+        descent::parse_expression(s, options)
     }
 
     /// Convenience method to create a new Tuple Expression variant from an iterator of Expressions
@@ -97,6 +131,40 @@ impl<'a> Expression<'a> {
         Expression::Object(iterator.into_iter().map(|(k, v)| (k.into(), v)).collect())
     }
 
+    /// Create a String Expression variant if the template has no interpolations, or a Template
+    /// variant otherwise
+    pub fn from_template(template: Template<'a>) -> Self {
+        if template.iter().all(TemplatePart::is_literal) {
+            let mut literals = template.into_iter().filter_map(TemplatePart::into_literal);
+            match (literals.next(), literals.next()) {
+                (None, _) => Expression::String(Cow::Borrowed("")),
+                (Some(literal), None) => Expression::String(literal),
+                (Some(first), Some(second)) => {
+                    let mut string = first.into_owned();
+                    string.push_str(&second);
+                    literals.for_each(|literal| string.push_str(&literal));
+                    Expression::String(Cow::Owned(string))
+                }
+            }
+        } else {
+            Expression::Template(template)
+        }
+    }
+
+    /// Whether the expression is a literal value or a collection of literal values, which do not
+    /// need to be evaluated
+    pub fn is_literal(&self) -> bool {
+        match self {
+            Expression::Null
+            | Expression::Number(_)
+            | Expression::Boolean(_)
+            | Expression::String(_) => true,
+            Expression::Tuple(tuple) => tuple.iter().all(Expression::is_literal),
+            Expression::Object(object) => object.iter().all(|(_, value)| value.is_literal()),
+            _ => false,
+        }
+    }
+
     /// Get the name of the Expression variant as a string.
     pub fn variant_name(&self) -> &'static str {
         match self {
@@ -106,6 +174,13 @@ impl<'a> Expression<'a> {
             Expression::String(_) => STRING,
             Expression::Tuple(_) => TUPLE,
             Expression::Object(_) => OBJECT,
+            Expression::Template(_) => TEMPLATE,
+            Expression::Variable(_) => VARIABLE,
+            Expression::GetAttr(..) => GET_ATTR,
+            Expression::Index(..) => INDEX,
+            Expression::FunctionCall(..) => FUNCTION_CALL,
+            Expression::Operation(_) => OPERATION,
+            Expression::Conditional(_) => CONDITIONAL,
         }
     }
 }
@@ -121,6 +196,28 @@ impl<'a> crate::AsOwned for Expression<'a> {
             Expression::String(string) => Expression::String(Cow::Owned(string.to_string())),
             Expression::Tuple(tup) => Expression::Tuple(tup.as_owned()),
             Expression::Object(obj) => Expression::Object(obj.as_owned()),
+            Expression::Template(template) => Expression::Template(template.as_owned()),
+            Expression::Variable(name) => Expression::Variable(Cow::Owned(name.to_string())),
+            Expression::GetAttr(expr, name) => {
+                Expression::GetAttr(Box::new(expr.as_owned()), Cow::Owned(name.to_string()))
+            }
+            Expression::Index(expr, index) => {
+                Expression::Index(Box::new(expr.as_owned()), Box::new(index.as_owned()))
+            }
+            Expression::FunctionCall(name, arguments) => {
+                Expression::FunctionCall(Cow::Owned(name.to_string()), arguments.as_owned())
+            }
+            Expression::Operation(operation) => {
+                Expression::Operation(Box::new(operation.as_owned()))
+            }
+            Expression::Conditional(conditional) => {
+                let (condition, true_result, false_result) = &**conditional;
+                Expression::Conditional(Box::new((
+                    condition.as_owned(),
+                    true_result.as_owned(),
+                    false_result.as_owned(),
+                )))
+            }
         }
     }
 }
@@ -159,6 +256,7 @@ impl_from_expr_type!(Number, f32);
 impl_from_expr_type!(Number, f64);
 impl_from_expr_type!(Boolean, bool);
 impl_from_expr_type!(String, Cow<'a, str>);
+impl_from_expr_type!(String, String);
 impl_from_expr_type!(Tuple, Vec<Expression<'a>>);
 
 impl<'a> From<()> for Expression<'a> {
@@ -184,33 +282,113 @@ named!(
     )
 );
 
+// "(" Arguments ")"
 named!(
-    pub expression(CompleteStr) -> Expression,
+    function_arguments(CompleteStr) -> Vec<Expression>,
+    delimited!(
+        char!('('),
+        whitespace!(separated_list!(char!(','), expression)),
+        terminated!(whitespace!(opt!(char!(','))), char!(')'))
+    )
+);
+
+/// Parse an expression that starts with an identifier: the literals `null`, `true` and `false`,
+/// a function call or a variable
+fn identifier_expression(input: CompleteStr) -> IResult<CompleteStr, Expression, u32> {
+    let (remaining, name) = identifier(input)?;
+    match &*name {
+        "null" => Ok((remaining, Expression::Null)),
+        "true" => Ok((remaining, Expression::Boolean(true))),
+        "false" => Ok((remaining, Expression::Boolean(false))),
+        _ => match function_arguments(remaining) {
+            Ok((remaining, arguments)) => {
+                Ok((remaining, Expression::FunctionCall(name, arguments)))
+            }
+            Err(nom::Err::Error(_)) => Ok((remaining, Expression::Variable(name))),
+            Err(e) => Err(e),
+        },
+    }
+}
+
+named!(
+    pub expression_term(CompleteStr) -> Expression,
     alt_complete!(
-        // LiteralValue -> "null"
-        call!(null) => { |_| Expression::Null }
         // LiteralValue -> NumericLit
-        | call!(number) => { |v| From::from(v) }
-        // LiteralValue -> "true" | "false"
-        | call!(boolean) => { |v| From::from(v) }
+        call!(number) => { |v| From::from(v) }
+        // LiteralValue -> "null" | "true" | "false"
+        // VariableExpr
+        // FunctionCall
+        | call!(identifier_expression)
         // TemplateExpr
         // https://github.com/hashicorp/hcl2/blob/master/hcl/hclsyntax/spec.md#template-expressions
-        | string => { |v| From::from(v) }
+        | quoted_template => { Expression::from_template }
+        | heredoc_template => { Expression::from_template }
         // CollectionValue -> tuple
         | tuple => { |v| From::from(v) }
         // CollectionValue -> object
         | object => { |obj| Expression::Object(obj) }
-        // VariableExpr
-        // FunctionCall
         // ForExpr
-        // ExprTerm Index
-        // ExprTerm GetAttr
-        // ExprTerm Splat
         // "(" Expression ")"
         | call!(bracket_expression)
     )
 );
 
+/// Parse an expression term followed by any number of `Index` and `GetAttr` traversals
+pub fn postfix_expression(input: CompleteStr) -> IResult<CompleteStr, Expression, u32> {
+    let (mut input, mut expr) = expression_term(input)?;
+
+    loop {
+        // ExprTerm GetAttr
+        if let Ok((remaining, name)) = do_parse!(input, char!('.') >> name: identifier >> (name)) {
+            expr = Expression::GetAttr(Box::new(expr), name);
+            input = remaining;
+            continue;
+        }
+        // ExprTerm Index
+        if let Ok((remaining, index)) = delimited!(
+            input,
+            char!('['),
+            whitespace!(call!(expression)),
+            char!(']')
+        ) {
+            expr = Expression::Index(Box::new(expr), Box::new(index));
+            input = remaining;
+            continue;
+        }
+        // ExprTerm Splat
+        break;
+    }
+
+    Ok((input, expr))
+}
+
+// Expression "?" Expression ":" Expression
+named!(
+    conditional_results(CompleteStr) -> (Expression, Expression),
+    do_parse!(
+        call!(inline_whitespace)
+        >> char!('?')
+        >> true_result: inline_whitespace!(call!(expression))
+        >> char!(':')
+        >> call!(inline_whitespace)
+        >> false_result: call!(expression)
+        >> (true_result, false_result)
+    )
+);
+
+/// Parse an expression
+pub fn expression(input: CompleteStr) -> IResult<CompleteStr, Expression, u32> {
+    let (input, condition) = operation(input)?;
+    match conditional_results(input) {
+        Ok((remaining, (true_result, false_result))) => Ok((
+            remaining,
+            Expression::Conditional(Box::new((condition, true_result, false_result))),
+        )),
+        Err(nom::Err::Error(_)) => Ok((input, condition)),
+        Err(e) => Err(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -295,4 +473,113 @@ EOF
             assert_eq!(actual_value, *expected_value);
         }
     }
+
+    #[test]
+    fn expressions_that_have_to_be_evaluated_are_parsed_successfully() {
+        use crate::parser::operation::BinaryOperator;
+
+        let variable = |name| Expression::Variable(Cow::Borrowed(name));
+        let test_cases = [
+            ("nullable", variable("nullable"), ""),
+            ("true_value ", variable("true_value"), " "),
+            (
+                "var.list[0]",
+                Expression::Index(
+                    Box::new(Expression::GetAttr(
+                        Box::new(variable("var")),
+                        Cow::Borrowed("list"),
+                    )),
+                    Box::new(Expression::from(0)),
+                ),
+                "",
+            ),
+            (
+                "max(1, [x],\n)",
+                Expression::FunctionCall(
+                    Cow::Borrowed("max"),
+                    vec![
+                        Expression::from(1),
+                        Expression::new_tuple(vec![variable("x")]),
+                    ],
+                ),
+                "",
+            ),
+            (
+                "f()",
+                Expression::FunctionCall(Cow::Borrowed("f"), vec![]),
+                "",
+            ),
+            (
+                "a ? b : c ? d : e",
+                Expression::Conditional(Box::new((
+                    variable("a"),
+                    variable("b"),
+                    Expression::Conditional(Box::new((
+                        variable("c"),
+                        variable("d"),
+                        variable("e"),
+                    ))),
+                ))),
+                "",
+            ),
+            (
+                r#""port ${port + 1}!""#,
+                Expression::Template(vec![
+                    TemplatePart::Literal(Cow::Borrowed("port ")),
+                    TemplatePart::Interpolation(Expression::Operation(Box::new(
+                        Operation::Binary(
+                            BinaryOperator::Add,
+                            variable("port"),
+                            Expression::from(1),
+                        ),
+                    ))),
+                    TemplatePart::Literal(Cow::Borrowed("!")),
+                ]),
+                "",
+            ),
+            (
+                r#""${upper("a")}""#,
+                Expression::Template(vec![TemplatePart::Interpolation(Expression::FunctionCall(
+                    Cow::Borrowed("upper"),
+                    vec![Expression::from("a")],
+                ))]),
+                "",
+            ),
+            (r#""$${literal}""#, Expression::from("${literal}"), ""),
+            (
+                "<<EOF\nhello ${name}\nEOF\n",
+                Expression::Template(vec![
+                    TemplatePart::Literal(Cow::Borrowed("hello ")),
+                    TemplatePart::Interpolation(variable("name")),
+                ]),
+                "\n",
+            ),
+        ];
+
+        for (input, expected_value, expected_remaining) in test_cases.iter() {
+            println!("Testing {}", input);
+            let (remaining, actual_value) = expression(CompleteStr(input)).unwrap();
+            assert_eq!(&remaining.0, expected_remaining);
+            assert_eq!(actual_value, *expected_value);
+        }
+    }
+
+    #[test]
+    fn parsing_rejects_deep_nesting() {
+        let input = format!("{}1{}", "[".repeat(200_000), "]".repeat(200_000));
+        match Expression::parse(&input) {
+            Err(Error::LimitExceeded { position, .. }) => assert_eq!(position.offset, 64),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+
+        let options = ParseOptions::new().max_depth(Some(2));
+        assert!(Expression::parse_with_options("[[1]]", &options).is_ok());
+        assert!(Expression::parse_with_options("[[[1]]]", &options).is_err());
+    }
+
+    #[test]
+    fn parsing_rejects_remaining_input() {
+        assert!(Expression::parse("1 2").is_err());
+        assert!(Expression::parse("[1]]").is_err());
+    }
 }
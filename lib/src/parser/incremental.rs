@@ -0,0 +1,342 @@
+//! Incremental parsing for editors
+//!
+//! A [`Tree`] keeps the source of a document together with its top level body elements and their
+//! byte ranges. When the source is edited, only the elements the edit touches are parsed again.
+//! Parsing continues past them until an element starts where an element started before the
+//! edit, after which the old elements are reused.
+//!
+//! ```rust
+//! use ferrous_chloride::parser::incremental::{Edit, Tree};
+//!
+//! let mut tree = Tree::parse("a = 1\nb = 2\nc = 3\n").unwrap();
+//! let changed = tree.edit(&Edit::new(10..11, "42")).unwrap();
+//!
+//! assert_eq!(tree.source(), "a = 1\nb = 42\nc = 3\n");
+//! assert_eq!(changed, [6..12]);
+//! assert_eq!(tree.elements()[2].range, 13..18);
+//! ```
+use std::ops::Range;
+
+use crate::parser::body::{Body, BodyElement};
+use crate::parser::descent::Elements;
+use crate::parser::lexer::{Lexer, TokenKind};
+use crate::{AsOwned, Error};
+
+/// A text edit, which replaces a byte range of the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn new<S: Into<String>>(range: Range<usize>, text: S) -> Self {
+        Self {
+            range,
+            text: text.into(),
+        }
+    }
+}
+
+/// A top level body element and its byte range in the source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Element {
+    pub range: Range<usize>,
+    pub element: BodyElement<'static>,
+}
+
+/// A parsed document which can be edited
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    source: String,
+    elements: Vec<Element>,
+}
+
+impl Tree {
+    /// Parse the `source` of a document
+    pub fn parse<S: Into<String>>(source: S) -> Result<Self, Error> {
+        let source = source.into();
+        let mut parser = Elements::starting_at(&source, 0);
+        let mut elements = vec![];
+        while let Some((range, element)) = parser.next()? {
+            elements.push(Element {
+                range,
+                element: element.as_owned(),
+            });
+        }
+
+        Ok(Self { source, elements })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn elements(&self) -> &[Element] {
+        &self.elements
+    }
+
+    /// Copy the elements into a [`Body`]
+    pub fn to_body(&self) -> Body<'static> {
+        self.elements
+            .iter()
+            .map(|element| element.element.clone())
+            .collect()
+    }
+
+    /// Apply `edit` to the source and parse the elements it affects again
+    ///
+    /// Returns the byte ranges of the elements which were parsed again in the edited source.
+    /// If the edited source cannot be parsed, the tree is left unchanged.
+    pub fn edit(&mut self, edit: &Edit) -> Result<Vec<Range<usize>>, Error> {
+        let Range { start, end } = edit.range;
+        if start > end || !self.source.is_char_boundary(start) || !self.source.is_char_boundary(end)
+        {
+            return Err(Error::InvalidEdit(format!(
+                "{:?} is not a range of character boundaries in the source",
+                edit.range
+            )));
+        }
+
+        let mut source = String::with_capacity(self.source.len() - (end - start) + edit.text.len());
+        source.push_str(&self.source[..start]);
+        source.push_str(&edit.text);
+        source.push_str(&self.source[end..]);
+        // Offset in the edited source of an offset after the edit
+        let shift = |offset: usize| offset - end + start + edit.text.len();
+
+        // Elements which end before the edit are not affected by it, unless there is no newline
+        // between them and the edit, which could then extend the last of them. An element which
+        // ends where the edit starts could be extended by it as well.
+        let mut first = self
+            .elements
+            .iter()
+            .position(|element| element.range.end >= start)
+            .unwrap_or(self.elements.len());
+        if let Some(previous) = first.checked_sub(1) {
+            let separated = Lexer::starting_at(&self.source, self.elements[previous].range.end)
+                .take_while(|token| token.end() <= start)
+                .any(|token| token.kind == TokenKind::Newline);
+            if !separated {
+                first = previous;
+            }
+        }
+        let offset = match first.checked_sub(1) {
+            Some(previous) => self.elements[previous].range.end,
+            None => 0,
+        };
+
+        let mut parser = Elements::starting_at(&source, offset);
+        let mut parsed = vec![];
+        let mut reused = first;
+        let reused = loop {
+            let offset = match parser.next_offset()? {
+                Some(offset) => offset,
+                None => break self.elements.len(),
+            };
+
+            // Elements which start after the edit can be reused if an element starts there
+            while let Some(element) = self.elements.get(reused) {
+                if element.range.start > end && shift(element.range.start) >= offset {
+                    break;
+                }
+                reused += 1;
+            }
+            match self.elements.get(reused) {
+                Some(element) if shift(element.range.start) == offset => break reused,
+                _ => {}
+            }
+
+            match parser.next()? {
+                Some((range, element)) => parsed.push(Element {
+                    range,
+                    element: element.as_owned(),
+                }),
+                None => break self.elements.len(),
+            }
+        };
+
+        let changed = parsed.iter().map(|element| element.range.clone()).collect();
+        for element in &mut self.elements[reused..] {
+            element.range = shift(element.range.start)..shift(element.range.end);
+        }
+        self.elements.splice(first..reused, parsed);
+        self.source = source;
+        Ok(changed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixtures;
+
+    fn apply(source: &str, edit: &Edit) -> String {
+        let mut source = source.to_string();
+        source.replace_range(edit.range.clone(), &edit.text);
+        source
+    }
+
+    #[test]
+    fn edits_are_parsed_like_the_edited_source() {
+        let edits = [
+            Edit::new(0..0, "x = 1\n"),
+            Edit::new(0..1, ""),
+            Edit::new(2..3, "="),
+            Edit::new(5..6, ""),
+            Edit::new(5..5, " {"),
+            Edit::new(6..6, "# "),
+            Edit::new(6..6, "/* "),
+            Edit::new(9..9, "\n}\n"),
+            Edit::new(10..20, ""),
+            Edit::new(12..12, "\"${"),
+            Edit::new(20..20, "<<EOF\n"),
+            Edit::new(20..21, "\n\n"),
+        ];
+
+        for source in fixtures::ALL {
+            let tree = Tree::parse(*source).unwrap();
+            for edit in edits.iter().filter(|edit| edit.range.end <= source.len()) {
+                let edited = apply(source, edit);
+                println!("Testing {:?} on {:?}", edit, source);
+
+                let mut actual = tree.clone();
+                match crate::parse_str(&edited) {
+                    Ok(expected) => {
+                        actual.edit(edit).unwrap();
+                        assert_eq!(actual.source(), edited);
+                        assert_eq!(actual.to_body(), expected.as_owned());
+                        assert_eq!(actual, Tree::parse(edited.clone()).unwrap());
+                    }
+                    Err(expected) => {
+                        let error = actual.edit(edit).unwrap_err();
+                        assert_eq!(error.to_string(), expected.to_string());
+                        assert_eq!(actual, tree);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn elements_before_the_edit_on_the_same_line_are_parsed() {
+        for (source, edit) in &[
+            ("a = 1 \nb = 2\n", Edit::new(6..6, "+ 2")),
+            ("a = 1 # c\nb = 2\n", Edit::new(6..7, "+")),
+            ("a = 1 /*\n*/\nb = 2\n", Edit::new(11..11, " + 2")),
+        ] {
+            let edited = apply(source, edit);
+            let mut tree = Tree::parse(*source).unwrap();
+            tree.edit(edit).unwrap();
+            assert_eq!(tree.to_body(), crate::parse_str(&edited).unwrap());
+            assert_eq!(tree, Tree::parse(edited).unwrap());
+        }
+    }
+
+    #[test]
+    fn random_edits_are_parsed_like_the_edited_source() {
+        let texts = [
+            "",
+            " ",
+            "\n",
+            "\n\n",
+            "x",
+            "1",
+            "+ 2",
+            "= ",
+            "#",
+            " # c",
+            "//",
+            "/*",
+            "*/",
+            "{",
+            "}",
+            "{\n",
+            "\n}",
+            "[",
+            "]",
+            "\"",
+            "${",
+            "<<EOF\n",
+            "\nEOF\n",
+            "a = 1\n",
+            "b {\n  c = 2\n}\n",
+        ];
+        // xorshift, so that failures can be reproduced
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut random = |bound: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % bound as u64) as usize
+        };
+
+        for source in fixtures::ALL {
+            let mut tree = Tree::parse(*source).unwrap();
+            for _ in 0..500 {
+                // Edits at the ends of lines might extend the elements before them
+                let length = tree.source().len() + 1;
+                let newlines: Vec<_> = tree.source().match_indices('\n').collect();
+                let start = match random(2) {
+                    0 if !newlines.is_empty() => newlines[random(newlines.len())].0,
+                    _ => random(length),
+                };
+                let end = (start + random(8)).min(length - 1);
+                let source = tree.source();
+                if !source.is_char_boundary(start) || !source.is_char_boundary(end) {
+                    continue;
+                }
+                let edit = Edit::new(start..end, texts[random(texts.len())]);
+                let edited = apply(tree.source(), &edit);
+                println!("Testing {:?} on {:?}", edit, tree.source());
+
+                let previous = tree.clone();
+                match crate::parse_str(&edited) {
+                    Ok(expected) => {
+                        tree.edit(&edit).unwrap();
+                        assert_eq!(tree.to_body(), expected.as_owned());
+                        assert_eq!(tree, Tree::parse(edited).unwrap());
+                    }
+                    Err(expected) => {
+                        let error = tree.edit(&edit).unwrap_err();
+                        assert_eq!(error.to_string(), expected.to_string());
+                        assert_eq!(tree, previous);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn only_affected_elements_are_parsed() {
+        let mut tree = Tree::parse("a = 1\nb {\n  c = 2\n}\n\nd = 3\n").unwrap();
+
+        assert_eq!(tree.edit(&Edit::new(16..17, "20")).unwrap(), [6..20]);
+        assert_eq!(tree.edit(&Edit::new(21..21, "e = 4\n")).unwrap(), [21..26]);
+        // An element right after the edit might be joined with the text before it
+        assert_eq!(tree.edit(&Edit::new(0..6, "")).unwrap(), [0..14]);
+        assert_eq!(tree.source(), "b {\n  c = 20\n}\ne = 4\n\nd = 3\n");
+
+        let ranges: Vec<_> = tree
+            .elements()
+            .iter()
+            .map(|element| element.range.clone())
+            .collect();
+        assert_eq!(ranges, [0..14, 15..20, 22..27]);
+        assert_eq!(tree, Tree::parse(tree.source()).unwrap());
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn edits_must_be_ranges_of_the_source() {
+        let mut tree = Tree::parse("a = \"ß\"\n").unwrap();
+        for range in &[3..2, 6..7, 8..10] {
+            match tree.edit(&Edit::new(range.clone(), "")) {
+                Err(Error::InvalidEdit(_)) => {}
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+    }
+}
@@ -0,0 +1,596 @@
+//! Tokenizer for HCL source
+//!
+//! The lexer splits HCL source into [`Token`]s, which is the first step of
+//! [`parse_str`](crate::parser::parse_str). Tokens can also be used on their own, for example to
+//! highlight HCL source, without building a syntax tree.
+//!
+//! Spaces and tabs separate tokens and are skipped, while newlines and comments are kept as
+//! tokens because newlines terminate attributes and blocks. Every token borrows its text from the
+//! source and knows its byte range in the source.
+//!
+//! Templates make tokenizing context sensitive: the text of a quoted or heredoc template is
+//! literal, except for interpolation sequences like `${name}`, which contain expressions again.
+//! The lexer keeps a stack of the templates and interpolation sequences it is in, and a `}` closes
+//! an interpolation sequence only if it does not close a `{` opened within the sequence.
+//!
+//! Tokenizing never fails. Characters that cannot start a token are returned as
+//! [`TokenKind::Invalid`] tokens, and parsing reports them as syntax errors.
+//!
+//! ```rust
+//! use ferrous_chloride::parser::lexer::{tokenize, TokenKind};
+//!
+//! let tokens: Vec<_> = tokenize(r#"name = "${var}" # comment"#)
+//!     .map(|token| (token.kind, token.text))
+//!     .collect();
+//!
+//! assert_eq!(
+//!     tokens,
+//!     [
+//!         (TokenKind::Identifier, "name"),
+//!         (TokenKind::Equal, "="),
+//!         (TokenKind::OpenQuote, "\""),
+//!         (TokenKind::TemplateInterpolation, "${"),
+//!         (TokenKind::Identifier, "var"),
+//!         (TokenKind::TemplateSequenceEnd, "}"),
+//!         (TokenKind::CloseQuote, "\""),
+//!         (TokenKind::Comment, "# comment"),
+//!     ]
+//! );
+//! ```
+use std::ops::Range;
+
+use unic_ucd_ident::{is_id_continue, is_id_start};
+
+/// The kind of a [`Token`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// An identifier or a keyword, like `resource` or `true`
+    Identifier,
+    /// A number literal without a sign, like `1.5e3`
+    Number,
+    /// `"` opening a quoted template
+    OpenQuote,
+    /// `"` closing a quoted template
+    CloseQuote,
+    /// Literal text of a template, with escape sequences as they are written
+    TemplateLiteral,
+    /// `${` opening an interpolation sequence
+    TemplateInterpolation,
+    /// `}` closing an interpolation sequence
+    TemplateSequenceEnd,
+    /// `<<EOF` or `<<-EOF` opening a heredoc template, including the newline after it
+    HeredocStart,
+    /// The marker closing a heredoc template, including its indentation
+    HeredocEnd,
+    /// A line comment without the newline ending it, or an inline comment
+    Comment,
+    /// `\n` or `\r\n`
+    Newline,
+    /// `{`
+    OpenBrace,
+    /// `}`
+    CloseBrace,
+    /// `[`
+    OpenBracket,
+    /// `]`
+    CloseBracket,
+    /// `(`
+    OpenParenthesis,
+    /// `)`
+    CloseParenthesis,
+    /// `,`
+    Comma,
+    /// `.`
+    Dot,
+    /// `=`
+    Equal,
+    /// `?`
+    Question,
+    /// `:`
+    Colon,
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+    /// `*`
+    Star,
+    /// `/`
+    Slash,
+    /// `%`
+    Percent,
+    /// `==`
+    EqualEqual,
+    /// `!=`
+    NotEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessEqual,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterEqual,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `!`
+    Bang,
+    /// Characters that do not start a token, or an unterminated inline comment or heredoc
+    Invalid,
+}
+
+/// A token of HCL source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    /// The source text of the token
+    pub text: &'a str,
+    /// Byte offset of the token in the source
+    pub offset: usize,
+}
+
+impl<'a> Token<'a> {
+    /// Byte offset after the end of the token
+    pub fn end(&self) -> usize {
+        self.offset + self.text.len()
+    }
+
+    /// Byte range of the token in the source
+    pub fn range(&self) -> Range<usize> {
+        self.offset..self.end()
+    }
+}
+
+/// Split HCL source into tokens
+///
+/// See the [module documentation](self) for the tokens that are returned.
+pub fn tokenize(source: &str) -> impl Iterator<Item = Token<'_>> {
+    Lexer::new(source)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Context {
+    /// Inside a quoted template that cannot extend beyond `limit`
+    Quoted { limit: usize },
+    /// Inside a heredoc template whose content ends at `content_end`, followed by the end marker
+    Heredoc {
+        content_end: usize,
+        marker_end: usize,
+    },
+    /// Inside an interpolation sequence with `braces` unclosed `{`
+    Interpolation { braces: usize, limit: usize },
+}
+
+/// An iterator over the [`Token`]s of HCL source
+#[derive(Debug, Clone)]
+pub(crate) struct Lexer<'a> {
+    source: &'a str,
+    position: usize,
+    stack: Vec<Context>,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            position: 0,
+            stack: vec![],
+        }
+    }
+
+    /// Tokenize `source` from `offset`, which must not be inside a template
+    pub fn starting_at(source: &'a str, offset: usize) -> Self {
+        Self {
+            source,
+            position: offset,
+            stack: vec![],
+        }
+    }
+
+    /// The offset the current context cannot extend beyond
+    fn limit(&self) -> usize {
+        match self.stack.last() {
+            None => self.source.len(),
+            Some(Context::Quoted { limit }) | Some(Context::Interpolation { limit, .. }) => *limit,
+            Some(Context::Heredoc { content_end, .. }) => *content_end,
+        }
+    }
+
+    fn token(&mut self, kind: TokenKind, len: usize) -> Token<'a> {
+        let offset = self.position;
+        self.position += len;
+        Token {
+            kind,
+            text: &self.source[offset..self.position],
+            offset,
+        }
+    }
+
+    fn expression_token(&mut self) -> Option<Token<'a>> {
+        use TokenKind::*;
+
+        let limit = self.limit();
+        let bytes = self.source.as_bytes();
+        while self.position < limit {
+            match bytes[self.position] {
+                b' ' | b'\t' => self.position += 1,
+                b'\r' if bytes.get(self.position + 1) != Some(&b'\n') => self.position += 1,
+                _ => break,
+            }
+        }
+
+        if self.position >= limit {
+            return match self.stack.last() {
+                // An unterminated interpolation sequence ends with its template
+                Some(Context::Interpolation { .. }) => {
+                    self.stack.pop();
+                    self.next()
+                }
+                _ => None,
+            };
+        }
+
+        let rest = &self.source[self.position..limit];
+        let rest_bytes = rest.as_bytes();
+        let second = rest_bytes.get(1).copied();
+        let token = match rest_bytes[0] {
+            b'\n' => self.token(Newline, 1),
+            b'\r' => self.token(Newline, 2),
+            b'#' => self.token(Comment, line_length(rest)),
+            b'/' if second == Some(b'/') => self.token(Comment, line_length(rest)),
+            b'/' if second == Some(b'*') => match rest[2..].find("*/") {
+                Some(index) => self.token(Comment, index + 4),
+                None => self.token(Invalid, rest.len()),
+            },
+            b'"' => {
+                self.stack.push(Context::Quoted { limit });
+                self.token(OpenQuote, 1)
+            }
+            b'<' if second == Some(b'<') => self.heredoc_start(rest, limit),
+            b'0'..=b'9' => self.token(Number, number_length(rest)),
+            b'.' if second.is_some_and(|c| c.is_ascii_digit()) => {
+                self.token(Number, number_length(rest))
+            }
+            b'{' => {
+                if let Some(Context::Interpolation { braces, .. }) = self.stack.last_mut() {
+                    *braces += 1;
+                }
+                self.token(OpenBrace, 1)
+            }
+            b'}' => match self.stack.last_mut() {
+                Some(Context::Interpolation { braces: 0, .. }) => {
+                    self.stack.pop();
+                    self.token(TemplateSequenceEnd, 1)
+                }
+                Some(Context::Interpolation { braces, .. }) => {
+                    *braces -= 1;
+                    self.token(CloseBrace, 1)
+                }
+                _ => self.token(CloseBrace, 1),
+            },
+            b'[' => self.token(OpenBracket, 1),
+            b']' => self.token(CloseBracket, 1),
+            b'(' => self.token(OpenParenthesis, 1),
+            b')' => self.token(CloseParenthesis, 1),
+            b',' => self.token(Comma, 1),
+            b'.' => self.token(Dot, 1),
+            b'?' => self.token(Question, 1),
+            b':' => self.token(Colon, 1),
+            b'+' => self.token(Plus, 1),
+            b'-' => self.token(Minus, 1),
+            b'*' => self.token(Star, 1),
+            b'/' => self.token(Slash, 1),
+            b'%' => self.token(Percent, 1),
+            b'=' if second == Some(b'=') => self.token(EqualEqual, 2),
+            b'=' => self.token(Equal, 1),
+            b'!' if second == Some(b'=') => self.token(NotEqual, 2),
+            b'!' => self.token(Bang, 1),
+            b'<' if second == Some(b'=') => self.token(LessEqual, 2),
+            b'<' => self.token(Less, 1),
+            b'>' if second == Some(b'=') => self.token(GreaterEqual, 2),
+            b'>' => self.token(Greater, 1),
+            b'&' if second == Some(b'&') => self.token(And, 2),
+            b'|' if second == Some(b'|') => self.token(Or, 2),
+            _ => match identifier_length(rest) {
+                0 => {
+                    let len = rest.chars().next().map_or(1, char::len_utf8);
+                    self.token(Invalid, len)
+                }
+                len => self.token(Identifier, len),
+            },
+        };
+        Some(token)
+    }
+
+    /// Tokenize `<<EOF` or `<<-EOF` and look for the end marker of the heredoc
+    fn heredoc_start(&mut self, rest: &'a str, limit: usize) -> Token<'a> {
+        let marker = rest[2..].strip_prefix('-').unwrap_or(&rest[2..]);
+        let identifier_len = marker
+            .find(|c: char| !c.is_alphanumeric() && c != '_')
+            .unwrap_or(marker.len());
+        let identifier = &marker[..identifier_len];
+        let after_identifier = &marker[identifier_len..];
+        let newline_len = if after_identifier.starts_with('\n') {
+            1
+        } else if after_identifier.starts_with("\r\n") {
+            2
+        } else {
+            0
+        };
+        let start_len = rest.len() - after_identifier.len();
+        if identifier.is_empty() || newline_len == 0 {
+            return self.token(TokenKind::Invalid, start_len);
+        }
+
+        // The end marker is the identifier on a line of its own, ending with a newline
+        let content_start = self.position + start_len + newline_len;
+        let mut line_start = content_start;
+        while line_start < limit {
+            let line = &self.source[line_start..limit];
+            let line_end = match line.find('\n') {
+                Some(index) => index,
+                None => break,
+            };
+            let line = &line[..line_end];
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if line.trim_start_matches([' ', '\t']) == identifier {
+                self.stack.push(Context::Heredoc {
+                    content_end: line_start,
+                    marker_end: line_start + line.len(),
+                });
+                return self.token(TokenKind::HeredocStart, start_len + newline_len);
+            }
+            line_start += line_end + 1;
+        }
+        self.token(TokenKind::Invalid, start_len)
+    }
+
+    fn template_token(&mut self, quoted: bool) -> Option<Token<'a>> {
+        let limit = self.limit();
+        if self.position >= limit {
+            return match self.stack.pop() {
+                Some(Context::Heredoc { marker_end, .. }) => {
+                    Some(self.token(TokenKind::HeredocEnd, marker_end - limit))
+                }
+                // An unterminated quoted template ends with its context
+                _ => self.next(),
+            };
+        }
+
+        let rest = &self.source.as_bytes()[self.position..limit];
+        if quoted && rest[0] == b'"' {
+            self.stack.pop();
+            return Some(self.token(TokenKind::CloseQuote, 1));
+        }
+        if rest.starts_with(b"${") {
+            self.stack.push(Context::Interpolation { braces: 0, limit });
+            return Some(self.token(TokenKind::TemplateInterpolation, 2));
+        }
+
+        let mut len = 0;
+        while len < rest.len() {
+            match rest[len] {
+                b'"' if quoted => break,
+                b'\\' if quoted => len += 1 + rest.get(len + 1).map_or(0, |c| char_length(*c)),
+                b'$' if rest[len..].starts_with(b"$${") => len += 3,
+                b'$' if rest[len..].starts_with(b"${") => break,
+                _ => len += 1,
+            }
+        }
+        Some(self.token(TokenKind::TemplateLiteral, len.min(rest.len())))
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.stack.last() {
+            Some(Context::Quoted { .. }) => self.template_token(true),
+            Some(Context::Heredoc { .. }) => self.template_token(false),
+            _ => self.expression_token(),
+        }
+    }
+}
+
+/// Length of the UTF-8 encoded character starting with `byte`
+fn char_length(byte: u8) -> usize {
+    match byte {
+        0xF0..=0xFF => 4,
+        0xE0..=0xEF => 3,
+        0x80..=0xDF => 2,
+        _ => 1,
+    }
+}
+
+/// Length of the line at the start of `s`, without the newline
+fn line_length(s: &str) -> usize {
+    s.find(['\r', '\n']).unwrap_or(s.len())
+}
+
+/// Length of the number literal at the start of `s`
+///
+/// ```ebnf
+/// NumericLit = (decimal+ ("." decimal*)? | "." decimal+) (expmark decimal+)?;
+/// expmark    = ('e' | 'E') ("+" | "-")?;
+/// ```
+fn number_length(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let digits = |from: usize| {
+        bytes[from..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count()
+    };
+
+    let mut len = digits(0);
+    if bytes.get(len) == Some(&b'.') {
+        len += 1 + digits(len + 1);
+    }
+    if let Some(b'e') | Some(b'E') = bytes.get(len) {
+        let sign = match bytes.get(len + 1) {
+            Some(b'+') | Some(b'-') => 1,
+            _ => 0,
+        };
+        let exponent = digits(len + 1 + sign);
+        if exponent > 0 {
+            len += 1 + sign + exponent;
+        }
+    }
+    len
+}
+
+/// Length of the identifier at the start of `s`, or 0 if `s` does not start with an identifier
+///
+/// ```ebnf
+/// Identifier = ID_Start (ID_Continue | '-')*;
+/// ```
+fn identifier_length(s: &str) -> usize {
+    let mut chars = s.char_indices();
+    match chars.next() {
+        Some((_, c))
+            if c.is_ascii_alphabetic() || c == '_' || (!c.is_ascii() && is_id_start(c)) => {}
+        _ => return 0,
+    }
+    chars
+        .find(|(_, c)| {
+            !(c.is_ascii_alphanumeric()
+                || *c == '_'
+                || *c == '-'
+                || (!c.is_ascii() && is_id_continue(*c)))
+        })
+        .map_or(s.len(), |(index, _)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use TokenKind::*;
+
+    fn tokens(source: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(source)
+            .map(|token| (token.kind, token.text))
+            .collect()
+    }
+
+    #[test]
+    fn tokens_have_byte_ranges_in_the_source() {
+        let source = "é = \"ü\" # 🦀\n";
+        let ranges: Vec<_> = tokenize(source).map(|token| token.range()).collect();
+        assert_eq!(ranges, [0..2, 3..4, 5..6, 6..8, 8..9, 10..16, 16..17]);
+        for token in tokenize(source) {
+            assert_eq!(&source[token.range()], token.text);
+        }
+    }
+
+    #[test]
+    fn expressions_are_tokenized() {
+        assert_eq!(
+            tokens("a = -1.5e3 >= f(x.y[0]) ? b-c : !d // comment\r\n"),
+            [
+                (Identifier, "a"),
+                (Equal, "="),
+                (Minus, "-"),
+                (Number, "1.5e3"),
+                (GreaterEqual, ">="),
+                (Identifier, "f"),
+                (OpenParenthesis, "("),
+                (Identifier, "x"),
+                (Dot, "."),
+                (Identifier, "y"),
+                (OpenBracket, "["),
+                (Number, "0"),
+                (CloseBracket, "]"),
+                (CloseParenthesis, ")"),
+                (Question, "?"),
+                (Identifier, "b-c"),
+                (Colon, ":"),
+                (Bang, "!"),
+                (Identifier, "d"),
+                (Comment, "// comment"),
+                (Newline, "\r\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn numbers_are_tokenized() {
+        for number in &["0", "1.", ".5", "1e7", "1.e4", "-1E-7", "1.2e+4"] {
+            let source = number.trim_start_matches('-');
+            assert_eq!(tokens(source), [(Number, source)]);
+        }
+        assert_eq!(tokens("1e"), [(Number, "1"), (Identifier, "e")]);
+    }
+
+    #[test]
+    fn quoted_templates_are_tokenized() {
+        assert_eq!(
+            tokens(r#""a \"${ {b = "}"}.b }$${c}" "#),
+            [
+                (OpenQuote, "\""),
+                (TemplateLiteral, r#"a \""#),
+                (TemplateInterpolation, "${"),
+                (OpenBrace, "{"),
+                (Identifier, "b"),
+                (Equal, "="),
+                (OpenQuote, "\""),
+                (TemplateLiteral, "}"),
+                (CloseQuote, "\""),
+                (CloseBrace, "}"),
+                (Dot, "."),
+                (Identifier, "b"),
+                (TemplateSequenceEnd, "}"),
+                (TemplateLiteral, "$${c}"),
+                (CloseQuote, "\""),
+            ]
+        );
+    }
+
+    #[test]
+    fn heredoc_templates_are_tokenized() {
+        assert_eq!(
+            tokens("x = <<-EOF\n  a ${b}\n  EOF\ny = <<EOT\nEOT\n"),
+            [
+                (Identifier, "x"),
+                (Equal, "="),
+                (HeredocStart, "<<-EOF\n"),
+                (TemplateLiteral, "  a "),
+                (TemplateInterpolation, "${"),
+                (Identifier, "b"),
+                (TemplateSequenceEnd, "}"),
+                (TemplateLiteral, "\n"),
+                (HeredocEnd, "  EOF"),
+                (Newline, "\n"),
+                (Identifier, "y"),
+                (Equal, "="),
+                (HeredocStart, "<<EOT\n"),
+                (HeredocEnd, "EOT"),
+                (Newline, "\n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_input_is_tokenized_as_invalid() {
+        assert_eq!(
+            tokens("a & <<EOF\n/* b"),
+            [
+                (Identifier, "a"),
+                (Invalid, "&"),
+                (Invalid, "<<EOF"),
+                (Newline, "\n"),
+                (Invalid, "/* b"),
+            ]
+        );
+        assert_eq!(
+            tokens("\"${a"),
+            [
+                (OpenQuote, "\""),
+                (TemplateInterpolation, "${"),
+                (Identifier, "a")
+            ]
+        );
+    }
+}
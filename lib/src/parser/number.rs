@@ -1,6 +1,13 @@
 //! Number
+//!
+//! HCL numbers are arbitrary precision. A [`Number`] keeps the literal it was parsed from, and
+//! numbers are equal if they have the same value regardless of how they are written, like `1000`
+//! and `1e3`. With the `bigdecimal` feature, numbers can be converted to and from
+//! [`BigDecimal`](bigdecimal::BigDecimal) for exact arithmetic.
 
 use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+use std::num::ParseIntError;
 use std::ops::Deref;
 use std::str::FromStr;
 
@@ -10,7 +17,7 @@ use nom::{alt, char, digit, opt, pair, tuple};
 
 use crate::AsOwned;
 
-#[derive(Debug, Clone, Eq, Hash)]
+#[derive(Debug, Clone, Eq)]
 pub struct Number<'a> {
     /// The original input number literal
     input: Cow<'a, str>,
@@ -55,6 +62,75 @@ impl<'a> Number<'a> {
     pub fn is_float(&self) -> bool {
         !self.is_signed()
     }
+
+    /// Is an integer, even if it is written with a fraction or an exponent like `1.5e3`
+    pub fn is_integral(&self) -> bool {
+        self.canonical()
+            .is_some_and(|canonical| canonical.exponent >= 0)
+    }
+
+    /// The canonical form of the number, or `None` if the exponent is too large to represent
+    fn canonical(&self) -> Option<Canonical> {
+        let whole = self.whole.as_deref().unwrap_or_default();
+        let fraction = self.fraction.as_deref().unwrap_or_default();
+        let digits = format!("{}{}", whole, fraction);
+        let digits = digits.trim_start_matches('0');
+        let significant = digits.trim_end_matches('0');
+        if significant.is_empty() {
+            return Some(Canonical {
+                negative: false,
+                digits: String::new(),
+                exponent: 0,
+            });
+        }
+
+        let exponent = match &self.exponent {
+            None => 0,
+            Some(exponent) => {
+                let value: i128 = exponent.exponent.parse().ok()?;
+                if exponent.positive {
+                    value
+                } else {
+                    -value
+                }
+            }
+        };
+        let trailing_zeros = digits.len() - significant.len();
+        let exponent = exponent
+            .checked_sub(fraction.len() as i128)?
+            .checked_add(trailing_zeros as i128)?;
+
+        Some(Canonical {
+            negative: !self.positive,
+            digits: significant.to_string(),
+            exponent,
+        })
+    }
+
+    /// The number written as an integer without a fraction or an exponent, if it is integral
+    fn integer_literal(&self) -> Option<String> {
+        let canonical = self.canonical()?;
+        if canonical.exponent < 0 {
+            return None;
+        }
+        if canonical.digits.is_empty() {
+            return Some("0".to_string());
+        }
+        // Integers with more than 40 digits overflow all integer types, so the zeros beyond
+        // that are left out
+        let zeros = std::cmp::min(canonical.exponent, 40) as usize;
+        let sign = if canonical.negative { "-" } else { "" };
+        Some(format!("{}{}{}", sign, canonical.digits, "0".repeat(zeros)))
+    }
+}
+
+/// The value of a number as `digits * 10^exponent`, without leading or trailing zeros in the
+/// digits. Zero has no digits.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Canonical {
+    negative: bool,
+    digits: String,
+    exponent: i128,
 }
 
 macro_rules! from_uint {
@@ -116,8 +192,26 @@ macro_rules! to_number {
     };
 }
 
+macro_rules! to_integer {
+    ($($name:ident => $to:ty, )*) => {$(
+        to_integer!($name => $to => concat!(
+            "Attempt conversion to ", stringify!($to), ". Integral numbers with a fraction or an ",
+            "exponent, like `1e3`, are converted too."
+        ));
+    )*};
+    ($name:ident => $to:ty => $doc:expr) => {
+        #[doc=$doc]
+        pub fn $name(&self) -> Result<$to, ParseIntError> {
+            match self.integer_literal() {
+                Some(literal) => literal.parse(),
+                None => self.input.as_ref().parse(),
+            }
+        }
+    };
+}
+
 impl<'a> Number<'a> {
-    to_number!(
+    to_integer!(
         as_u8 => u8,
         as_u16 => u16,
         as_u32 => u32,
@@ -128,11 +222,40 @@ impl<'a> Number<'a> {
         as_i32 => i32,
         as_i64 => i64,
         as_i128 => i128,
+    );
+
+    to_number!(
         as_f32 => f32,
         as_f64 => f64,
     );
 }
 
+#[cfg(feature = "bigdecimal")]
+impl<'a> Number<'a> {
+    /// Convert to an arbitrary precision decimal
+    pub fn as_big_decimal(
+        &self,
+    ) -> Result<bigdecimal::BigDecimal, bigdecimal::ParseBigDecimalError> {
+        match self.canonical() {
+            Some(canonical) if canonical.digits.is_empty() => Ok(Default::default()),
+            Some(canonical) => {
+                let sign = if canonical.negative { "-" } else { "" };
+                format!("{}{}e{}", sign, canonical.digits, canonical.exponent).parse()
+            }
+            None => self.input.parse(),
+        }
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+impl<'a> From<bigdecimal::BigDecimal> for Number<'a> {
+    fn from(decimal: bigdecimal::BigDecimal) -> Self {
+        let literal = decimal.to_string();
+        let (_, number) = number(CompleteStr(&literal)).expect("decimals to be valid numbers");
+        number.as_owned()
+    }
+}
+
 impl<'a> Deref for Number<'a> {
     type Target = str;
     fn deref(&self) -> &Self::Target {
@@ -154,50 +277,21 @@ impl<'a> AsOwned for Number<'a> {
     }
 }
 
+/// Numbers are equal if they have the same value
 impl<'a> std::cmp::PartialEq for Number<'a> {
     fn eq(&self, other: &Self) -> bool {
-        if self.is_float() || other.is_float() {
-            // Good enough comparison
-            // From https://users.rust-lang.org/t/assert-eq-for-float-numbers/7034/4
-            let us = self.as_f64();
-            let them = other.as_f64();
-            if us.is_err() || them.is_err() {
-                println!("Error converting to float!");
-                return false;
-            }
+        match (self.canonical(), other.canonical()) {
+            (Some(us), Some(them)) => us == them,
+            _ => self.input == other.input,
+        }
+    }
+}
 
-            let us = us.unwrap().abs();
-            let them = them.unwrap().abs();
-            let diff = (us - them).abs();
-
-            if us == them {
-                // Handle infinities.
-                true
-            } else if us == 0.0 || them == 0.0 || diff < std::f64::MIN_POSITIVE {
-                // One of a or b is zero (or both are extremely close to it,) use absolute error.
-                diff < (std::f64::EPSILON * std::f64::MIN_POSITIVE)
-            } else {
-                // Use relative error.
-                (diff / f64::min(us + them, std::f64::MAX)) < std::f64::EPSILON
-            }
-        } else if self.is_unsigned() && other.is_unsigned() {
-            // Unsigned
-            let us = self.as_u128();
-            let them = other.as_u128();
-            if us.is_err() || them.is_err() {
-                println!("Error converting to unsigned!");
-                return false;
-            }
-            us.unwrap().eq(&them.unwrap())
-        } else {
-            // Signed
-            let us = self.as_i128();
-            let them = other.as_i128();
-            if us.is_err() || them.is_err() {
-                println!("Error converting to signed!");
-                return false;
-            }
-            us.unwrap().eq(&them.unwrap())
+impl<'a> Hash for Number<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match self.canonical() {
+            Some(canonical) => canonical.hash(state),
+            None => self.input.hash(state),
         }
     }
 }
@@ -259,6 +353,49 @@ pub fn number<'a>(s: CompleteStr<'a>) -> IResult<CompleteStr<'a>, Number<'a>, u3
     Ok((remaining, number))
 }
 
+impl<'a> Number<'a> {
+    /// Split a number literal that has already been recognized by the lexer into its parts. The
+    /// literal may start with a sign.
+    pub(crate) fn from_literal(literal: &'a str) -> Self {
+        let (positive, unsigned) = match literal.as_bytes().first() {
+            Some(b'-') => (false, &literal[1..]),
+            Some(b'+') => (true, &literal[1..]),
+            _ => (true, literal),
+        };
+
+        let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+            Some(index) => {
+                let exponent = &unsigned[index + 1..];
+                let (positive, exponent) = match exponent.as_bytes().first() {
+                    Some(b'-') => (false, &exponent[1..]),
+                    Some(b'+') => (true, &exponent[1..]),
+                    _ => (true, exponent),
+                };
+                let exponent = Exponent {
+                    positive,
+                    exponent: Cow::Borrowed(exponent),
+                };
+                (&unsigned[..index], Some(exponent))
+            }
+            None => (unsigned, None),
+        };
+
+        let (whole, fraction) = match mantissa.find('.') {
+            Some(0) => (None, Some(&mantissa[1..])),
+            Some(index) => (Some(&mantissa[..index]), Some(&mantissa[index + 1..])),
+            None => (Some(mantissa), None),
+        };
+
+        Number::new(
+            Cow::Borrowed(literal),
+            positive,
+            whole.map(Cow::Borrowed),
+            fraction.map(Cow::Borrowed),
+            exponent,
+        )
+    }
+}
+
 fn exponent<'a>(input: CompleteStr<'a>) -> IResult<CompleteStr<'a>, Option<Exponent<'a>>, u32> {
     let (remaining, exponent) = opt!(
         input,
@@ -316,18 +453,26 @@ mod tests {
             let (remaining, parsed) = number(CompleteStr(*case)).unwrap();
             assert!(remaining.is_empty());
 
-            let expected_int: Result<i64, _> = case.parse();
-            let actual_int = parsed.as_i64();
+            // Integral numbers can be converted to integers, even with an exponent
+            let integral = case
+                .parse::<f64>()
+                .ok()
+                .filter(|float| float.fract() == 0.0);
+
+            let expected_int = integral.map(|float| float as i64);
+            let actual_int = parsed.as_i64().ok();
             assert_eq!(expected_int, actual_int);
-            if let Ok(int) = expected_int {
+            if let Some(int) = expected_int {
                 let test_from = Number::from(int);
                 assert_eq!(parsed, test_from);
             }
 
-            let expected_uint: Result<u64, _> = case.parse();
-            let actual_uint = parsed.as_u64();
+            let expected_uint = integral
+                .filter(|float| *float >= 0.0)
+                .map(|float| float as u64);
+            let actual_uint = parsed.as_u64().ok();
             assert_eq!(expected_uint, actual_uint);
-            if let Ok(int) = expected_uint {
+            if let Some(int) = expected_uint {
                 let test_from = Number::from(int);
                 assert_eq!(parsed, test_from);
             }
@@ -341,4 +486,99 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn numbers_are_compared_by_value() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let hash = |number: &Number| {
+            let mut hasher = DefaultHasher::new();
+            number.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let equal = [
+            ("1000", "1e3"),
+            ("1000", "1.0e3"),
+            ("0.5", "5e-1"),
+            ("-0.5", "-.50"),
+            ("0", "-0.0"),
+            ("0", "0e99999999999999999999999999999999999999999"),
+            ("0.1", "0.100000000000000000000000000000000000000"),
+            (
+                "123456789012345678901234567890",
+                "1.2345678901234567890123456789e29",
+            ),
+        ];
+        for (lhs, rhs) in equal.iter() {
+            println!("Testing {} == {}", lhs, rhs);
+            let (_, lhs) = number(CompleteStr(lhs)).unwrap();
+            let (_, rhs) = number(CompleteStr(rhs)).unwrap();
+            assert_eq!(lhs, rhs);
+            assert_eq!(hash(&lhs), hash(&rhs));
+        }
+
+        let not_equal = [
+            ("1", "-1"),
+            ("0.1", "0.10000000000000001"),
+            (
+                "123456789012345678901234567890",
+                "123456789012345678901234567891",
+            ),
+        ];
+        for (lhs, rhs) in not_equal.iter() {
+            println!("Testing {} != {}", lhs, rhs);
+            let (_, lhs) = number(CompleteStr(lhs)).unwrap();
+            let (_, rhs) = number(CompleteStr(rhs)).unwrap();
+            assert_ne!(lhs, rhs);
+        }
+    }
+
+    #[test]
+    fn integral_numbers_are_converted_to_integers() {
+        let test_cases = [
+            ("1e3", Ok(1000)),
+            ("1.5e1", Ok(15)),
+            ("-2.50E2", Ok(-250)),
+            ("-0", Ok(0)),
+            ("1.5", Err(())),
+            ("1e-3", Err(())),
+            ("1e40", Err(())),
+            ("-1e99999", Err(())),
+        ];
+        for (input, expected) in test_cases.iter() {
+            println!("Testing {}", input);
+            let (_, number) = number(CompleteStr(input)).unwrap();
+            assert_eq!(number.as_i64().map_err(|_| ()), *expected);
+        }
+
+        let (_, big) = number(CompleteStr("1.7e38")).unwrap();
+        assert_eq!(
+            big.as_u128(),
+            Ok(170_000_000_000_000_000_000_000_000_000_000_000_000)
+        );
+    }
+
+    #[cfg(feature = "bigdecimal")]
+    #[test]
+    fn numbers_are_converted_to_and_from_big_decimals() {
+        use bigdecimal::BigDecimal;
+
+        let test_cases = [
+            ("0.1", "0.1"),
+            ("-1.5e-30", "-1.5e-30"),
+            (
+                "123456789012345678901234567890.5",
+                "123456789012345678901234567890.5",
+            ),
+            ("0.0", "0"),
+        ];
+        for (input, expected) in test_cases.iter() {
+            println!("Testing {}", input);
+            let (_, number) = number(CompleteStr(input)).unwrap();
+            let decimal = number.as_big_decimal().unwrap();
+            assert_eq!(decimal, expected.parse::<BigDecimal>().unwrap());
+            assert_eq!(Number::from(decimal), number);
+        }
+    }
 }
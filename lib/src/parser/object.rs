@@ -15,9 +15,11 @@ use std::borrow::{Borrow, Cow};
 use nom::types::CompleteStr;
 use nom::{alt, call, char, do_parse, named, peek, recognize, tag, terminated, IResult};
 
+use crate::parser::descent;
 use crate::parser::expression::{expression, Expression};
 use crate::parser::identifier::{identifier, Identifier};
 use crate::parser::whitespace::newline;
+use crate::Error;
 
 // TODO: Dealing with expressions and ambiguity. See reference
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -39,14 +41,29 @@ pub enum ObjectElementIdentifier<'a> {
 
 impl<'a> ObjectElementIdentifier<'a> {
     /// Turn an object element identifier into a string
-    pub fn as_str(&self) -> Cow<'a, str> {
-        match self {
-            ObjectElementIdentifier::Identifier(ident) => ident.clone(),
-            // FIXME
-            ObjectElementIdentifier::Expression(_) => {
-                unimplemented!("Expression evaluation is not yet supported")
+    ///
+    /// Expressions are only supported if they are string literals, because other expressions
+    /// have to be evaluated.
+    pub fn as_str(&self) -> Result<Cow<'a, str>, Error> {
+        let expression = match self {
+            ObjectElementIdentifier::Identifier(ident) => return Ok(ident.clone()),
+            ObjectElementIdentifier::Expression(expression) => expression,
+        };
+        let string = match expression {
+            Cow::Borrowed(expression) => {
+                match descent::parse_expression(expression, &Default::default()) {
+                    Ok(Expression::String(string)) => Some(string),
+                    _ => None,
+                }
             }
-        }
+            Cow::Owned(expression) => {
+                match descent::parse_expression(expression, &Default::default()) {
+                    Ok(Expression::String(string)) => Some(Cow::Owned(string.into_owned())),
+                    _ => None,
+                }
+            }
+        };
+        string.ok_or_else(|| Error::UnsupportedObjectKey(expression.to_string()))
     }
 }
 
@@ -648,4 +665,24 @@ EOF
 
         assert_eq!(expected, parsed);
     }
+
+    #[test]
+    fn object_element_identifiers_are_turned_into_strings() {
+        let identifier = ObjectElementIdentifier::Identifier(Cow::Borrowed("foo"));
+        assert_eq!(identifier.as_str().unwrap(), "foo");
+
+        let string = ObjectElementIdentifier::Expression(Cow::Borrowed(r#""foo \"bar\"""#));
+        assert_eq!(string.as_str().unwrap(), r#"foo "bar""#);
+
+        let string = ObjectElementIdentifier::Expression(Cow::Owned(r#""foo""#.to_string()));
+        assert_eq!(string.as_str().unwrap(), "foo");
+
+        for expression in &["foo.bar", r#""${foo}""#, r#""foo" + 1"#, r#""foo"#] {
+            let expression = ObjectElementIdentifier::Expression(Cow::Borrowed(expression));
+            match expression.as_str() {
+                Err(Error::UnsupportedObjectKey(_)) => {}
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+    }
 }
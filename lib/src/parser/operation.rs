@@ -0,0 +1,340 @@
+//! Operations
+//!
+//! [Reference](https://github.com/hashicorp/hcl2/blob/master/hcl/hclsyntax/spec.md#operations)
+//!
+//! ```ebnf
+//! Operation = unaryOp | binaryOp;
+//! unaryOp = ("-" | "!") ExprTerm;
+//! binaryOp = ExprTerm binaryOperator ExprTerm;
+//! binaryOperator = compareOperator | arithmeticOperator | logicOperator;
+//! compareOperator = "==" | "!=" | "<" | ">" | "<=" | ">=";
+//! arithmeticOperator = "+" | "-" | "*" | "/" | "%";
+//! logicOperator = "&&" | "||";
+//! ```
+//!
+//! Binary operators are grouped by precedence, from the highest to the lowest:
+//!
+//! | Level | Operators          |
+//! |-------|--------------------|
+//! | 6     | `*` `/` `%`        |
+//! | 5     | `+` `-`            |
+//! | 4     | `>` `>=` `<` `<=`  |
+//! | 3     | `==` `!=`          |
+//! | 2     | `&&`               |
+//! | 1     | `\|\|`             |
+//!
+//! Operators of the same level are left associative.
+use std::fmt;
+
+use nom::types::CompleteStr;
+use nom::{alt, call, char, do_parse, named, not, preceded, tag, terminated, IResult};
+
+use crate::parser::expression::{postfix_expression, Expression};
+use crate::parser::whitespace::inline_whitespace;
+
+/// An operation on one or two operands
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operation<'a> {
+    Unary(UnaryOperator, Expression<'a>),
+    Binary(BinaryOperator, Expression<'a>, Expression<'a>),
+}
+
+impl<'a> crate::AsOwned for Operation<'a> {
+    type Output = Operation<'static>;
+
+    fn as_owned(&self) -> Self::Output {
+        match self {
+            Operation::Unary(operator, operand) => Operation::Unary(*operator, operand.as_owned()),
+            Operation::Binary(operator, lhs, rhs) => {
+                Operation::Binary(*operator, lhs.as_owned(), rhs.as_owned())
+            }
+        }
+    }
+}
+
+/// Operators with a single operand
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnaryOperator {
+    /// `-`
+    Negate,
+    /// `!`
+    Not,
+}
+
+impl UnaryOperator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UnaryOperator::Negate => "-",
+            UnaryOperator::Not => "!",
+        }
+    }
+}
+
+impl fmt::Display for UnaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Operators with two operands
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryOperator {
+    /// `*`
+    Multiply,
+    /// `/`
+    Divide,
+    /// `%`
+    Modulo,
+    /// `+`
+    Add,
+    /// `-`
+    Subtract,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessEqual,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+}
+
+impl BinaryOperator {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::And => "&&",
+            BinaryOperator::Or => "||",
+        }
+    }
+
+    /// The precedence of the operator. Operators with a higher precedence bind tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => 6,
+            BinaryOperator::Add | BinaryOperator::Subtract => 5,
+            BinaryOperator::Greater
+            | BinaryOperator::GreaterEqual
+            | BinaryOperator::Less
+            | BinaryOperator::LessEqual => 4,
+            BinaryOperator::Equal | BinaryOperator::NotEqual => 3,
+            BinaryOperator::And => 2,
+            BinaryOperator::Or => 1,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+named!(
+    pub unary_operator(CompleteStr) -> UnaryOperator,
+    alt!(
+        char!('-') => { |_| UnaryOperator::Negate }
+        | terminated!(char!('!'), not!(char!('='))) => { |_| UnaryOperator::Not }
+    )
+);
+
+// Two character operators are tried first. `/` must not be the start of a comment.
+named!(
+    pub binary_operator(CompleteStr) -> BinaryOperator,
+    alt!(
+        tag!("==") => { |_| BinaryOperator::Equal }
+        | tag!("!=") => { |_| BinaryOperator::NotEqual }
+        | tag!(">=") => { |_| BinaryOperator::GreaterEqual }
+        | tag!("<=") => { |_| BinaryOperator::LessEqual }
+        | tag!("&&") => { |_| BinaryOperator::And }
+        | tag!("||") => { |_| BinaryOperator::Or }
+        | tag!(">") => { |_| BinaryOperator::Greater }
+        | tag!("<") => { |_| BinaryOperator::Less }
+        | tag!("+") => { |_| BinaryOperator::Add }
+        | tag!("-") => { |_| BinaryOperator::Subtract }
+        | tag!("*") => { |_| BinaryOperator::Multiply }
+        | terminated!(tag!("/"), not!(alt!(char!('/') | char!('*')))) => { |_| BinaryOperator::Divide }
+        | tag!("%") => { |_| BinaryOperator::Modulo }
+    )
+);
+
+// A postfix expression, or a unary operator applied to one
+named!(
+    unary_expression(CompleteStr) -> Expression,
+    alt!(
+        call!(postfix_expression)
+        | do_parse!(
+            operator: call!(unary_operator)
+            >> call!(inline_whitespace)
+            >> operand: call!(unary_expression)
+            >> (Expression::Operation(Box::new(Operation::Unary(operator, operand))))
+        )
+    )
+);
+
+/// Parse binary operations with operators of at least `min_precedence` by precedence climbing
+fn binary_expression(
+    input: CompleteStr,
+    min_precedence: u8,
+) -> IResult<CompleteStr, Expression, u32> {
+    let (mut input, mut lhs) = unary_expression(input)?;
+
+    loop {
+        let (remaining, operator) = match preceded!(input, inline_whitespace, binary_operator) {
+            Ok(output) => output,
+            Err(nom::Err::Error(_)) => break,
+            Err(e) => return Err(e),
+        };
+        if operator.precedence() < min_precedence {
+            break;
+        }
+
+        let (remaining, rhs) = do_parse!(
+            remaining,
+            call!(inline_whitespace)
+                >> rhs: call!(binary_expression, operator.precedence() + 1)
+                >> (rhs)
+        )?;
+        lhs = Expression::Operation(Box::new(Operation::Binary(operator, lhs, rhs)));
+        input = remaining;
+    }
+
+    Ok((input, lhs))
+}
+
+/// Parse an operation, or a single postfix expression if there are no operators
+pub fn operation(input: CompleteStr) -> IResult<CompleteStr, Expression, u32> {
+    binary_expression(input, 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unary(operator: UnaryOperator, operand: Expression<'static>) -> Expression<'static> {
+        Expression::Operation(Box::new(Operation::Unary(operator, operand)))
+    }
+
+    fn binary(
+        operator: BinaryOperator,
+        lhs: Expression<'static>,
+        rhs: Expression<'static>,
+    ) -> Expression<'static> {
+        Expression::Operation(Box::new(Operation::Binary(operator, lhs, rhs)))
+    }
+
+    #[test]
+    fn operations_are_parsed_with_precedence() {
+        use BinaryOperator::*;
+
+        let test_cases = [
+            ("1", Expression::from(1), ""),
+            (
+                "1 + 2",
+                binary(Add, Expression::from(1), Expression::from(2)),
+                "",
+            ),
+            (
+                "1+2",
+                binary(Add, Expression::from(1), Expression::from(2)),
+                "",
+            ),
+            (
+                "1 + 2 * 3",
+                binary(
+                    Add,
+                    Expression::from(1),
+                    binary(Multiply, Expression::from(2), Expression::from(3)),
+                ),
+                "",
+            ),
+            (
+                "1 - 2 - 3",
+                binary(
+                    Subtract,
+                    binary(Subtract, Expression::from(1), Expression::from(2)),
+                    Expression::from(3),
+                ),
+                "",
+            ),
+            (
+                "(1 + 2) * 3",
+                binary(
+                    Multiply,
+                    binary(Add, Expression::from(1), Expression::from(2)),
+                    Expression::from(3),
+                ),
+                "",
+            ),
+            (
+                "1 < 2 == true || !false && 1 >= 2",
+                binary(
+                    Or,
+                    binary(
+                        Equal,
+                        binary(Less, Expression::from(1), Expression::from(2)),
+                        Expression::from(true),
+                    ),
+                    binary(
+                        And,
+                        unary(UnaryOperator::Not, Expression::from(false)),
+                        binary(GreaterEqual, Expression::from(1), Expression::from(2)),
+                    ),
+                ),
+                "",
+            ),
+            ("-1", Expression::from(-1), ""),
+            (
+                "- (1)",
+                unary(UnaryOperator::Negate, Expression::from(1)),
+                "",
+            ),
+            (
+                "6 / 3",
+                binary(Divide, Expression::from(6), Expression::from(3)),
+                "",
+            ),
+            ("6 // comment", Expression::from(6), " // comment"),
+            ("6 /* comment */", Expression::from(6), " /* comment */"),
+            (
+                "1 % 2 ",
+                binary(Modulo, Expression::from(1), Expression::from(2)),
+                " ",
+            ),
+            ("1\n+ 2", Expression::from(1), "\n+ 2"),
+        ];
+
+        for (input, expected_value, expected_remaining) in test_cases.iter() {
+            println!("Testing {}", input);
+            let (remaining, actual_value) = operation(CompleteStr(input)).unwrap();
+            assert_eq!(&remaining.0, expected_remaining);
+            assert_eq!(actual_value, *expected_value);
+        }
+    }
+
+    #[test]
+    fn incomplete_operations_are_errors() {
+        for input in &["1 +", "1 + ", "!", "1 * -"] {
+            println!("Testing {}", input);
+            assert!(operation(CompleteStr(input)).is_err());
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Options to limit the resources used by parsing
+//!
+//! Documents from untrusted sources can be parsed with limits, so that parsing them fails with an
+//! [`Error`](crate::Error) instead of using excessive memory or overflowing the stack.
+//!
+//! ```rust
+//! use ferrous_chloride::parser::{parse_str_with_options, ParseOptions};
+//! use ferrous_chloride::Error;
+//!
+//! let options = ParseOptions::new().max_depth(Some(2));
+//! assert!(parse_str_with_options("a = [[1]]\n", &options).is_ok());
+//!
+//! match parse_str_with_options("a = [[[1]]]\n", &options) {
+//!     Err(Error::LimitExceeded { position, .. }) => assert_eq!(position.column, 7),
+//!     result => panic!("unexpected {:?}", result),
+//! }
+//! ```
+
+/// The default limit on the depth of nesting
+///
+/// Parsing is recursive, and this limit keeps the stack used by parsing within half the stack of
+/// a thread spawned with the default size, even in debug builds.
+pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+/// Limits on the documents being parsed
+///
+/// The limits are inclusive. `None` means there is no limit.
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash)]
+pub struct ParseOptions {
+    /// Maximum depth of nested blocks, tuples, objects, parentheses, function calls, templates
+    /// and operations
+    ///
+    /// Every operator of a chain like `a + b + c` and every traversal of a chain like `a.b[0]`
+    /// counts as a level, because they make up expressions which are as deep as the chains are
+    /// long.
+    pub max_depth: Option<usize>,
+    /// Maximum size of the input in bytes
+    pub max_input_size: Option<usize>,
+    /// Maximum length of the content of a heredoc in bytes
+    pub max_heredoc_length: Option<usize>,
+    /// Maximum number of attributes and blocks in the document, including those in the bodies of
+    /// blocks
+    pub max_body_elements: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_input_size: None,
+            max_heredoc_length: None,
+            max_body_elements: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Options with only the default limit on the depth of nesting
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Options without any limits
+    ///
+    /// Deeply nested input might overflow the stack.
+    pub fn unlimited() -> Self {
+        Self {
+            max_depth: None,
+            ..Default::default()
+        }
+    }
+
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn max_input_size(mut self, max_input_size: Option<usize>) -> Self {
+        self.max_input_size = max_input_size;
+        self
+    }
+
+    pub fn max_heredoc_length(mut self, max_heredoc_length: Option<usize>) -> Self {
+        self.max_heredoc_length = max_heredoc_length;
+        self
+    }
+
+    pub fn max_body_elements(mut self, max_body_elements: Option<usize>) -> Self {
+        self.max_body_elements = max_body_elements;
+        self
+    }
+
+    /// Check the size of the input against the limit
+    pub(crate) fn check_input_size(&self, size: usize) -> Result<(), crate::Error> {
+        match self.max_input_size {
+            Some(limit) if size > limit => Err(crate::Error::InputTooLarge(limit)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::parser::{parse_reader_with_options, parse_str_with_options};
+    use crate::Error;
+
+    /// `a = ` followed by `depth` levels of nesting made of `open` and `close`
+    fn nested(open: &str, inner: &str, close: &str, depth: usize) -> String {
+        format!(
+            "a = {}{}{}\n",
+            open.repeat(depth),
+            inner,
+            close.repeat(depth)
+        )
+    }
+
+    fn nested_blocks(depth: usize) -> String {
+        format!("{}a = 1\n{}", "a {\n".repeat(depth), "}\n".repeat(depth))
+    }
+
+    fn deeply_nested(depth: usize) -> Vec<String> {
+        vec![
+            nested("[", "1", "]", depth),
+            nested("{ a = ", "1", " }", depth),
+            nested("(", "1", ")", depth),
+            nested("f(", "1", ")", depth),
+            nested("a[", "1", "]", depth),
+            nested("\"${", "1", "}\"", depth),
+            nested("!", "true", "", depth),
+            nested("- ", "1", "", depth),
+            nested("true ? 1 : ", "1", "", depth),
+            nested("", "1", " + 1", depth),
+            nested("", "x", "[0]", depth),
+            nested("", "x", ".y", depth),
+            nested_blocks(depth),
+        ]
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        for input in deeply_nested(100_000) {
+            match crate::parse_str(&input) {
+                Err(Error::LimitExceeded { .. }) => {}
+                result => panic!("unexpected {:?}", result.map(|_| ())),
+            }
+        }
+    }
+
+    #[test]
+    fn long_chains_are_rejected() {
+        let chains = [
+            nested("", "1", " + 1", 200_000),
+            nested("", "x", "[0]", 200_000),
+            nested("", "x", ".y", 200_000),
+        ];
+        for input in chains.iter() {
+            match crate::parse_str(input) {
+                Err(Error::LimitExceeded { position, .. }) => assert_eq!(position.line, 1),
+                result => panic!("unexpected {:?}", result.map(|_| ())),
+            }
+        }
+
+        let input = nested("", "1", " + 1", DEFAULT_MAX_DEPTH + 1);
+        parse_str_with_options(&input, &ParseOptions::unlimited()).unwrap();
+    }
+
+    #[test]
+    fn nesting_up_to_the_default_limit_is_parsed() {
+        for input in deeply_nested(DEFAULT_MAX_DEPTH) {
+            crate::parse_str(&input).unwrap();
+        }
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected_before_parsing_values() {
+        let input = nested("[", "1", "]", 100_000);
+        match crate::value::from_str(&input, None) {
+            Err(Error::LimitExceeded { position, .. }) => {
+                assert_eq!(position.offset, 4 + DEFAULT_MAX_DEPTH)
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+
+        let input = nested("[", "1", "]", DEFAULT_MAX_DEPTH);
+        crate::value::from_str(&input, None).unwrap();
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected_by_events() {
+        use crate::parser::events::EventReader;
+
+        // Blocks are not parsed recursively by the event reader
+        let input = nested_blocks(100_000) + &nested("[", "1", "]", 100_000);
+        let events: Result<Vec<_>, _> = EventReader::new(input.as_bytes()).collect();
+        match events {
+            Err(Error::LimitExceeded { position, .. }) => assert_eq!(position.line, 200_002),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn nesting_can_be_unlimited() {
+        let input = nested("[", "1", "]", DEFAULT_MAX_DEPTH + 1);
+        assert!(crate::parse_str(&input).is_err());
+        parse_str_with_options(&input, &ParseOptions::unlimited()).unwrap();
+    }
+
+    #[test]
+    fn input_size_is_limited() {
+        let input = "a = 1\nb = 2\n";
+        let options = ParseOptions::new().max_input_size(Some(input.len()));
+        parse_str_with_options(input, &options).unwrap();
+        parse_reader_with_options(input.as_bytes(), &options).unwrap();
+
+        let options = options.max_input_size(Some(input.len() - 1));
+        for result in &[
+            parse_str_with_options(input, &options).map(|_| ()),
+            parse_reader_with_options(input.as_bytes(), &options).map(|_| ()),
+        ] {
+            match result {
+                Err(Error::InputTooLarge(limit)) => assert_eq!(*limit, input.len() - 1),
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn heredoc_length_is_limited() {
+        let input = "a = 1\nb = <<EOF\nfoo\n${a}\nEOF\n";
+        let options = ParseOptions::new().max_heredoc_length(Some(8));
+        parse_str_with_options(input, &options).unwrap();
+
+        let options = options.max_heredoc_length(Some(7));
+        match parse_str_with_options(input, &options) {
+            Err(Error::LimitExceeded { position, message }) => {
+                assert_eq!(position.to_string(), "2:5");
+                assert_eq!(message, "heredoc is longer than 7 bytes");
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+
+    #[test]
+    fn body_elements_are_limited() {
+        let input = "a = 1\nb {\n  c = 2\n}\nd {}\n";
+        let options = ParseOptions::new().max_body_elements(Some(4));
+        parse_str_with_options(input, &options).unwrap();
+
+        let options = options.max_body_elements(Some(3));
+        match parse_str_with_options(input, &options) {
+            Err(Error::LimitExceeded { position, message }) => {
+                assert_eq!(position.to_string(), "5:1");
+                assert_eq!(message, "more than 3 body elements");
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+}
@@ -8,17 +8,21 @@ use std::borrow::Cow;
 use std::str;
 
 use crate::errors::InternalKind;
-use log::debug;
+use crate::parser::expression::{expression, Expression};
+use crate::AsOwned;
 use nom::types::CompleteStr;
 use nom::ErrorKind;
 use nom::{
-    alt, call, complete, delimited, do_parse, escaped_transform, map, map_res, named, opt, peek,
-    preceded, return_error, tag, take_while1, take_while_m_n, IResult,
+    alt, call, complete, delimited, do_parse, error_position, escaped_transform, map, map_res,
+    named, opt, peek, preceded, return_error, tag, take_while, take_while1, take_while_m_n,
+    IResult, Slice,
 };
 
 /// The StringLit production permits the escape sequences discussed for quoted template expressions
 /// as above, but does not permit template interpolation or directive sequences.
-pub type StringLiteral = String;
+///
+/// String literals without escape sequences are borrowed from the input.
+pub type StringLiteral<'a> = Cow<'a, str>;
 
 fn is_hex_digit(c: char) -> bool {
     c.is_digit(16)
@@ -29,15 +33,11 @@ fn is_oct_digit(c: char) -> bool {
 }
 
 fn legal_string_literal_character(c: char) -> bool {
-    let test = c != '\\' && c != '"';
-    debug!("Checking valid string character {:?}: {:?}", c, test);
-    test
+    c != '\\' && c != '"'
 }
 
 fn legal_string_literal_single_line_character(c: char) -> bool {
-    let test = c != '\\' && c != '"' && c != '\r' && c != '\n';
-    debug!("Checking valid string character {:?}: {:?}", c, test);
-    test
+    c != '\\' && c != '"' && c != '\r' && c != '\n'
 }
 
 fn octal_to_string(s: &str) -> Result<String, InternalKind> {
@@ -64,17 +64,13 @@ fn unindent_heredoc(string: &str, indentation: usize) -> Cow<str> {
 
     let mut result = String::with_capacity(string.len());
     for line in string.split('\n') {
-        // Trim spaces at the beginning first
-        // Let's find a start index up to `indentation` to slice away
-        let mut beginning = line.char_indices().take(indentation);
-        let all_spaces = beginning.all(|(_, c)| c == ' ');
-        let rest = if all_spaces {
-            &line[indentation..]
-        } else {
-            let (start, _) = beginning.next().expect("to not be None");
-            &line[start - 1..]
-        };
-        result.push_str(rest);
+        // Trim up to `indentation` spaces at the beginning
+        let spaces = line
+            .bytes()
+            .take(indentation)
+            .take_while(|byte| *byte == b' ')
+            .count();
+        result.push_str(&line[spaces..]);
         result.push('\n');
     }
     // Remove the last `\n`
@@ -121,9 +117,24 @@ named!(hex_to_unicode(CompleteStr) -> Cow<str>,
     )
 );
 
-// Contents of a single line string
+/// Parse string content up to the first character that is not `legal`. Content with escape
+/// sequences is parsed by `escaped`, and content without them is borrowed from the input.
+fn borrow_unescaped<'a>(
+    input: CompleteStr<'a>,
+    legal: fn(char) -> bool,
+    escaped: fn(CompleteStr<'a>) -> IResult<CompleteStr<'a>, String, u32>,
+) -> IResult<CompleteStr<'a>, Cow<'a, str>, u32> {
+    let (remaining, content) = take_while!(input, legal)?;
+    if remaining.starts_with('\\') {
+        let (remaining, content) = escaped(input)?;
+        Ok((remaining, Cow::Owned(content)))
+    } else {
+        Ok((remaining, Cow::Borrowed(content.0)))
+    }
+}
+
 named!(
-    multiline_string_content(CompleteStr) -> String,
+    escaped_multiline_string_content(CompleteStr) -> String,
     escaped_transform!(
         take_while1!(legal_string_literal_character),
         '\\',
@@ -131,8 +142,17 @@ named!(
     )
 );
 
+// Contents of a quoted string, which may span multiple lines
+fn multiline_string_content(input: CompleteStr) -> IResult<CompleteStr, Cow<str>, u32> {
+    borrow_unescaped(
+        input,
+        legal_string_literal_character,
+        escaped_multiline_string_content,
+    )
+}
+
 named!(
-    quoted_string(CompleteStr) -> String,
+    quoted_string(CompleteStr) -> Cow<str>,
     delimited!(
         tag!("\""),
         call!(multiline_string_content),
@@ -141,7 +161,7 @@ named!(
 );
 
 named!(
-    pub string_literal_content(CompleteStr) -> StringLiteral,
+    escaped_string_literal_content(CompleteStr) -> String,
     escaped_transform!(
         take_while1!(legal_string_literal_single_line_character),
         '\\',
@@ -149,6 +169,15 @@ named!(
     )
 );
 
+/// Parse the contents of a single line string literal
+pub fn string_literal_content(input: CompleteStr) -> IResult<CompleteStr, StringLiteral, u32> {
+    borrow_unescaped(
+        input,
+        legal_string_literal_single_line_character,
+        escaped_string_literal_content,
+    )
+}
+
 named!(
     pub string_literal(CompleteStr) -> StringLiteral,
     delimited!(
@@ -225,13 +254,134 @@ named!(
 named!(
     pub string(CompleteStr) -> Cow<str>,
     alt!(
-        quoted_string => { |s| Cow::Owned(s) }
+        quoted_string
         | heredoc_string
     )
 );
 
-// TODO:
-// - Interpolation `${test("...")}`
+/// A part of a template
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TemplatePart<'a> {
+    /// Literal text
+    Literal(Cow<'a, str>),
+    /// An interpolation sequence, like `${name}`
+    Interpolation(Expression<'a>),
+}
+
+impl<'a> TemplatePart<'a> {
+    pub fn is_literal(&self) -> bool {
+        match self {
+            TemplatePart::Literal(_) => true,
+            TemplatePart::Interpolation(_) => false,
+        }
+    }
+
+    pub fn into_literal(self) -> Option<Cow<'a, str>> {
+        match self {
+            TemplatePart::Literal(literal) => Some(literal),
+            TemplatePart::Interpolation(_) => None,
+        }
+    }
+}
+
+impl<'a> AsOwned for TemplatePart<'a> {
+    type Output = TemplatePart<'static>;
+
+    fn as_owned(&self) -> Self::Output {
+        match self {
+            TemplatePart::Literal(literal) => {
+                TemplatePart::Literal(Cow::Owned(literal.to_string()))
+            }
+            TemplatePart::Interpolation(expr) => TemplatePart::Interpolation(expr.as_owned()),
+        }
+    }
+}
+
+/// A template made of literal text and interpolation sequences
+///
+/// The literal `${` can be written as `$${` in a template.
+pub type Template<'a> = Vec<TemplatePart<'a>>;
+
+// "${" Expression "}"
+named!(
+    pub interpolation(CompleteStr) -> Expression,
+    delimited!(
+        tag!("${"),
+        whitespace!(call!(expression)),
+        tag!("}")
+    )
+);
+
+/// Parse the parts of a template. Quoted templates end with a `"` and have their escape
+/// sequences processed; other templates end with the input.
+fn template_parts(mut input: CompleteStr, quoted: bool) -> IResult<CompleteStr, Template, u32> {
+    let mut parts = vec![];
+    let mut literal = String::new();
+
+    loop {
+        if input.is_empty() && !quoted {
+            break;
+        } else if quoted && input.starts_with('"') {
+            input = input.slice(1..);
+            break;
+        } else if input.starts_with("$${") {
+            literal.push_str("${");
+            input = input.slice(3..);
+        } else if input.starts_with("${") {
+            let (remaining, expr) = interpolation(input)?;
+            if !literal.is_empty() {
+                parts.push(TemplatePart::Literal(Cow::Owned(std::mem::take(
+                    &mut literal,
+                ))));
+            }
+            parts.push(TemplatePart::Interpolation(expr));
+            input = remaining;
+        } else if quoted && input.starts_with('\\') {
+            let (remaining, unescaped) = unescape(input.slice(1..))?;
+            literal.push_str(&unescaped);
+            input = remaining;
+        } else {
+            match input.chars().next() {
+                Some(c) => {
+                    literal.push(c);
+                    input = input.slice(c.len_utf8()..);
+                }
+                None => return Err(nom::Err::Error(error_position!(input, ErrorKind::Eof))),
+            }
+        }
+    }
+
+    if !literal.is_empty() {
+        parts.push(TemplatePart::Literal(Cow::Owned(literal)));
+    }
+    Ok((input, parts))
+}
+
+/// Parse a quoted template, like `"Hello ${name}"`
+pub fn quoted_template(input: CompleteStr) -> IResult<CompleteStr, Template, u32> {
+    let (input, _) = tag!(input, "\"")?;
+    template_parts(input, true)
+}
+
+/// Parse the whole input as the contents of a template, without quotes or escape sequences
+pub fn template(input: CompleteStr) -> IResult<CompleteStr, Template, u32> {
+    template_parts(input, false)
+}
+
+/// Parse a heredoc template
+pub fn heredoc_template(input: CompleteStr) -> IResult<CompleteStr, Template, u32> {
+    let (remaining, content) = heredoc_string(input)?;
+    let template = match content {
+        Cow::Borrowed(content) => template_parts(CompleteStr(content), false)
+            .map(|(_, template)| template)
+            .ok(),
+        Cow::Owned(content) => template_parts(CompleteStr(&content), false)
+            .map(|(_, template)| template.as_owned())
+            .ok(),
+    };
+    let template = template.ok_or(nom::Err::Failure(error_position!(input, ErrorKind::Verify)))?;
+    Ok((remaining, template))
+}
 
 #[cfg(test)]
 mod tests {
@@ -252,8 +402,8 @@ mod tests {
             (r#"\"#, "\\"),
             (r#"""#, "\""),
             ("?", "?"),
-            (r#"xff"#, "ÿ"),           // Hex
-            (r#"251"#, "©"),           // Octal
+            (r#"xff"#, "ÿ"),            // Hex
+            (r#"251"#, "©"),            // Octal
             (r#"uD000"#, "\u{D000}"),   // Unicode up to 4 bytes
             (r#"U29000"#, "\u{29000}"), // Unicode up to 8 bytes... but max unicode is only up to 6
         ];
@@ -323,6 +473,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn strings_without_escapes_are_borrowed() {
+        let (_, borrowed) = quoted_string(CompleteStr(r#""foo bar""#)).unwrap();
+        assert_eq!(borrowed, Cow::Borrowed("foo bar"));
+        assert!(matches!(borrowed, Cow::Borrowed(_)));
+
+        let (_, owned) = string_literal(CompleteStr(r#""foo\tbar""#)).unwrap();
+        assert_eq!(owned, "foo\tbar");
+        assert!(matches!(owned, Cow::Owned(_)));
+    }
+
     #[test]
     fn heredoc_identifier_is_parsed_correctly() {
         let test_cases = [
@@ -486,6 +647,19 @@ the
 spaces
 but    not   these 老虎"#,
             ),
+            (
+                r#"<<-EOF
+    lines
+
+  indented less
+ than the marker 老虎
+    EOF
+"#,
+                r#"lines
+
+indented less
+than the marker 老虎"#,
+            ),
         ];
 
         for (input, expected) in test_cases.iter() {
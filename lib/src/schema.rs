@@ -0,0 +1,804 @@
+//! Schema driven decoding of bodies
+//!
+//! A [`BodySchema`] declares the attributes and blocks that are expected in a body, similar to
+//! [`hcldec`](https://godoc.org/github.com/hashicorp/hcl2/hcldec) in Go. Decoding a [`Body`]
+//! against a schema checks the types of the attributes, the number of labels of the blocks and
+//! collects blocks according to their [`Nesting`] mode.
+//!
+//! Decoding does not stop at the first problem. The values that could be decoded are always
+//! returned, together with a list of [`Diagnostic`]s.
+//!
+//! ```rust
+//! use ferrous_chloride::parse_str;
+//! use ferrous_chloride::schema::*;
+//!
+//! let schema = BodySchema::new().block(
+//!     BlockSchema::new("resource", Nesting::Map)
+//!         .labels(&["type", "name"])
+//!         .body(
+//!             BodySchema::new()
+//!                 .attribute(AttributeSchema::required("name", Type::String))
+//!                 .attribute(AttributeSchema::optional("count", Type::Number)),
+//!         ),
+//! );
+//!
+//! let body = parse_str(r#"
+//! resource "instance" "foo" {
+//!   name = "foo"
+//!   size = "large"
+//! }
+//! "#).unwrap();
+//!
+//! let decoded = schema.decode(&body);
+//! assert_eq!(decoded.diagnostics.len(), 1);
+//! assert_eq!(
+//!     decoded.diagnostics[0].to_string(),
+//!     "resource.instance.foo.size: Unknown attribute"
+//! );
+//!
+//! match &decoded.body.blocks["resource"] {
+//!     DecodedBlocks::Map(map) => assert!(map.contains_key(&vec!["instance", "foo"])),
+//!     _ => unreachable!(),
+//! }
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+
+use failure_derive::Fail;
+use indexmap::IndexMap;
+
+use crate::parser::block::Block;
+use crate::parser::body::{Body, BodyElement};
+use crate::parser::expression::Expression;
+use crate::parser::parse_str;
+use crate::select::{Segment, Selector};
+use crate::span::Span;
+use crate::Error;
+
+/// The expected type of an attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    /// Any value is accepted
+    Any,
+    String,
+    Number,
+    Bool,
+    /// A tuple with elements of the same type
+    List(Box<Type>),
+    /// An object with values of the same type
+    Map(Box<Type>),
+}
+
+/// How multiple blocks of the same type are collected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nesting {
+    /// At most one block is allowed
+    Single,
+    /// Blocks are collected in the order they appear
+    List,
+    /// Blocks are collected in the order they appear, with duplicate blocks removed
+    Set,
+    /// Blocks are collected into a map keyed by their labels. Each block must have unique labels.
+    Map,
+}
+
+/// Schema for an attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeSchema {
+    pub name: String,
+    pub r#type: Type,
+    pub required: bool,
+}
+
+/// Schema for blocks of a type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSchema {
+    pub r#type: String,
+    /// Names of the labels of the block. Blocks must have exactly this number of labels.
+    pub labels: Vec<String>,
+    pub nesting: Nesting,
+    /// Whether at least one block is required
+    pub required: bool,
+    pub body: BodySchema,
+}
+
+/// Schema for a body
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct BodySchema {
+    pub attributes: Vec<AttributeSchema>,
+    pub blocks: Vec<BlockSchema>,
+}
+
+/// The result of decoding a body against a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decoded<'s, 'a> {
+    /// Values that were successfully decoded
+    pub body: DecodedBody<'s, 'a>,
+    /// Problems found while decoding
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// A body decoded against a schema
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DecodedBody<'s, 'a> {
+    /// Attributes which are present and have the expected type, in document order
+    pub attributes: IndexMap<String, &'s Expression<'a>>,
+    /// Blocks, keyed by the block type in schema order. Every block type in the schema has an
+    /// entry.
+    pub blocks: IndexMap<String, DecodedBlocks<'s, 'a>>,
+}
+
+/// A block decoded against a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedBlock<'s, 'a> {
+    /// Values of the labels, in the order of the label names in the schema
+    pub labels: Vec<&'s str>,
+    pub body: DecodedBody<'s, 'a>,
+}
+
+/// Blocks of a type collected according to the `Nesting` of their schema
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedBlocks<'s, 'a> {
+    Single(Option<DecodedBlock<'s, 'a>>),
+    List(Vec<DecodedBlock<'s, 'a>>),
+    Set(Vec<DecodedBlock<'s, 'a>>),
+    /// Blocks keyed by their labels, in document order
+    Map(IndexMap<Vec<&'s str>, DecodedBlock<'s, 'a>>),
+}
+
+/// A problem found while decoding a body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// Path to the attribute or block with the problem
+    pub path: Selector,
+    pub kind: DiagnosticKind,
+    /// Location of the name of the attribute or the type of the block in the source
+    pub span: Option<Span>,
+}
+
+/// The kinds of problems found while decoding a body
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum DiagnosticKind {
+    #[fail(display = "Unknown attribute")]
+    UnknownAttribute,
+
+    #[fail(display = "Unexpected block")]
+    UnexpectedBlock,
+
+    #[fail(display = "Missing required attribute")]
+    MissingAttribute,
+
+    #[fail(display = "Missing required block")]
+    MissingBlock,
+
+    #[fail(display = "Duplicate attribute")]
+    DuplicateAttribute,
+
+    #[fail(display = "Duplicate block")]
+    DuplicateBlock,
+
+    #[fail(display = "Expected {} labels but got {}", expected, actual)]
+    WrongLabelCount { expected: usize, actual: usize },
+
+    #[fail(display = "Expected a value of type {} but got {}", expected, actual)]
+    TypeMismatch {
+        expected: Type,
+        actual: &'static str,
+    },
+}
+
+impl Type {
+    /// Returns whether the expression is of this type. `null` is of every type, and so are
+    /// expressions that have to be evaluated.
+    pub fn matches(&self, expression: &Expression) -> bool {
+        match (self, expression) {
+            (_, Expression::Null) | (Type::Any, _) => true,
+            (Type::String, Expression::String(_))
+            | (Type::Number, Expression::Number(_))
+            | (Type::Bool, Expression::Boolean(_)) => true,
+            (Type::List(element), Expression::Tuple(tuple)) => {
+                tuple.iter().all(|expr| element.matches(expr))
+            }
+            (Type::Map(element), Expression::Object(object)) => {
+                object.iter().all(|(_, expr)| element.matches(expr))
+            }
+            // The type of expressions that have to be evaluated is not known
+            (_, Expression::Template(_))
+            | (_, Expression::Variable(_))
+            | (_, Expression::GetAttr(..))
+            | (_, Expression::Index(..))
+            | (_, Expression::FunctionCall(..))
+            | (_, Expression::Operation(_))
+            | (_, Expression::Conditional(_)) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Any => f.write_str("any"),
+            Type::String => f.write_str("string"),
+            Type::Number => f.write_str("number"),
+            Type::Bool => f.write_str("bool"),
+            Type::List(element) => write!(f, "list({})", element),
+            Type::Map(element) => write!(f, "map({})", element),
+        }
+    }
+}
+
+impl AttributeSchema {
+    pub fn new<S: Into<String>>(name: S, r#type: Type, required: bool) -> Self {
+        Self {
+            name: name.into(),
+            r#type,
+            required,
+        }
+    }
+
+    pub fn required<S: Into<String>>(name: S, r#type: Type) -> Self {
+        Self::new(name, r#type, true)
+    }
+
+    pub fn optional<S: Into<String>>(name: S, r#type: Type) -> Self {
+        Self::new(name, r#type, false)
+    }
+}
+
+impl BlockSchema {
+    /// Create a schema for optional blocks with no labels and an empty body
+    pub fn new<S: Into<String>>(r#type: S, nesting: Nesting) -> Self {
+        Self {
+            r#type: r#type.into(),
+            labels: vec![],
+            nesting,
+            required: false,
+            body: BodySchema::default(),
+        }
+    }
+
+    /// Set the names of the labels
+    pub fn labels<S: AsRef<str>>(mut self, labels: &[S]) -> Self {
+        self.labels = labels
+            .iter()
+            .map(|label| label.as_ref().to_string())
+            .collect();
+        self
+    }
+
+    /// Require at least one block
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Set the schema of the body
+    pub fn body(mut self, body: BodySchema) -> Self {
+        self.body = body;
+        self
+    }
+}
+
+impl BodySchema {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add an attribute to the schema
+    pub fn attribute(mut self, attribute: AttributeSchema) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    /// Add a block type to the schema
+    pub fn block(mut self, block: BlockSchema) -> Self {
+        self.blocks.push(block);
+        self
+    }
+
+    /// Decode a body against this schema
+    ///
+    /// Diagnostics do not have a location. Use [`validate`](BodySchema::validate) to get
+    /// diagnostics with their locations in the source.
+    pub fn decode<'s, 'a>(&self, body: &'s Body<'a>) -> Decoded<'s, 'a> {
+        let mut decoder = Decoder::new(None);
+        let body = decoder.decode_body(self, body, None);
+        Decoded {
+            body,
+            diagnostics: decoder.diagnostics,
+        }
+    }
+
+    /// Parse `source` and validate it against this schema
+    ///
+    /// Returns the diagnostics found, each with the location of the attribute or block in
+    /// `source`. Diagnostics for missing attributes and blocks are located at the block that
+    /// should contain them, and have no location at the top level.
+    pub fn validate(&self, source: &str) -> Result<Vec<Diagnostic>, Error> {
+        let body = parse_str(source)?;
+        let mut decoder = Decoder::new(Some(source));
+        decoder.decode_body(self, &body, None);
+        Ok(decoder.diagnostics)
+    }
+}
+
+impl<'s, 'a> Decoded<'s, 'a> {
+    /// Returns the decoded body if there are no diagnostics
+    pub fn into_result(self) -> Result<DecodedBody<'s, 'a>, Vec<Diagnostic>> {
+        if self.diagnostics.is_empty() {
+            Ok(self.body)
+        } else {
+            Err(self.diagnostics)
+        }
+    }
+}
+
+impl<'s, 'a> DecodedBlocks<'s, 'a> {
+    /// Iterate over the decoded blocks
+    pub fn iter<'b>(&'b self) -> Box<dyn Iterator<Item = &'b DecodedBlock<'s, 'a>> + 'b> {
+        match self {
+            DecodedBlocks::Single(block) => Box::new(block.iter()),
+            DecodedBlocks::List(blocks) | DecodedBlocks::Set(blocks) => Box::new(blocks.iter()),
+            DecodedBlocks::Map(blocks) => Box::new(blocks.values()),
+        }
+    }
+
+    /// Number of decoded blocks
+    pub fn len(&self) -> usize {
+        match self {
+            DecodedBlocks::Single(block) => block.iter().count(),
+            DecodedBlocks::List(blocks) | DecodedBlocks::Set(blocks) => blocks.len(),
+            DecodedBlocks::Map(blocks) => blocks.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(span) = &self.span {
+            write!(f, "{}: ", span.start)?;
+        }
+        write!(f, "{}: {}", self.path, self.kind)
+    }
+}
+
+/// State while decoding a body
+struct Decoder<'src> {
+    /// Source of the body, used to locate diagnostics
+    source: Option<&'src str>,
+    path: Vec<Segment>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'src> Decoder<'src> {
+    fn new(source: Option<&'src str>) -> Self {
+        Self {
+            source,
+            path: vec![],
+            diagnostics: vec![],
+        }
+    }
+
+    /// Add a diagnostic for the current path, located at `at` if it is a slice of the source
+    fn diagnose(&mut self, kind: DiagnosticKind, at: Option<&str>) {
+        let span = match (self.source, at) {
+            (Some(source), Some(at)) => Span::of(source, at),
+            _ => None,
+        };
+        self.diagnostics.push(Diagnostic {
+            path: Selector::new(self.path.clone()),
+            kind,
+            span,
+        });
+    }
+
+    /// Add a diagnostic for `block`
+    fn diagnose_block(&mut self, kind: DiagnosticKind, block: &Block) {
+        let depth = self.path.len();
+        self.path.extend(block_path(block));
+        self.diagnose(kind, Some(&block.r#type));
+        self.path.truncate(depth);
+    }
+
+    fn decode_block<'s, 'a>(
+        &mut self,
+        schema: &BlockSchema,
+        block: &'s Block<'a>,
+    ) -> DecodedBlock<'s, 'a> {
+        let depth = self.path.len();
+        self.path.extend(block_path(block));
+        let body = self.decode_body(&schema.body, &block.body, Some(&block.r#type));
+        self.path.truncate(depth);
+        DecodedBlock {
+            labels: block.labels.iter().map(|label| label.as_str()).collect(),
+            body,
+        }
+    }
+
+    /// Decode `body`. `parent` is the type of the block containing the body, if any.
+    fn decode_body<'s, 'a>(
+        &mut self,
+        schema: &BodySchema,
+        body: &'s Body<'a>,
+        parent: Option<&str>,
+    ) -> DecodedBody<'s, 'a> {
+        let mut decoded = DecodedBody::default();
+        let mut seen_attributes: Vec<&str> = vec![];
+        let mut blocks: HashMap<&str, Vec<&Block>> = HashMap::new();
+
+        for element in body {
+            match element {
+                BodyElement::Attribute((name, expr)) => {
+                    self.path.push(Segment::Name(name.to_string()));
+                    let attribute_schema = schema
+                        .attributes
+                        .iter()
+                        .find(|attribute| attribute.name == *name);
+                    match attribute_schema {
+                        None => self.diagnose(DiagnosticKind::UnknownAttribute, Some(name)),
+                        Some(_) if seen_attributes.contains(&name.as_ref()) => {
+                            self.diagnose(DiagnosticKind::DuplicateAttribute, Some(name))
+                        }
+                        Some(attribute_schema) => {
+                            seen_attributes.push(name);
+                            if attribute_schema.r#type.matches(expr) {
+                                decoded.attributes.insert(name.to_string(), expr);
+                            } else {
+                                let kind = DiagnosticKind::TypeMismatch {
+                                    expected: attribute_schema.r#type.clone(),
+                                    actual: expr.variant_name(),
+                                };
+                                self.diagnose(kind, Some(name));
+                            }
+                        }
+                    }
+                    self.path.pop();
+                }
+                BodyElement::Block(block) => {
+                    let block_schema = schema
+                        .blocks
+                        .iter()
+                        .find(|block_schema| block_schema.r#type == block.r#type);
+                    match block_schema {
+                        None => self.diagnose_block(DiagnosticKind::UnexpectedBlock, block),
+                        Some(block_schema) if block_schema.labels.len() != block.labels.len() => {
+                            let kind = DiagnosticKind::WrongLabelCount {
+                                expected: block_schema.labels.len(),
+                                actual: block.labels.len(),
+                            };
+                            self.diagnose_block(kind, block);
+                        }
+                        Some(block_schema) => blocks
+                            .entry(block_schema.r#type.as_str())
+                            .or_default()
+                            .push(block),
+                    }
+                }
+            }
+        }
+
+        for attribute_schema in &schema.attributes {
+            if attribute_schema.required
+                && !seen_attributes.contains(&attribute_schema.name.as_str())
+            {
+                self.path.push(Segment::Name(attribute_schema.name.clone()));
+                self.diagnose(DiagnosticKind::MissingAttribute, parent);
+                self.path.pop();
+            }
+        }
+
+        for block_schema in &schema.blocks {
+            let blocks = blocks
+                .remove(block_schema.r#type.as_str())
+                .unwrap_or_default();
+            if block_schema.required && blocks.is_empty() {
+                self.path.push(Segment::Name(block_schema.r#type.clone()));
+                self.diagnose(DiagnosticKind::MissingBlock, parent);
+                self.path.pop();
+            }
+
+            let decoded_blocks = match block_schema.nesting {
+                Nesting::Single => {
+                    for duplicate in blocks.iter().skip(1) {
+                        self.diagnose_block(DiagnosticKind::DuplicateBlock, duplicate);
+                    }
+                    DecodedBlocks::Single(
+                        blocks
+                            .first()
+                            .map(|block| self.decode_block(block_schema, block)),
+                    )
+                }
+                Nesting::List => DecodedBlocks::List(
+                    blocks
+                        .iter()
+                        .map(|block| self.decode_block(block_schema, block))
+                        .collect(),
+                ),
+                Nesting::Set => {
+                    let mut unique: Vec<&Block> = vec![];
+                    for block in blocks {
+                        if !unique.contains(&block) {
+                            unique.push(block);
+                        }
+                    }
+                    DecodedBlocks::Set(
+                        unique
+                            .into_iter()
+                            .map(|block| self.decode_block(block_schema, block))
+                            .collect(),
+                    )
+                }
+                Nesting::Map => {
+                    let mut map = IndexMap::new();
+                    for block in blocks {
+                        let decoded_block = self.decode_block(block_schema, block);
+                        if map.contains_key(&decoded_block.labels) {
+                            self.diagnose_block(DiagnosticKind::DuplicateBlock, block);
+                        } else {
+                            map.insert(decoded_block.labels.clone(), decoded_block);
+                        }
+                    }
+                    DecodedBlocks::Map(map)
+                }
+            };
+            decoded
+                .blocks
+                .insert(block_schema.r#type.clone(), decoded_blocks);
+        }
+
+        decoded
+    }
+}
+
+fn block_path<'b>(block: &'b Block) -> impl Iterator<Item = Segment> + 'b {
+    std::iter::once(Segment::Name(block.r#type.to_string())).chain(
+        block
+            .labels
+            .iter()
+            .map(|label| Segment::Name(label.as_str().to_string())),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixtures;
+
+    fn block_fixture_schema() -> BodySchema {
+        let rule = BodySchema::new()
+            .attribute(AttributeSchema::required("name", Type::String))
+            .attribute(AttributeSchema::required(
+                "cidrs",
+                Type::List(Box::new(Type::String)),
+            ));
+
+        BodySchema::new()
+            .block(
+                BlockSchema::new("simple_map", Nesting::List).body(
+                    BodySchema::new()
+                        .attribute(AttributeSchema::optional("foo", Type::String))
+                        .attribute(AttributeSchema::optional("bar", Type::String))
+                        .attribute(AttributeSchema::required("index", Type::Number)),
+                ),
+            )
+            .block(
+                BlockSchema::new("resource", Nesting::Map)
+                    .labels(&["type", "name"])
+                    .required()
+                    .body(
+                        BodySchema::new()
+                            .attribute(AttributeSchema::required("name", Type::String))
+                            .attribute(AttributeSchema::optional("image", Type::String))
+                            .block(BlockSchema::new("allow", Nesting::Set).body(rule.clone()))
+                            .block(BlockSchema::new("deny", Nesting::Single).body(rule))
+                            .block(
+                                BlockSchema::new("user", Nesting::Map)
+                                    .labels(&["name"])
+                                    .body(
+                                        BodySchema::new().attribute(AttributeSchema::optional(
+                                            "root",
+                                            Type::Bool,
+                                        )),
+                                    ),
+                            ),
+                    ),
+            )
+    }
+
+    #[test]
+    fn types_match_expressions_correctly() {
+        let list_of_strings = Type::List(Box::new(Type::String));
+        let map_of_numbers = Type::Map(Box::new(Type::Number));
+        let test_cases = [
+            (Type::Any, Expression::from("a"), true),
+            (Type::String, Expression::from("a"), true),
+            (Type::String, Expression::Null, true),
+            (Type::String, Expression::from(1), false),
+            (Type::Number, Expression::from(1.5), true),
+            (Type::Bool, Expression::from(true), true),
+            (Type::Bool, Expression::from("true"), false),
+            (
+                list_of_strings.clone(),
+                Expression::new_tuple(vec![Expression::from("a"), Expression::from("b")]),
+                true,
+            ),
+            (
+                list_of_strings.clone(),
+                Expression::new_tuple(vec![Expression::from("a"), Expression::from(1)]),
+                false,
+            ),
+            (list_of_strings, Expression::from("a"), false),
+            (
+                map_of_numbers.clone(),
+                Expression::new_object(vec![("a", Expression::from(1))]),
+                true,
+            ),
+            (
+                map_of_numbers,
+                Expression::new_object(vec![("a", Expression::from(false))]),
+                false,
+            ),
+        ];
+
+        for (r#type, expression, expected) in test_cases.iter() {
+            assert_eq!(
+                r#type.matches(expression),
+                *expected,
+                "{} and {:?}",
+                r#type,
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn fixture_is_decoded_correctly() {
+        let body = parse_str(fixtures::BLOCK).unwrap();
+        let decoded = block_fixture_schema().decode(&body).into_result().unwrap();
+
+        assert_eq!(decoded.blocks["simple_map"].len(), 2);
+
+        let resources = match &decoded.blocks["resource"] {
+            DecodedBlocks::Map(resources) => resources,
+            other => panic!("Unexpected blocks {:?}", other),
+        };
+        assert_eq!(resources.len(), 3);
+
+        let foobar = &resources[&vec!["security/group", "foobar"]];
+        assert_eq!(foobar.labels, ["security/group", "foobar"]);
+        assert_eq!(foobar.body.attributes["name"], &Expression::from("foobar"));
+        assert_eq!(foobar.body.blocks["allow"].len(), 2);
+        match &foobar.body.blocks["deny"] {
+            DecodedBlocks::Single(Some(deny)) => {
+                assert_eq!(deny.body.attributes["name"], &Expression::from("internet"))
+            }
+            other => panic!("Unexpected blocks {:?}", other),
+        }
+        assert!(foobar.body.blocks["user"].is_empty());
+
+        let instance = &resources[&vec!["instance", "an_instance"]];
+        assert_eq!(instance.body.blocks["user"].len(), 1);
+    }
+
+    #[test]
+    fn diagnostics_are_reported() {
+        let hcl = r#"
+simple_map {
+  foo = 1
+  extra = true
+}
+
+simple_map "labelled" {
+  index = 1
+}
+
+resource "instance" "foo" {
+  name = "foo"
+  name = "bar"
+
+  allow {
+    name = "all"
+    cidrs = ["0.0.0.0/0"]
+  }
+
+  allow {
+    name = "all"
+    cidrs = ["0.0.0.0/0"]
+  }
+
+  deny {
+    name = "one"
+    cidrs = []
+  }
+
+  deny {
+    name = "two"
+    cidrs = []
+  }
+
+  unknown {}
+}
+
+resource "instance" "foo" {
+  name = "foo"
+}
+"#;
+        let body = parse_str(hcl).unwrap();
+        let decoded = block_fixture_schema().decode(&body);
+
+        let diagnostics: Vec<_> = decoded
+            .diagnostics
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                "simple_map.labelled: Expected 0 labels but got 1",
+                "simple_map.foo: Expected a value of type string but got Number",
+                "simple_map.extra: Unknown attribute",
+                "simple_map.index: Missing required attribute",
+                "resource.instance.foo.name: Duplicate attribute",
+                "resource.instance.foo.unknown: Unexpected block",
+                "resource.instance.foo.deny: Duplicate block",
+                "resource.instance.foo: Duplicate block",
+            ]
+        );
+
+        // Duplicate blocks in a set are merged
+        let resource = decoded.body.blocks["resource"].iter().next().unwrap();
+        assert_eq!(resource.body.blocks["allow"].len(), 1);
+    }
+
+    #[test]
+    fn validation_diagnostics_are_located() {
+        let hcl = r#"resource "security/group" "foobar" {
+  name = "foobar"
+
+  allow {
+    name = "localhost"
+    cidr = ["127.0.0.1/32"]
+  }
+
+  user {}
+}
+"#;
+        let diagnostics: Vec<_> = block_fixture_schema()
+            .validate(hcl)
+            .unwrap()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            diagnostics,
+            [
+                r#"9:3: resource."security/group".foobar.user: Expected 1 labels but got 0"#,
+                r#"6:5: resource."security/group".foobar.allow.cidr: Unknown attribute"#,
+                r#"4:3: resource."security/group".foobar.allow.cidrs: Missing required attribute"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_blocks_are_reported() {
+        let body = parse_str("").unwrap();
+        let decoded = block_fixture_schema().decode(&body);
+
+        assert_eq!(
+            decoded.diagnostics,
+            [Diagnostic {
+                path: "resource".parse().unwrap(),
+                kind: DiagnosticKind::MissingBlock,
+                span: None,
+            }]
+        );
+        assert!(decoded.body.blocks["simple_map"].is_empty());
+    }
+}
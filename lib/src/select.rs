@@ -0,0 +1,651 @@
+//! Select nodes from a parsed HCL document using a path
+//!
+//! A selector is a sequence of segments separated by `.`:
+//!
+//! - A bare name or a quoted string (for names or labels with characters such as `.` or `/`)
+//!   selects attributes or blocks of a body by name, the next label of blocks or keys of an
+//!   object.
+//! - `*` selects every attribute and block of a body, every next label of blocks, and every
+//!   element of an object or tuple.
+//! - `[n]` selects the n-th element of a tuple, or the n-th block out of several blocks with the
+//!   same type and labels. `[*]` selects all of them.
+//! - `..` in place of `.` selects the following segment recursively at any depth.
+//!
+//! ```rust
+//! use ferrous_chloride::parse_str;
+//! use ferrous_chloride::select::{Node, Select};
+//!
+//! let body = parse_str(r#"
+//! resource "instance" "foo" {
+//!   name = "foo"
+//! }
+//!
+//! resource "security_group" "bar" {
+//!   name = "bar"
+//! }
+//! "#).unwrap();
+//!
+//! let names: Vec<_> = body
+//!     .select("resource.*.*.name")
+//!     .unwrap()
+//!     .into_iter()
+//!     .filter_map(Node::expression)
+//!     .map(ToString::to_string)
+//!     .collect();
+//!
+//! assert_eq!(names, [r#""foo""#, r#""bar""#]);
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use crate::parser::block::Block;
+use crate::parser::body::{Body, BodyElement};
+use crate::parser::expression::Expression;
+use crate::Error;
+
+/// A segment of a `Selector`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    /// Select attributes, blocks, labels or object keys by name
+    Name(String),
+    /// `*`: Select all attributes, blocks, labels, and object or tuple elements
+    Wildcard,
+    /// `[n]`: Select an element of a tuple or a block out of several blocks
+    Index(usize),
+    /// `[*]`: Select all elements of a tuple or all blocks out of several blocks
+    IndexWildcard,
+    /// `..`: Select the current node and all of its descendants
+    RecursiveDescent,
+}
+
+/// A parsed path used to select nodes from a document
+///
+/// See the [module level documentation](index.html) for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Selector {
+    segments: Vec<Segment>,
+}
+
+/// A node matched by a `Selector`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Node<'s, 'a> {
+    Body(&'s Body<'a>),
+    Block(&'s Block<'a>),
+    Expression(&'s Expression<'a>),
+}
+
+/// Types that nodes can be selected from
+pub trait Select<'a> {
+    /// Parse `selector` and return the nodes it matches, in document order
+    fn select<'s>(&'s self, selector: &str) -> Result<Vec<Node<'s, 'a>>, Error>;
+}
+
+/// Intermediate node while a selector is being applied
+#[derive(Debug, Clone)]
+enum Cursor<'s, 'a> {
+    Body(&'s Body<'a>),
+    /// Blocks of the same type with the same first `depth` labels
+    Blocks {
+        blocks: Vec<&'s Block<'a>>,
+        depth: usize,
+    },
+    Expression(&'s Expression<'a>),
+}
+
+impl Selector {
+    pub fn new(segments: Vec<Segment>) -> Self {
+        Self { segments }
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /// Return the nodes in `body` matched by this selector
+    pub fn select<'s, 'a>(&self, body: &'s Body<'a>) -> Vec<Node<'s, 'a>> {
+        self.apply(Cursor::Body(body))
+    }
+
+    /// Return the nodes in `expression` matched by this selector
+    pub fn select_expression<'s, 'a>(&self, expression: &'s Expression<'a>) -> Vec<Node<'s, 'a>> {
+        self.apply(Cursor::Expression(expression))
+    }
+
+    fn apply<'s, 'a>(&self, root: Cursor<'s, 'a>) -> Vec<Node<'s, 'a>> {
+        let mut cursors = vec![root];
+        for segment in &self.segments {
+            let mut next = vec![];
+            for cursor in cursors {
+                step(cursor, segment, &mut next);
+            }
+            cursors = next;
+        }
+
+        let mut nodes = vec![];
+        for cursor in cursors {
+            match cursor {
+                Cursor::Body(body) => nodes.push(Node::Body(body)),
+                Cursor::Blocks { blocks, .. } => nodes.extend(blocks.into_iter().map(Node::Block)),
+                Cursor::Expression(expr) => nodes.push(Node::Expression(expr)),
+            }
+        }
+        nodes
+    }
+}
+
+impl FromStr for Selector {
+    type Err = Error;
+
+    fn from_str(selector: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| Error::InvalidSelector(format!("{}: {}", selector, reason));
+
+        let mut segments = vec![];
+        let mut chars = selector.chars().peekable();
+        let mut expect_name = true;
+
+        if selector.starts_with("..") {
+            chars.next();
+            chars.next();
+            segments.push(Segment::RecursiveDescent);
+        }
+
+        while let Some(c) = chars.peek().cloned() {
+            match c {
+                '.' if !expect_name => {
+                    chars.next();
+                    if chars.peek() == Some(&'.') {
+                        chars.next();
+                        segments.push(Segment::RecursiveDescent);
+                    }
+                    expect_name = true;
+                }
+                // An index follows a segment directly, or `..` and the start of the selector
+                '[' if !expect_name
+                    || segments.is_empty()
+                    || segments.last() == Some(&Segment::RecursiveDescent) =>
+                {
+                    chars.next();
+                    let mut index = String::new();
+                    loop {
+                        match chars.next() {
+                            None => return Err(invalid("unterminated index")),
+                            Some(']') => break,
+                            Some(c) => index.push(c),
+                        }
+                    }
+                    let segment = match index.trim() {
+                        "*" => Segment::IndexWildcard,
+                        index => Segment::Index(
+                            index
+                                .parse()
+                                .map_err(|_| invalid("indices must be non-negative integers"))?,
+                        ),
+                    };
+                    segments.push(segment);
+                    expect_name = false;
+                }
+                '"' if expect_name => {
+                    chars.next();
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            None => return Err(invalid("unterminated quoted string")),
+                            Some('"') => break,
+                            Some('\\') => match chars.next() {
+                                Some(escaped) => name.push(escaped),
+                                None => return Err(invalid("unterminated quoted string")),
+                            },
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    segments.push(Segment::Name(name));
+                    expect_name = false;
+                }
+                _ if expect_name => {
+                    let mut name = String::new();
+                    while let Some(c) = chars.peek().cloned() {
+                        if c == '.' || c == '[' || c == '"' {
+                            break;
+                        }
+                        name.push(c);
+                        chars.next();
+                    }
+                    match name.as_str() {
+                        "" => return Err(invalid("empty name")),
+                        "*" => segments.push(Segment::Wildcard),
+                        _ => segments.push(Segment::Name(name)),
+                    }
+                    expect_name = false;
+                }
+                c => return Err(invalid(&format!("unexpected character {:?}", c))),
+            }
+        }
+
+        if expect_name && !selector.is_empty() {
+            return Err(invalid("selector cannot end with `.`"));
+        }
+
+        Ok(Self { segments })
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        let mut previous = None;
+        for segment in &self.segments {
+            let after_descent = previous == Some(&Segment::RecursiveDescent);
+            match segment {
+                // Indices do not write a separator of their own, so complete the `..`
+                Segment::Index(_) | Segment::IndexWildcard if after_descent => f.write_str(".")?,
+                _ => {}
+            }
+            match segment {
+                Segment::Index(index) => write!(f, "[{}]", index)?,
+                Segment::IndexWildcard => f.write_str("[*]")?,
+                // The separator of the following segment is the second `.`
+                Segment::RecursiveDescent => f.write_str(".")?,
+                Segment::Wildcard | Segment::Name(_) => {
+                    if !first {
+                        f.write_str(".")?;
+                    }
+                    match segment {
+                        Segment::Name(name) if is_bare_name(name) => f.write_str(name)?,
+                        Segment::Name(name) => write!(f, "{:?}", name)?,
+                        _ => f.write_str("*")?,
+                    }
+                }
+            }
+            first = false;
+            previous = Some(segment);
+        }
+        Ok(())
+    }
+}
+
+/// Names that can be written without quotes
+fn is_bare_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "*"
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+}
+
+impl<'s, 'a> Node<'s, 'a> {
+    /// Returns the expression if the node is an expression
+    pub fn expression(self) -> Option<&'s Expression<'a>> {
+        match self {
+            Node::Expression(expr) => Some(expr),
+            _ => None,
+        }
+    }
+
+    /// Returns the body of the node if the node is a body or a block
+    pub fn body(self) -> Option<&'s Body<'a>> {
+        match self {
+            Node::Body(body) => Some(body),
+            Node::Block(block) => Some(&block.body),
+            Node::Expression(_) => None,
+        }
+    }
+}
+
+impl<'a> Select<'a> for Body<'a> {
+    fn select<'s>(&'s self, selector: &str) -> Result<Vec<Node<'s, 'a>>, Error> {
+        Ok(selector.parse::<Selector>()?.select(self))
+    }
+}
+
+impl<'a> Select<'a> for Expression<'a> {
+    fn select<'s>(&'s self, selector: &str) -> Result<Vec<Node<'s, 'a>>, Error> {
+        Ok(selector.parse::<Selector>()?.select_expression(self))
+    }
+}
+
+/// Group blocks by a key, preserving the order in which each key first appears
+fn group_by_key<'s, 'a, F>(blocks: Vec<&'s Block<'a>>, key: F) -> Vec<Vec<&'s Block<'a>>>
+where
+    F: Fn(&'s Block<'a>) -> &'s str,
+{
+    let mut groups: Vec<(&str, Vec<&Block>)> = vec![];
+    for block in blocks {
+        let block_key = key(block);
+        match groups.iter_mut().find(|(k, _)| *k == block_key) {
+            Some((_, group)) => group.push(block),
+            None => groups.push((block_key, vec![block])),
+        }
+    }
+    groups.into_iter().map(|(_, group)| group).collect()
+}
+
+/// Split blocks into those with more labels than `depth` and those without
+fn partition_blocks<'s, 'a>(
+    blocks: Vec<&'s Block<'a>>,
+    depth: usize,
+) -> (Vec<&'s Block<'a>>, Vec<&'s Block<'a>>) {
+    blocks
+        .into_iter()
+        .partition(|block| block.labels.len() > depth)
+}
+
+fn step<'s, 'a>(cursor: Cursor<'s, 'a>, segment: &Segment, output: &mut Vec<Cursor<'s, 'a>>) {
+    match (cursor, segment) {
+        (cursor, Segment::RecursiveDescent) => descendants(cursor, true, output),
+        (Cursor::Body(body), Segment::Name(name)) => {
+            let mut blocks = vec![];
+            for element in body {
+                match element {
+                    BodyElement::Attribute((ident, expr)) if ident == name => {
+                        output.push(Cursor::Expression(expr))
+                    }
+                    BodyElement::Block(block) if block.r#type == *name => blocks.push(block),
+                    _ => {}
+                }
+            }
+            if !blocks.is_empty() {
+                output.push(Cursor::Blocks { blocks, depth: 0 });
+            }
+        }
+        (Cursor::Body(body), Segment::Wildcard) => {
+            let mut blocks = vec![];
+            for element in body {
+                match element {
+                    BodyElement::Attribute((_, expr)) => output.push(Cursor::Expression(expr)),
+                    BodyElement::Block(block) => blocks.push(block),
+                }
+            }
+            output.extend(
+                group_by_key(blocks, |block| &block.r#type)
+                    .into_iter()
+                    .map(|blocks| Cursor::Blocks { blocks, depth: 0 }),
+            );
+        }
+        (Cursor::Body(_), _) => {}
+        (Cursor::Blocks { blocks, depth }, Segment::Name(name)) => {
+            let (labelled, exhausted) = partition_blocks(blocks, depth);
+            let blocks: Vec<_> = labelled
+                .into_iter()
+                .filter(|block| block.labels[depth] == *name)
+                .collect();
+            if !blocks.is_empty() {
+                output.push(Cursor::Blocks {
+                    blocks,
+                    depth: depth + 1,
+                });
+            }
+            for block in exhausted {
+                step(Cursor::Body(&block.body), segment, output);
+            }
+        }
+        (Cursor::Blocks { blocks, depth }, Segment::Wildcard) => {
+            let (labelled, exhausted) = partition_blocks(blocks, depth);
+            output.extend(
+                group_by_key(labelled, |block| block.labels[depth].as_str())
+                    .into_iter()
+                    .map(|blocks| Cursor::Blocks {
+                        blocks,
+                        depth: depth + 1,
+                    }),
+            );
+            for block in exhausted {
+                step(Cursor::Body(&block.body), segment, output);
+            }
+        }
+        (Cursor::Blocks { blocks, depth }, Segment::Index(index)) => {
+            let (_, exhausted) = partition_blocks(blocks, depth);
+            if let Some(block) = exhausted.get(*index) {
+                output.push(Cursor::Blocks {
+                    blocks: vec![block],
+                    depth,
+                });
+            }
+        }
+        (Cursor::Blocks { blocks, depth }, Segment::IndexWildcard) => {
+            let (_, exhausted) = partition_blocks(blocks, depth);
+            output.extend(exhausted.into_iter().map(|block| Cursor::Blocks {
+                blocks: vec![block],
+                depth,
+            }));
+        }
+        (Cursor::Expression(Expression::Object(object)), Segment::Name(name)) => output.extend(
+            object
+                .iter()
+                .filter(|(key, _)| key == name)
+                .map(|(_, expr)| Cursor::Expression(expr)),
+        ),
+        (Cursor::Expression(Expression::Object(object)), Segment::Wildcard) => {
+            output.extend(object.iter().map(|(_, expr)| Cursor::Expression(expr)))
+        }
+        (Cursor::Expression(Expression::Tuple(tuple)), Segment::Index(index)) => {
+            output.extend(tuple.get(*index).map(Cursor::Expression))
+        }
+        (Cursor::Expression(Expression::Tuple(tuple)), Segment::Wildcard)
+        | (Cursor::Expression(Expression::Tuple(tuple)), Segment::IndexWildcard) => {
+            output.extend(tuple.iter().map(Cursor::Expression))
+        }
+        (Cursor::Expression(_), _) => {}
+    }
+}
+
+/// Collect `cursor` (if `include_self`) and all nodes below it
+fn descendants<'s, 'a>(
+    cursor: Cursor<'s, 'a>,
+    include_self: bool,
+    output: &mut Vec<Cursor<'s, 'a>>,
+) {
+    match cursor {
+        Cursor::Body(body) => {
+            if include_self {
+                output.push(cursor.clone());
+            }
+            for element in body {
+                match element {
+                    BodyElement::Attribute((_, expr)) => {
+                        descendants(Cursor::Expression(expr), true, output)
+                    }
+                    BodyElement::Block(block) => {
+                        descendants(Cursor::Body(&block.body), true, output)
+                    }
+                }
+            }
+        }
+        Cursor::Blocks { ref blocks, depth } => {
+            if include_self {
+                output.push(cursor.clone());
+            }
+            for block in blocks {
+                // Segments applied to blocks without further labels already apply to their
+                // bodies, so the bodies themselves are not included again
+                let include_body = block.labels.len() > depth;
+                descendants(Cursor::Body(&block.body), include_body, output);
+            }
+        }
+        Cursor::Expression(expr) => {
+            if include_self {
+                output.push(cursor.clone());
+            }
+            match expr {
+                Expression::Tuple(tuple) => {
+                    for expr in tuple {
+                        descendants(Cursor::Expression(expr), true, output);
+                    }
+                }
+                Expression::Object(object) => {
+                    for (_, expr) in object {
+                        descendants(Cursor::Expression(expr), true, output);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixtures;
+    use crate::parser::parse_str;
+
+    fn expressions<'s, 'a>(nodes: Vec<Node<'s, 'a>>) -> Vec<String> {
+        nodes
+            .into_iter()
+            .map(|node| node.expression().expect("to be an expression").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn selectors_are_parsed_correctly() {
+        let test_cases = [
+            ("", vec![]),
+            ("foo", vec![Segment::Name("foo".to_string())]),
+            (
+                r#"resource."security/group".*.allow[0].cidrs[*]"#,
+                vec![
+                    Segment::Name("resource".to_string()),
+                    Segment::Name("security/group".to_string()),
+                    Segment::Wildcard,
+                    Segment::Name("allow".to_string()),
+                    Segment::Index(0),
+                    Segment::Name("cidrs".to_string()),
+                    Segment::IndexWildcard,
+                ],
+            ),
+            (
+                r#"..name"#,
+                vec![Segment::RecursiveDescent, Segment::Name("name".to_string())],
+            ),
+            ("..[0]", vec![Segment::RecursiveDescent, Segment::Index(0)]),
+            (
+                "allow..[*]",
+                vec![
+                    Segment::Name("allow".to_string()),
+                    Segment::RecursiveDescent,
+                    Segment::IndexWildcard,
+                ],
+            ),
+            (
+                r#"resource..cidrs[1]."with \"quotes\"""#,
+                vec![
+                    Segment::Name("resource".to_string()),
+                    Segment::RecursiveDescent,
+                    Segment::Name("cidrs".to_string()),
+                    Segment::Index(1),
+                    Segment::Name("with \"quotes\"".to_string()),
+                ],
+            ),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            let selector: Selector = input.parse().unwrap();
+            assert_eq!(selector.segments(), expected.as_slice());
+            // Round trip
+            assert_eq!(selector.to_string().parse::<Selector>().unwrap(), selector);
+        }
+    }
+
+    #[test]
+    fn invalid_selectors_are_rejected() {
+        for input in &[
+            "foo.",
+            "foo...bar",
+            "foo[bar]",
+            r#""unterminated"#,
+            "a\"b\"",
+            "..",
+            "[0",
+            "foo[0",
+            "foo[*",
+            "foo.[0]",
+            "foo.[*]",
+        ] {
+            assert!(
+                input.parse::<Selector>().is_err(),
+                "{} should be invalid",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn exact_paths_are_selected_correctly() {
+        let body = parse_str(fixtures::BLOCK).unwrap();
+        let selected = body
+            .select(r#"resource."security/group".foobar.allow[1].cidrs[0]"#)
+            .unwrap();
+
+        assert_eq!(expressions(selected), [r#""192.168.0.0/16""#]);
+    }
+
+    #[test]
+    fn wildcards_are_selected_correctly() {
+        let body = parse_str(fixtures::BLOCK).unwrap();
+
+        let selected = body.select("resource.*.*.name").unwrap();
+        assert_eq!(
+            expressions(selected),
+            [r#""foobar""#, r#""second""#, r#""an_instance""#]
+        );
+
+        let selected = body
+            .select(r#"resource."security/group".*.*[*].name"#)
+            .unwrap();
+        assert_eq!(
+            expressions(selected),
+            [r#""localhost""#, r#""lan""#, r#""internet""#, r#""all""#]
+        );
+
+        let selected = body.select("simple_map.index").unwrap();
+        assert_eq!(expressions(selected), ["1", "0"]);
+    }
+
+    #[test]
+    fn recursive_descent_is_selected_correctly() {
+        let body = parse_str(fixtures::BLOCK).unwrap();
+
+        let selected = body.select("resource..cidrs[0]").unwrap();
+        assert_eq!(
+            expressions(selected),
+            [
+                r#""127.0.0.1/32""#,
+                r#""192.168.0.0/16""#,
+                r#""0.0.0.0/0""#,
+                r#""0.0.0.0/0""#
+            ]
+        );
+
+        let selected = body.select("..root").unwrap();
+        assert_eq!(expressions(selected), ["true"]);
+    }
+
+    #[test]
+    fn blocks_are_selected_correctly() {
+        let body = parse_str(fixtures::BLOCK).unwrap();
+
+        let selected = body.select("simple_map[1]").unwrap();
+        match selected.as_slice() {
+            [Node::Block(block)] => {
+                assert_eq!(block.r#type, "simple_map");
+                assert_eq!(block.body.len(), 3);
+            }
+            others => panic!("Unexpected nodes {:?}", others),
+        }
+
+        let selected = body.select("resource.instance").unwrap();
+        match selected.as_slice() {
+            [Node::Block(block)] => assert_eq!(block.labels, ["instance", "an_instance"]),
+            others => panic!("Unexpected nodes {:?}", others),
+        }
+
+        assert!(body.select("resource.missing").unwrap().is_empty());
+        assert!(body.select("simple_map[2]").unwrap().is_empty());
+    }
+
+    #[test]
+    fn empty_selector_selects_the_body() {
+        let body = parse_str(fixtures::BLOCK).unwrap();
+        assert_eq!(body.select("").unwrap(), [Node::Body(&body)]);
+    }
+}
@@ -5,7 +5,16 @@
 //!
 //! The sub-modules contain implementation details that you can usually disregard. To find out more
 //! about _using_ them, head to [`serde` documentation](https://serde.rs/).
+#[cfg(feature = "bigdecimal")]
+pub mod big_decimal;
 pub mod de;
+pub mod model;
+pub mod raw;
+pub mod spanned;
 
 #[doc(inline)]
-pub use de::from_str;
+pub use de::{from_reader, from_slice, from_str, from_str_with_context};
+#[doc(inline)]
+pub use raw::RawExpression;
+#[doc(inline)]
+pub use spanned::Spanned;
@@ -0,0 +1,50 @@
+//! Serialize and deserialize [`BigDecimal`] fields without losing precision
+//!
+//! `BigDecimal` deserializes numbers from floats, which rounds numbers that cannot be
+//! represented exactly like `0.1`. Fields annotated with
+//! `#[serde(with = "ferrous_chloride::serde::big_decimal")]` are deserialized from the literal of
+//! the number instead:
+//!
+//! ```rust
+//! use bigdecimal::BigDecimal;
+//! use ferrous_chloride::eval::Context;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Account {
+//!     #[serde(with = "ferrous_chloride::serde::big_decimal")]
+//!     balance: BigDecimal,
+//! }
+//!
+//! let account: Account = ferrous_chloride::from_str("balance = 0.1\n").unwrap();
+//! assert_eq!(account.balance, "0.1".parse::<BigDecimal>().unwrap());
+//!
+//! // Arithmetic is exact too
+//! let context = Context::new();
+//! let account: Account =
+//!     ferrous_chloride::from_str_with_context("balance = 0.1 + 0.2\n", &context).unwrap();
+//! assert_eq!(account.balance, "0.3".parse::<BigDecimal>().unwrap());
+//! ```
+//!
+//! Numbers are serialized like [`Number`]s: as integers if they are integral and fit into 128
+//! bits, and as floats otherwise.
+use bigdecimal::BigDecimal;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+
+use crate::parser::number::Number;
+
+pub fn serialize<S>(decimal: &BigDecimal, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    Number::from(decimal.clone()).serialize(serializer)
+}
+
+pub fn deserialize<'de, D>(deserializer: D) -> Result<BigDecimal, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let number = Number::deserialize(deserializer)?;
+    number.as_big_decimal().map_err(de::Error::custom)
+}
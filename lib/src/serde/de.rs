@@ -7,22 +7,30 @@ pub mod block;
 pub mod body;
 pub mod expression;
 pub mod object;
+pub mod options;
+pub mod seq;
+pub mod variant;
 
 #[doc(inline)]
 pub use self::error::*;
 #[doc(inline)]
-pub use body::{from_str, Deserializer};
+pub use body::{from_reader, from_slice, from_str, from_str_with_context, Deserializer};
+#[doc(inline)]
+pub use options::{BlockCollection, Options};
 
 use crate::parser;
-use serde::de::{IntoDeserializer, Visitor};
+use serde::de::Visitor;
 use std::borrow::Cow;
 
 mod error {
-    use std::fmt::Display;
+    use std::fmt::{self, Display};
     use std::ops::Deref;
 
     use failure::{self, Fail};
 
+    use crate::select::{Segment, Selector};
+    use crate::span::Span;
+
     /// Error type for deserialization
     #[derive(Debug, Fail)]
     pub enum Error {
@@ -53,8 +61,106 @@ mod error {
         #[fail(display = "Body has duplicate key {}", _0)]
         BodyDuplicateKey(String),
 
+        #[fail(display = "Invalid enum: {}", _0)]
+        InvalidEnum(String),
+
+        #[fail(display = "A map value was requested before its key")]
+        ValueBeforeKey,
+
+        #[fail(
+            display = "Spans are only available for attributes and blocks deserialized along \
+                       with their source"
+        )]
+        SpanUnavailable,
+
+        #[fail(display = "Error evaluating expression: {}", _0)]
+        Evaluation(#[cause] crate::Error),
+
+        #[fail(
+            display = "{} expressions can only be deserialized with an evaluation context",
+            _0
+        )]
+        Unevaluated(&'static str),
+
         #[fail(display = "{}", _0)]
         Custom(String),
+
+        #[fail(display = "{}: {}", context, error)]
+        At { context: Context, error: Box<Error> },
+    }
+
+    /// The path to the element that an error occurred in, and its location in the source
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct Context {
+        pub path: Selector,
+        /// Span of the innermost element of the path whose location is known
+        pub span: Option<Span>,
+    }
+
+    impl Display for Context {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self.path)?;
+            if let Some(span) = self.span {
+                write!(f, " at {}", span.start)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl Error {
+        /// Path to the element the error occurred in, if known
+        pub fn path(&self) -> Option<&Selector> {
+            match self {
+                Error::At { context, .. } => Some(&context.path),
+                _ => None,
+            }
+        }
+
+        /// Span of the element the error occurred in, if known
+        pub fn span(&self) -> Option<Span> {
+            match self {
+                Error::At { context, .. } => context.span,
+                _ => None,
+            }
+        }
+
+        /// The error without its context
+        pub fn inner(&self) -> &Error {
+            match self {
+                Error::At { error, .. } => error,
+                error => error,
+            }
+        }
+
+        /// Prepend `segment` to the path of the error
+        ///
+        /// `span` is only called if the location of the error is not known yet.
+        pub(crate) fn at<F>(self, segment: Segment, span: F) -> Self
+        where
+            F: FnOnce() -> Option<Span>,
+        {
+            match self {
+                Error::At { context, error } => {
+                    let mut segments = Vec::with_capacity(context.path.segments().len() + 1);
+                    segments.push(segment);
+                    segments.extend_from_slice(context.path.segments());
+                    Error::At {
+                        context: Context {
+                            path: Selector::new(segments),
+                            span: context.span.or_else(span),
+                        },
+                        error,
+                    }
+                }
+                error => Error::At {
+                    context: Context {
+                        path: Selector::new(vec![segment]),
+                        span: span(),
+                    },
+                    error: Box::new(error),
+                },
+            }
+        }
     }
 
     impl From<crate::Error> for Error {
@@ -88,6 +194,16 @@ mod error {
     #[derive(Debug)]
     pub struct Compat(pub failure::Compat<Error>);
 
+    impl Compat {
+        /// Prepend `segment` to the path of the error
+        pub(crate) fn at<F>(self, segment: Segment, span: F) -> Self
+        where
+            F: FnOnce() -> Option<Span>,
+        {
+            From::from(Error::from(self).at(segment, span))
+        }
+    }
+
     impl Deref for Compat {
         type Target = failure::Compat<Error>;
         fn deref(&self) -> &Self::Target {
@@ -148,12 +264,18 @@ fn deserialize_number<'de, V>(
 where
     V: Visitor<'de>,
 {
-    if number.is_float() {
-        visitor.visit_f64(number.as_f64().map_err(Error::ParseFloatError)?)
-    } else if number.is_signed() {
-        visitor.visit_i64(number.as_i64().map_err(Error::ParseIntError)?)
+    // Integral numbers are integers even if they are written with an exponent, and integers
+    // that do not fit into 64 bits are 128 bit integers or floats
+    if let Ok(number) = number.as_u64() {
+        visitor.visit_u64(number)
+    } else if let Ok(number) = number.as_i64() {
+        visitor.visit_i64(number)
+    } else if let Ok(number) = number.as_u128() {
+        visitor.visit_u128(number)
+    } else if let Ok(number) = number.as_i128() {
+        visitor.visit_i128(number)
     } else {
-        visitor.visit_u64(number.as_u64().map_err(Error::ParseIntError)?)
+        visitor.visit_f64(number.as_f64().map_err(Error::ParseFloatError)?)
     }
 }
 
@@ -175,7 +297,7 @@ where
         }
     }
 
-    visitor.visit_seq(tuple.into_deserializer())
+    seq::visit_seq(tuple.into_iter(), visitor)
 }
 
 fn deserialize_object<'de, V>(
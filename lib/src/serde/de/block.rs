@@ -1,42 +1,102 @@
-use std::collections::{hash_map, HashMap};
 use std::vec;
 
-use serde::de::{self, Deserializer, IntoDeserializer, Visitor};
+use indexmap::{map, IndexMap};
+use serde::de::{self, DeserializeSeed, Deserializer, IntoDeserializer, Visitor};
 use serde::forward_to_deserialize_any;
 
 use crate::parser::block::{BlockBody, BlockLabel};
 use crate::parser::body::Body;
-use crate::serde::de::body::Deserializer as BodyDeserializer;
-use crate::serde::de::Compat;
+use crate::select::Segment;
+use crate::serde::de::body::{
+    BodyValue, Deserializer as BodyDeserializer, Origin, ValueDeserializer,
+};
+use crate::serde::de::options::{BlockCollection, State};
+use crate::serde::de::seq;
+use crate::serde::de::variant::EnumAccess;
+use crate::serde::de::{Compat, Error};
 
-fn deserialize_body_seq<'de, V>(bodies: Vec<Body<'de>>, visitor: V) -> Result<V::Value, Compat>
+fn deserialize_body_seq<'de, V>(
+    bodies: Vec<Body<'de>>,
+    state: State<'de>,
+    visitor: V,
+) -> Result<V::Value, Compat>
 where
     V: Visitor<'de>,
 {
-    visitor.visit_seq(
+    seq::visit_seq(
         bodies
             .into_iter()
-            .map(BodyDeserializer::new)
-            .collect::<Vec<_>>()
-            .into_deserializer(),
+            .map(|body| BodyDeserializer::from_parts(body, state.clone())),
+        visitor,
     )
 }
 
-fn deserialize_map<'de, V>(body: Body<'de>, visitor: V) -> Result<V::Value, Compat>
+fn deserialize_map<'de, V>(
+    body: Body<'de>,
+    state: State<'de>,
+    visitor: V,
+) -> Result<V::Value, Compat>
 where
     V: Visitor<'de>,
 {
-    BodyDeserializer::new(body).deserialize_map(visitor)
+    BodyDeserializer::from_parts(body, state).deserialize_map(visitor)
+}
+
+/// Deserializer for the bodies of blocks with the same type
+///
+/// If the source of the blocks is known, [`Spanned`](crate::serde::Spanned) values can be
+/// deserialized from their bodies.
+#[derive(Clone, Debug)]
+pub struct BlockBodyDeserializer<'de> {
+    blocks: BlockBody<'de>,
+    state: State<'de>,
+}
+
+impl<'de> BlockBodyDeserializer<'de> {
+    pub fn new(blocks: BlockBody<'de>) -> Self {
+        Self::from_parts(blocks, Default::default())
+    }
+
+    pub(crate) fn from_parts(blocks: BlockBody<'de>, state: State<'de>) -> Self {
+        let blocks = match state.options.blocks {
+            BlockCollection::Merge => merge_bodies(blocks),
+            BlockCollection::Auto | BlockCollection::Seq => blocks,
+        };
+        Self { blocks, state }
+    }
+}
+
+/// Merge the bodies of blocks with the same labels into a single body
+fn merge_bodies(blocks: BlockBody) -> BlockBody {
+    fn merge(bodies: Vec<Body>) -> Vec<Body> {
+        if bodies.len() > 1 {
+            vec![bodies.into_iter().flatten().collect()]
+        } else {
+            bodies
+        }
+    }
+
+    match blocks {
+        BlockBody::Body(bodies) => BlockBody::Body(merge(bodies)),
+        BlockBody::Labels { empty, labels } => BlockBody::Labels {
+            empty: merge(empty),
+            labels: labels
+                .into_iter()
+                .map(|(label, blocks)| (label, merge_bodies(blocks)))
+                .collect(),
+        },
+    }
 }
 
 /// Possible states of `BlockBody`:
 /// - Empty: Single Body => Deserialize Map/Struct
 /// - Empty: Multiple Bodies => Seq
 /// - Labels: Zero labels => Logic error! Treat like Empty variannt
-/// - Labels: Zero empty => Single label: enum/struct with labels fields
-///                      => Multiple labels: Seq of above
-/// - Labels: Non-zero empty => Seq of structs with label fields
-impl<'de> de::Deserializer<'de> for BlockBody<'de> {
+/// - Labels: Zero empty => Map from the first label to the blocks with that label, or an enum
+///   selected by a single label
+/// - Labels: Non-zero empty => Seq of the bodies without labels, followed by a map with a
+///   single label for each label
+impl<'de> de::Deserializer<'de> for BlockBodyDeserializer<'de> {
     type Error = Compat;
 
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -44,13 +104,13 @@ impl<'de> de::Deserializer<'de> for BlockBody<'de> {
         V: Visitor<'de>,
     {
         // Make a "best guess" of how to deserialize the block
-        match self {
+        match self.blocks {
             BlockBody::Body(mut bodies) => {
-                if bodies.len() == 1 {
+                if bodies.len() == 1 && self.state.options.blocks != BlockCollection::Seq {
                     // Deseriaize the single block body as a map/struct
-                    deserialize_map(bodies.remove(0), visitor)
+                    deserialize_map(bodies.remove(0), self.state, visitor)
                 } else {
-                    deserialize_body_seq(bodies, visitor)
+                    deserialize_body_seq(bodies, self.state, visitor)
                 }
             }
             BlockBody::Labels { mut empty, labels } => {
@@ -58,12 +118,16 @@ impl<'de> de::Deserializer<'de> for BlockBody<'de> {
                     // This should be impossible but we handle it anyway
                     return if empty.len() == 1 {
                         // Deseriaize the single block body as a map/struct
-                        deserialize_map(empty.remove(0), visitor)
+                        deserialize_map(empty.remove(0), self.state, visitor)
                     } else {
-                        deserialize_body_seq(empty, visitor)
+                        deserialize_body_seq(empty, self.state, visitor)
                     };
                 }
-                unimplemented!("not yet")
+                if empty.is_empty() {
+                    visitor.visit_map(LabelsMapAccess::new(labels, self.state))
+                } else {
+                    visit_labels_seq(empty, labels, self.state, visitor)
+                }
             }
         }
     }
@@ -72,7 +136,7 @@ impl<'de> de::Deserializer<'de> for BlockBody<'de> {
     where
         V: Visitor<'de>,
     {
-        if self.len_blocks() == 0 {
+        if self.blocks.len_blocks() == 0 {
             visitor.visit_none()
         } else {
             visitor.visit_some(self)
@@ -83,48 +147,278 @@ impl<'de> de::Deserializer<'de> for BlockBody<'de> {
     where
         V: Visitor<'de>,
     {
-        match self {
-            BlockBody::Body(bodies) => deserialize_body_seq(bodies, visitor),
+        match self.blocks {
+            BlockBody::Body(bodies) => deserialize_body_seq(bodies, self.state, visitor),
             BlockBody::Labels { mut empty, labels } => {
                 if labels.is_empty() {
                     // This should be impossible but we handle it anyway
                     return if empty.len() == 1 {
                         // Deseriaize the single block body as a map/struct
-                        deserialize_map(empty.remove(0), visitor)
+                        deserialize_map(empty.remove(0), self.state, visitor)
                     } else {
-                        deserialize_body_seq(empty, visitor)
+                        deserialize_body_seq(empty, self.state, visitor)
                     };
                 }
-                unimplemented!("not yet")
+                visit_labels_seq(empty, labels, self.state, visitor)
             }
         }
     }
 
-    // Tuple
-    // map - mapaccess `"labels" = rest`
-    // struct
-    // enum
-    // identifier = probably enum
+    /// Either a single label selects the variant, or a single block body is deserialized as an
+    /// enum
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.blocks {
+            BlockBody::Body(mut bodies) => {
+                if bodies.len() != 1 {
+                    return Err(Error::InvalidEnum(format!(
+                        "expected a single block, got {} blocks",
+                        bodies.len()
+                    ))
+                    .into());
+                }
+                BodyDeserializer::from_parts(bodies.remove(0), self.state)
+                    .deserialize_enum(name, variants, visitor)
+            }
+            BlockBody::Labels { empty, labels } => {
+                if !empty.is_empty() || labels.len() != 1 {
+                    return Err(Error::InvalidEnum(format!(
+                        "expected blocks with a single distinct label, got {} labels \
+                         and {} blocks without labels",
+                        labels.len(),
+                        empty.len()
+                    ))
+                    .into());
+                }
+                let (variant, value) = labels.into_iter().next().expect("to be some");
+                let value = ValueDeserializer::from_parts(
+                    BodyValue::Block(value),
+                    self.state,
+                    Origin::Unknown,
+                );
+                visitor.visit_enum(EnumAccess::new(variant.as_cow(), value))
+            }
+        }
+    }
+
+    /// A single block body is deserialized as a struct
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.blocks {
+            BlockBody::Body(mut bodies) if bodies.len() == 1 => {
+                BodyDeserializer::from_parts(bodies.remove(0), self.state)
+                    .deserialize_struct(name, fields, visitor)
+            }
+            blocks => Self::from_parts(blocks, self.state).deserialize_any(visitor),
+        }
+    }
 
     // Many of these types cannot be deserialized from BlockBody
     forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
         bytes byte_buf unit unit_struct newtype_struct tuple
-        tuple_struct map struct enum identifier ignored_any
+        tuple_struct map identifier ignored_any
+    }
+}
+
+impl<'de> de::Deserializer<'de> for BlockBody<'de> {
+    type Error = Compat;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        BlockBodyDeserializer::new(self).deserialize_any(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        BlockBodyDeserializer::new(self).deserialize_option(visitor)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        BlockBodyDeserializer::new(self).deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        BlockBodyDeserializer::new(self).deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Visit blocks with and without labels as a sequence
+fn visit_labels_seq<'de, V>(
+    empty: Vec<Body<'de>>,
+    labels: IndexMap<BlockLabel<'de>, BlockBody<'de>>,
+    state: State<'de>,
+    visitor: V,
+) -> Result<V::Value, Compat>
+where
+    V: Visitor<'de>,
+{
+    let mut access = LabelsSeqAccess::new(empty, labels, state);
+    let value = visitor.visit_seq(&mut access)?;
+
+    let remaining = access.empty.len() + access.labels.len();
+    if remaining == 0 {
+        Ok(value)
+    } else {
+        Err(de::Error::invalid_length(
+            access.index + remaining,
+            &format!("{} elements in sequence", access.index).as_str(),
+        ))
     }
 }
 
+/// Access to the blocks without labels, each as a body, followed by the blocks with each label,
+/// each as a map with a single label
 #[derive(Debug)]
 pub struct LabelsSeqAccess<'de> {
     empty: vec::IntoIter<Body<'de>>,
-    labels: hash_map::IntoIter<BlockLabel<'de>, BlockBody<'de>>,
+    labels: map::IntoIter<BlockLabel<'de>, BlockBody<'de>>,
+    index: usize,
+    state: State<'de>,
 }
 
 impl<'de> LabelsSeqAccess<'de> {
-    pub fn new(empty: Vec<Body<'de>>, labels: HashMap<BlockLabel<'de>, BlockBody<'de>>) -> Self {
+    pub(crate) fn new(
+        empty: Vec<Body<'de>>,
+        labels: IndexMap<BlockLabel<'de>, BlockBody<'de>>,
+        state: State<'de>,
+    ) -> Self {
         Self {
             empty: empty.into_iter(),
             labels: labels.into_iter(),
+            index: 0,
+            state,
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for LabelsSeqAccess<'de> {
+    type Error = Compat;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let index = self.index;
+        self.index += 1;
+        if let Some(body) = self.empty.next() {
+            return seed
+                .deserialize(BodyDeserializer::from_parts(body, self.state.clone()))
+                .map(Some)
+                .map_err(|e| e.at(Segment::Index(index), || None));
         }
+
+        match self.labels.next() {
+            None => Ok(None),
+            Some((label, blocks)) => {
+                let labels = std::iter::once((label, blocks)).collect();
+                let blocks = BlockBodyDeserializer {
+                    blocks: BlockBody::Labels {
+                        empty: vec![],
+                        labels,
+                    },
+                    state: self.state.clone(),
+                };
+                seed.deserialize(blocks).map(Some)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.empty.len() + self.labels.len())
+    }
+}
+
+/// Access to the blocks with each label, as a map from the label to the blocks
+#[derive(Debug)]
+pub struct LabelsMapAccess<'de> {
+    labels: map::IntoIter<BlockLabel<'de>, BlockBody<'de>>,
+    /// MapAccess users have to call `next_key_seed` before `next_value_seed`
+    /// So we store the value extracted after calling `next_key_seed`
+    value: Option<(BlockLabel<'de>, BlockBody<'de>)>,
+    state: State<'de>,
+}
+
+impl<'de> LabelsMapAccess<'de> {
+    pub(crate) fn new(
+        labels: IndexMap<BlockLabel<'de>, BlockBody<'de>>,
+        state: State<'de>,
+    ) -> Self {
+        Self {
+            labels: labels.into_iter(),
+            value: None,
+            state,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for LabelsMapAccess<'de> {
+    type Error = Compat;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match self.labels.next() {
+            None => Ok(None),
+            Some((label, blocks)) => {
+                let key = label.as_cow();
+                self.value = Some((label, blocks));
+                seed.deserialize(key.into_deserializer()).map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let (label, blocks) = self.value.take().ok_or(Error::ValueBeforeKey)?;
+        let value = ValueDeserializer::from_parts(
+            BodyValue::Block(blocks),
+            self.state.clone(),
+            Origin::Unknown,
+        );
+        seed.deserialize(value)
+            .map_err(|e| e.at(Segment::Name(label.as_str().to_string()), || None))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.labels.len())
     }
 }
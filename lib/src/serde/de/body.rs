@@ -1,16 +1,29 @@
 use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, OnceLock};
 use std::vec;
 
+use serde::de::DeserializeOwned;
 use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
 use serde::forward_to_deserialize_any;
 use serde::Deserialize;
 
+use crate::eval::Context;
 use crate::parser::block;
 use crate::parser::body::{Body, BodyElement};
+use crate::parser::descent;
 use crate::parser::expression::Expression;
 use crate::parser::identifier::Identifier;
+use crate::parser::ConfigFile;
+use crate::select::Segment;
+use crate::serde::de::block::BlockBodyDeserializer;
+use crate::serde::de::options::{Options, State};
+use crate::serde::de::variant::EnumAccess;
 use crate::serde::de::{Compat, Error};
+use crate::serde::raw;
+use crate::serde::spanned::{self, SpannedMapAccess};
+use crate::span::Span;
+use crate::MergeBehaviour;
 
 #[derive(Clone, Debug)]
 pub enum BodyValue<'de> {
@@ -18,6 +31,33 @@ pub enum BodyValue<'de> {
     Block(block::BlockBody<'de>),
 }
 
+/// Where an element of a body is in the source
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Origin<'de> {
+    /// The name of the element was not borrowed from the source
+    Unknown,
+    /// An attribute with the name
+    Attribute(&'de str),
+    /// Blocks with the type of the first and the last block
+    Blocks { first: &'de str, last: &'de str },
+}
+
+impl<'de> Origin<'de> {
+    fn span(self, state: &State) -> Option<Span> {
+        match self {
+            Origin::Unknown => None,
+            Origin::Attribute(name) => state.span(name),
+            Origin::Blocks { first, last } => Some(Span {
+                start: state.span(first)?.start,
+                end: state.span(last)?.end,
+            }),
+        }
+    }
+}
+
+/// An attribute or the blocks with the same type in a body
+type Element<'de> = (Identifier<'de>, BodyValue<'de>, Origin<'de>);
+
 /// Deserializer for a HCL configuration file.
 ///
 /// In HCL, a [`ConfigFile`](crate::parser::ConfigFile) is essentially a
@@ -31,20 +71,81 @@ pub enum BodyValue<'de> {
 /// To deserialize HCL expressions into types like `&str`,
 /// use [`Expression::parse`]
 /// to parse the HCL expression, and then use the parsed [`Expression`] to deserialize.
+///
+/// Deserializers that know the source of the body can deserialize the
+/// [`Spanned`](crate::serde::Spanned) values of attributes and blocks.
+///
+/// Deserialization can be customised with [`Options`].
+///
+/// Types that buffer their input, like `#[serde(flatten)]` fields and untagged enums, see a
+/// single block body as a map, several blocks as a sequence and labelled blocks as maps from
+/// their labels to their bodies. [`Spanned`](crate::serde::Spanned) and
+/// [`RawExpression`](crate::serde::RawExpression) values cannot be deserialized from buffered
+/// input.
 #[derive(Clone, Debug)]
 pub struct Deserializer<'de> {
     body: Body<'de>,
+    state: State<'de>,
 }
 
 impl<'de> Deserializer<'de> {
     pub fn new(body: Body<'de>) -> Self {
-        Self { body }
+        Self::from_parts(body, Default::default())
+    }
+
+    /// Create a deserializer for a `body` that was parsed from `source`
+    pub fn with_source(body: Body<'de>, source: &'de str) -> Self {
+        let state = State {
+            source: Some(source),
+            ..Default::default()
+        };
+        Self::from_parts(body, state)
+    }
+
+    pub(crate) fn from_parts(body: Body<'de>, state: State<'de>) -> Self {
+        Self { body, state }
+    }
+
+    /// Customise deserialization with `options`
+    pub fn with_options(mut self, options: Options) -> Self {
+        self.state.options = options;
+        self
+    }
+
+    /// Evaluate expressions with `context` before they are deserialized
+    ///
+    /// Without a context, templates like `"a-${b}"` are deserialized as their source text and
+    /// other expressions that have to be evaluated cannot be deserialized.
+    pub fn with_context(mut self, context: &'de Context) -> Self {
+        self.state.context = Some(context);
+        self
     }
 
     #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &'de str) -> Result<Self, Error> {
-        let body = crate::parser::parse_str(s)?;
-        Ok(Self::new(body))
+        let (body, extents) = descent::parse_str_with_extents(s, &Default::default())?;
+        let state = State {
+            source: Some(s),
+            extents: Arc::new(OnceLock::from(extents)),
+            ..Default::default()
+        };
+        Ok(Self::from_parts(body, state))
+    }
+
+    /// Parse a slice of UTF-8 bytes into a deserializer
+    pub fn from_slice(bytes: &'de [u8]) -> Result<Self, Error> {
+        let s = std::str::from_utf8(bytes).map_err(crate::Error::from)?;
+        Self::from_str(s)
+    }
+}
+
+impl<'de> From<ConfigFile<'de>> for Deserializer<'de> {
+    /// Deserialize an already parsed configuration file
+    ///
+    /// Use [`Deserializer::with_source`] instead if the source of the configuration file is still
+    /// available to deserialize [`Spanned`](crate::serde::Spanned) values.
+    fn from(config_file: ConfigFile<'de>) -> Self {
+        Self::new(config_file)
     }
 }
 
@@ -56,7 +157,47 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
         V: Visitor<'de>,
     {
         // Best guess is to treat the visitor as a map
-        visitor.visit_map(MapAccess::new(self.body))
+        visitor.visit_map(MapAccess::from_parts(self.body, self.state))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let deny_unknown_fields = self.state.options.deny_unknown_fields;
+        let mut map = MapAccess::from_parts(self.body, self.state);
+        if deny_unknown_fields {
+            map.fields = Some(fields);
+        }
+        visitor.visit_map(map)
+    }
+
+    /// The body must have a single attribute or block type, which selects the variant
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let mut elements = elements(self.body, self.state.options.duplicates);
+        if elements.len() != 1 {
+            return Err(Error::InvalidEnum(format!(
+                "expected a body with a single attribute or block type, got {}",
+                elements.len()
+            ))
+            .into());
+        }
+        let (variant, value, origin) = elements.next().expect("to be some");
+        let value = ValueDeserializer::from_parts(value, self.state, origin);
+        visitor.visit_enum(EnumAccess::new(variant, value))
     }
 
     // Option
@@ -66,14 +207,12 @@ impl<'de> de::Deserializer<'de> for Deserializer<'de> {
     // seq
     // tuple
     // tuple struct
-    // struct
-    // enum
 
     // These types are not possible to deserialize from a Body
     forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
         bytes byte_buf option unit unit_struct newtype_struct seq tuple
-        tuple_struct map struct enum identifier ignored_any
+        tuple_struct map identifier ignored_any
     }
 }
 
@@ -87,41 +226,101 @@ impl<'de> IntoDeserializer<'de, Compat> for Deserializer<'de> {
 
 #[derive(Clone, Debug)]
 pub struct MapAccess<'de> {
-    elements: vec::IntoIter<(Identifier<'de>, BodyValue<'de>)>,
+    elements: vec::IntoIter<Element<'de>>,
     /// MapAccess users have to call `next_key_seed` before `next_value_seed`
     /// So we store the value extracted after calling `next_key_seed`
-    value: Option<BodyValue<'de>>,
+    value: Option<(Identifier<'de>, ValueDeserializer<'de>)>,
     /// Set of keys we have seen before
     seen: HashSet<Cow<'de, str>>,
+    /// The only keys that are allowed, if unknown fields are denied
+    fields: Option<&'static [&'static str]>,
+    state: State<'de>,
 }
 
 impl<'de> MapAccess<'de> {
     pub fn new(body: Body<'de>) -> Self {
+        Self::from_parts(body, Default::default())
+    }
+
+    pub(crate) fn from_parts(body: Body<'de>, state: State<'de>) -> Self {
         Self {
-            elements: build_map_acces_iter(body),
+            elements: elements(body, state.options.duplicates),
             value: None,
             seen: Default::default(),
+            fields: None,
+            state,
         }
     }
 }
 
-pub(crate) fn build_map_acces_iter<'de>(
-    body: Body<'de>,
-) -> vec::IntoIter<(Identifier<'de>, BodyValue<'de>)> {
+fn borrowed<'de>(ident: &Identifier<'de>) -> Option<&'de str> {
+    match ident {
+        Cow::Borrowed(ident) => Some(ident),
+        Cow::Owned(_) => None,
+    }
+}
+
+pub(crate) fn build_map_acces_iter<'de>(body: Body<'de>) -> vec::IntoIter<Element<'de>> {
     let (attributes, blocks): (Vec<_>, Vec<_>) =
         body.into_iter().partition(BodyElement::is_attribute);
     let attributes = attributes
         .into_iter()
         .map(BodyElement::unwrap_attribute)
-        .map(|(ident, expr)| (ident, BodyValue::Expression(expr)));
-    let blocks = block::Blocks::new(blocks.into_iter().map(BodyElement::unwrap_block))
+        .map(|(ident, expr)| {
+            let origin = borrowed(&ident).map_or(Origin::Unknown, Origin::Attribute);
+            (ident, BodyValue::Expression(expr), origin)
+        });
+
+    let blocks: Vec<_> = blocks.into_iter().map(BodyElement::unwrap_block).collect();
+    let mut origins: HashMap<String, Origin<'de>> = HashMap::new();
+    for block in &blocks {
+        let block_type = borrowed(&block.r#type);
+        let origin = origins
+            .entry(block.r#type.to_string())
+            .or_insert_with(|| match block_type {
+                Some(first) => Origin::Blocks { first, last: first },
+                None => Origin::Unknown,
+            });
+        if let (Origin::Blocks { last, .. }, Some(block_type)) = (origin, block_type) {
+            *last = block_type;
+        }
+    }
+    let blocks = block::Blocks::new(blocks)
         .into_iter()
-        .map(|(ident, bodies)| (ident, BodyValue::Block(bodies)));
+        .map(|(ident, bodies)| {
+            let origin = origins
+                .get(ident.as_ref())
+                .cloned()
+                .unwrap_or(Origin::Unknown);
+            (ident, BodyValue::Block(bodies), origin)
+        });
 
     let elements: Vec<_> = attributes.chain(blocks).collect();
     elements.into_iter()
 }
 
+/// The elements of a body, keeping only one of the elements with the same name unless
+/// duplicates are an error
+fn elements(body: Body, duplicates: MergeBehaviour) -> vec::IntoIter<Element> {
+    let elements = build_map_acces_iter(body);
+    let mut seen = HashSet::new();
+    let elements: Vec<_> = match duplicates {
+        MergeBehaviour::Error => return elements,
+        MergeBehaviour::TakeFirst => elements
+            .filter(|(key, _, _)| seen.insert(key.clone()))
+            .collect(),
+        MergeBehaviour::TakeLast => {
+            let mut elements: Vec<_> = elements
+                .rev()
+                .filter(|(key, _, _)| seen.insert(key.clone()))
+                .collect();
+            elements.reverse();
+            elements
+        }
+    };
+    elements.into_iter()
+}
+
 impl<'de> de::MapAccess<'de> for MapAccess<'de> {
     type Error = Compat;
 
@@ -134,34 +333,35 @@ impl<'de> de::MapAccess<'de> for MapAccess<'de> {
             return Ok(None);
         }
 
-        match next.expect("to be some") {
-            (ident, expr @ BodyValue::Expression(_)) => {
-                if !self.seen.insert(ident.clone()) {
-                    return Err(Error::BodyDuplicateKey(ident.to_string()).into());
+        let (key, value, origin) = next.expect("to be some");
+        let error = if !self.seen.insert(key.clone()) {
+            Some(Compat::from(Error::BodyDuplicateKey(key.to_string())))
+        } else {
+            match self.fields {
+                Some(fields) if !fields.contains(&key.as_ref()) => {
+                    Some(de::Error::unknown_field(&key, fields))
                 }
-                self.value = Some(expr);
-                seed.deserialize(ident.into_deserializer()).map(Some)
-            }
-            (block_type, blk @ BodyValue::Block(_)) => {
-                // If this key has been seen before, we have a bug
-                assert!(
-                    self.seen.insert(block_type.clone()),
-                    "bug in block merging code"
-                );
-                self.value = Some(blk);
-                seed.deserialize(block_type.into_deserializer()).map(Some)
+                _ => None,
             }
+        };
+        if let Some(error) = error {
+            let state = &self.state;
+            return Err(error.at(Segment::Name(key.to_string()), || origin.span(state)));
         }
+
+        let value = ValueDeserializer::from_parts(value, self.state.clone(), origin);
+        self.value = Some((key.clone(), value));
+        seed.deserialize(key.into_deserializer()).map(Some)
     }
 
     fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
     where
         V: DeserializeSeed<'de>,
     {
-        match self.value.take().expect("to be some") {
-            BodyValue::Expression(expr) => seed.deserialize(expr),
-            BodyValue::Block(blk) => seed.deserialize(blk),
-        }
+        let (key, value) = self.value.take().ok_or(Error::ValueBeforeKey)?;
+        let (state, origin) = (value.state.clone(), value.origin);
+        seed.deserialize(value)
+            .map_err(|e| e.at(Segment::Name(key.into_owned()), || origin.span(&state)))
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -171,6 +371,179 @@ impl<'de> de::MapAccess<'de> for MapAccess<'de> {
     }
 }
 
+/// Deserializer for the value of an attribute or blocks in a body
+///
+/// If the source of the body is known, [`Spanned`](crate::serde::Spanned) values can be
+/// deserialized.
+#[derive(Clone, Debug)]
+pub struct ValueDeserializer<'de> {
+    value: BodyValue<'de>,
+    state: State<'de>,
+    origin: Origin<'de>,
+}
+
+impl<'de> ValueDeserializer<'de> {
+    pub fn new(value: BodyValue<'de>) -> Self {
+        Self::from_parts(value, Default::default(), Origin::Unknown)
+    }
+
+    pub(crate) fn from_parts(
+        value: BodyValue<'de>,
+        state: State<'de>,
+        origin: Origin<'de>,
+    ) -> Self {
+        Self {
+            value,
+            state,
+            origin,
+        }
+    }
+
+    fn span(&self) -> Option<Span> {
+        self.origin.span(&self.state)
+    }
+
+    /// The source text of the expression of an attribute, if the source is known
+    fn expression_source(&self) -> Option<&'de str> {
+        match self.origin {
+            Origin::Attribute(_) => {
+                let attribute = self.span()?.slice(self.state.source?)?;
+                attribute.split_once('=').map(|(_, expr)| expr.trim_start())
+            }
+            Origin::Unknown | Origin::Blocks { .. } => None,
+        }
+    }
+
+    /// Evaluate the expression with the context, if there is one
+    fn evaluate(self) -> Result<Self, Compat> {
+        match (self.value, self.state.context) {
+            (BodyValue::Expression(expr), Some(context)) if !expr.is_literal() => {
+                let expr = context.evaluate(&expr).map_err(Error::Evaluation)?;
+                Ok(Self {
+                    value: BodyValue::Expression(expr),
+                    ..self
+                })
+            }
+            (value, _) => Ok(Self { value, ..self }),
+        }
+    }
+}
+
+impl<'de> From<BodyValue<'de>> for ValueDeserializer<'de> {
+    fn from(value: BodyValue<'de>) -> Self {
+        Self::new(value)
+    }
+}
+
+/// Forward deserialization to the expression or the blocks
+macro_rules! forward_to_value {
+    ($($method:ident ( $($arg:ident : $ty:ty),* ))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                let this = self.evaluate()?;
+                match this.value {
+                    BodyValue::Expression(expr) => {
+                        de::Deserializer::$method(expr, $($arg,)* visitor)
+                    }
+                    BodyValue::Block(blk) => de::Deserializer::$method(
+                        BlockBodyDeserializer::from_parts(blk, this.state),
+                        $($arg,)*
+                        visitor
+                    ),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer<'de> {
+    type Error = Compat;
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if name == spanned::NAME {
+            let span = self.span().ok_or(Error::SpanUnavailable)?;
+            let value = Self::from_parts(self.value, self.state, self.origin);
+            return visitor.visit_map(SpannedMapAccess::new(span, value));
+        }
+
+        let this = self.evaluate()?;
+        match this.value {
+            BodyValue::Expression(expr) => {
+                de::Deserializer::deserialize_struct(expr, name, fields, visitor)
+            }
+            BodyValue::Block(blk) => de::Deserializer::deserialize_struct(
+                BlockBodyDeserializer::from_parts(blk, this.state),
+                name,
+                fields,
+                visitor,
+            ),
+        }
+    }
+
+    /// Expressions are deserialized as their source text into a
+    /// [`RawExpression`](crate::serde::RawExpression) without being evaluated
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if name == raw::NAME {
+            let source = self.expression_source();
+            return match (self.value, source) {
+                (BodyValue::Expression(_), Some(source)) => visitor.visit_borrowed_str(source),
+                (BodyValue::Expression(expr), None) => visitor.visit_string(expr.to_string()),
+                (BodyValue::Block(_), _) => Err(de::Error::invalid_type(
+                    de::Unexpected::Other("blocks"),
+                    &"an expression",
+                )),
+            };
+        }
+
+        let this = self.evaluate()?;
+        match this.value {
+            BodyValue::Expression(expr) => {
+                de::Deserializer::deserialize_newtype_struct(expr, name, visitor)
+            }
+            BodyValue::Block(blk) => de::Deserializer::deserialize_newtype_struct(
+                BlockBodyDeserializer::from_parts(blk, this.state),
+                name,
+                visitor,
+            ),
+        }
+    }
+
+    forward_to_value! {
+        deserialize_any()
+        deserialize_option()
+        deserialize_seq()
+        deserialize_map()
+        deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize)
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_ignored_any()
+    }
+
+    // Neither expressions nor blocks treat these types specially
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct identifier
+    }
+}
+
 /// Deserialize a type `T` from a provided HCL String
 ///
 /// ```rust
@@ -202,10 +575,88 @@ where
     Ok(T::deserialize(deserializer)?)
 }
 
+/// Deserialize a type `T` from a provided HCL String, evaluating expressions with `context`
+///
+/// ```rust
+/// # use ferrous_chloride::serde::de::body::from_str_with_context;
+/// use ferrous_chloride::eval::Context;
+/// use ferrous_chloride::parser::Expression;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct Server {
+///     port: u16,
+///     name: String,
+/// }
+///
+/// let context = Context::new()
+///     .variable("var", Expression::new_object(vec![("base_port", Expression::from(8000))]))
+///     .variable("env", "production");
+/// let input = r#"
+/// port = var.base_port + 1
+/// name = "server-${env}"
+/// "#;
+///
+/// let server: Server = from_str_with_context(input, &context).unwrap();
+/// assert_eq!(server, Server { port: 8001, name: "server-production".to_string() });
+/// ```
+pub fn from_str_with_context<'a, T>(s: &'a str, context: &'a Context) -> Result<T, Error>
+where
+    T: Deserialize<'a>,
+{
+    let deserializer = Deserializer::from_str(s)?.with_context(context);
+    Ok(T::deserialize(deserializer)?)
+}
+
+/// Deserialize a type `T` from a slice of UTF-8 encoded HCL
+///
+/// ```rust
+/// # use ferrous_chloride::serde::de::body::from_slice;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, PartialEq, Debug)]
+/// struct DeserializeMe {
+///     name: String,
+///     index: usize,
+/// }
+///
+/// let deserialized: DeserializeMe = from_slice(b"name = \"second\"\nindex = 1\n").unwrap();
+/// assert_eq!(deserialized.index, 1);
+/// ```
+pub fn from_slice<'a, T>(bytes: &'a [u8]) -> Result<T, Error>
+where
+    T: Deserialize<'a>,
+{
+    let deserializer = Deserializer::from_slice(bytes)?;
+    Ok(T::deserialize(deserializer)?)
+}
+
+/// Deserialize a type `T` from an IO stream of HCL
+///
+/// The entire IO stream has to be buffered in memory first before parsing can occur, so `T`
+/// cannot borrow from the input.
+///
+/// When reading from a source against which short reads are not efficient, such as a
+/// [`File`](std::fs::File), you will want to apply your own buffering because the library
+/// will not buffer the input. See [`std::io::BufReader`].
+pub fn from_reader<R, T>(mut reader: R) -> Result<T, Error>
+where
+    R: std::io::Read,
+    T: DeserializeOwned,
+{
+    let mut buffer = String::new();
+    reader
+        .read_to_string(&mut buffer)
+        .map_err(crate::Error::from)?;
+    from_str(&buffer)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::serde::de::options::BlockCollection;
+
     #[test]
     fn deserialize_simple_structs() {
         #[derive(Deserialize, PartialEq, Debug)]
@@ -316,4 +767,893 @@ allow {
 
         assert_eq!(deserialized, expected);
     }
+
+    #[test]
+    fn deserialize_enums() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Protocol {
+            Tcp,
+            Udp,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Rule {
+            Allow { cidrs: Vec<String> },
+            Deny { cidrs: Vec<String> },
+            Log(bool),
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct SecurityGroup {
+            protocol: Protocol,
+            // Variant selected by the block type in the body of the block
+            default: Rule,
+            // Variant selected by the block label
+            rule: Rule,
+            // Variant selected by the attribute name in the body of the block
+            audit: Rule,
+        }
+
+        let input = r#"
+protocol = "udp"
+
+default {
+  deny {
+    cidrs = ["0.0.0.0/0"]
+  }
+}
+
+rule "allow" {
+  cidrs = ["10.0.0.0/8"]
+}
+
+audit {
+  log = true
+}
+"#;
+        let deserialized: SecurityGroup = from_str(input).unwrap();
+
+        let expected = SecurityGroup {
+            protocol: Protocol::Udp,
+            default: Rule::Deny {
+                cidrs: vec!["0.0.0.0/0".to_string()],
+            },
+            rule: Rule::Allow {
+                cidrs: vec!["10.0.0.0/8".to_string()],
+            },
+            audit: Rule::Log(true),
+        };
+
+        assert_eq!(deserialized, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a body with a single attribute or block type, got 2")]
+    fn deserialize_enums_errors_on_ambiguous_bodies() {
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "lowercase")]
+        #[allow(dead_code)]
+        enum Rule {
+            Allow { cidrs: Vec<String> },
+            Deny { cidrs: Vec<String> },
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct SecurityGroup {
+            #[allow(dead_code)]
+            default: Rule,
+        }
+
+        let input = r#"
+default {
+  allow {
+    cidrs = ["10.0.0.0/8"]
+  }
+  deny {
+    cidrs = ["0.0.0.0/0"]
+  }
+}
+"#;
+        let _: SecurityGroup = from_str(input).unwrap();
+    }
+
+    #[test]
+    fn deserialize_unit_variants_with_empty_content() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Mode {
+            Strict,
+            Lenient,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            string: Mode,
+            object: Mode,
+            body: Mode,
+            label: Mode,
+        }
+
+        let input = r#"
+string = "strict"
+object = { lenient = null }
+body {
+  strict {}
+}
+label "lenient" {}
+"#;
+        let deserialized: Config = from_str(input).unwrap();
+
+        let expected = Config {
+            string: Mode::Strict,
+            object: Mode::Lenient,
+            body: Mode::Strict,
+            label: Mode::Lenient,
+        };
+        assert_eq!(deserialized, expected);
+    }
+
+    #[test]
+    fn deserialize_unit_variants_errors_on_content() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Mode {
+            Strict,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            #[allow(dead_code)]
+            mode: Mode,
+        }
+
+        let cases = [
+            (
+                "mode { strict = 1 }\n",
+                "mode.strict at 1:1: invalid type: integer `1`, expected unit",
+            ),
+            (
+                "mode = { strict = \"yes\" }\n",
+                "mode.strict at 1:1: invalid type: string \"yes\", expected unit",
+            ),
+            (
+                "mode \"strict\" {\n  anything = 1\n}\n",
+                "mode.strict at 1:1: invalid type: map, expected unit",
+            ),
+        ];
+        for (input, expected) in cases.iter() {
+            let error = from_str::<Config>(input).unwrap_err();
+            assert_eq!(error.to_string(), *expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn deserialize_spanned_attributes_and_blocks() {
+        use crate::serde::Spanned;
+
+        #[derive(Deserialize, Debug)]
+        struct Allow {
+            cidrs: Spanned<Vec<String>>,
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct SecurityGroup {
+            name: Spanned<String>,
+            allow: Spanned<Vec<Allow>>,
+            deny: Spanned<Allow>,
+        }
+
+        let input = r#"
+name = "foobar" # Comment
+allow {
+  cidrs = ["127.0.0.1/32"]
+}
+allow {
+  cidrs = ["10.0.0.0/8"]
+}
+deny { cidrs = ["0.0.0.0/0"] }
+"#;
+        let deserialized: SecurityGroup = from_str(input).unwrap();
+
+        assert_eq!(deserialized.name.get_ref(), "foobar");
+        assert_eq!(deserialized.name.span().to_string(), "2:1-2:16");
+        assert_eq!(
+            deserialized.name.span().slice(input),
+            Some(r#"name = "foobar""#)
+        );
+
+        // Blocks of the same type span from the first to the last block
+        assert_eq!(deserialized.allow.get_ref().len(), 2);
+        assert_eq!(deserialized.allow.span().to_string(), "3:1-8:2");
+
+        let deny = deserialized.deny.get_ref();
+        assert_eq!(deserialized.deny.span().to_string(), "9:1-9:31");
+        assert_eq!(
+            deny.cidrs.span().slice(input),
+            Some(r#"cidrs = ["0.0.0.0/0"]"#)
+        );
+
+        let allow = &deserialized.allow.get_ref()[1];
+        assert_eq!(allow.cidrs.span().to_string(), "7:3-7:25");
+    }
+
+    #[test]
+    fn deserialize_spanned_attributes_of_any_syntax() {
+        use crate::eval::Context;
+        use crate::serde::Spanned;
+
+        #[derive(Deserialize, Debug)]
+        struct Conditional {
+            value: Spanned<u32>,
+        }
+
+        // Conditionals in parentheses can span multiple lines
+        let input = "value = (a ?\n  1 : 2)\n";
+        let context = Context::new().variable("a", true);
+        let body = crate::parser::parse_str(input).unwrap();
+        let deserializers = vec![
+            Deserializer::from_str(input).unwrap(),
+            Deserializer::with_source(body, input),
+        ];
+        for deserializer in deserializers {
+            let deserializer = deserializer.with_context(&context);
+            let deserialized = Conditional::deserialize(deserializer).unwrap();
+            assert_eq!(*deserialized.value.get_ref(), 1);
+            assert_eq!(deserialized.value.span().to_string(), "1:1-2:9");
+            assert_eq!(
+                deserialized.value.span().slice(input),
+                Some("value = (a ?\n  1 : 2)")
+            );
+        }
+    }
+
+    #[test]
+    fn deserializers_can_be_sent_between_threads() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Deserializer>();
+    }
+
+    #[test]
+    fn deserialize_spanned_errors_without_source() {
+        use crate::serde::Spanned;
+
+        #[derive(Deserialize, Debug)]
+        struct DeserializeMe {
+            #[allow(dead_code)]
+            name: Spanned<String>,
+        }
+
+        let body = crate::parser::parse_str("name = \"foobar\"\n").unwrap();
+        let error = DeserializeMe::deserialize(Deserializer::new(body)).unwrap_err();
+        let error = Error::from(error);
+        assert_eq!(error.path().unwrap().to_string(), "name");
+        match error.inner() {
+            Error::SpanUnavailable => {}
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn errors_have_the_path_and_location_of_the_failing_element() {
+        use std::collections::HashMap;
+
+        #[derive(Deserialize, Debug)]
+        struct Allow {
+            #[allow(dead_code)]
+            cidrs: Vec<String>,
+        }
+
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Rule {
+            Allow {
+                #[allow(dead_code)]
+                ports: HashMap<String, u16>,
+            },
+        }
+
+        #[derive(Deserialize, Debug)]
+        struct SecurityGroup {
+            #[allow(dead_code)]
+            name: String,
+            #[allow(dead_code)]
+            allow: Option<Vec<Allow>>,
+            #[allow(dead_code)]
+            rule: Option<Rule>,
+        }
+
+        let test_cases = [
+            (
+                r#"
+name = "foobar"
+allow {
+  cidrs = ["127.0.0.1/32", 8]
+}
+"#,
+                "allow[0].cidrs[1] at 4:3: invalid type: integer `8`, expected a string",
+            ),
+            (
+                r#"
+name = "foobar"
+rule "allow" {
+  ports = { http = "eighty" }
+}
+"#,
+                r#"rule.allow.ports.http at 4:3: invalid type: string "eighty", expected u16"#,
+            ),
+            (
+                r#"
+name = "foobar"
+allow {
+}
+"#,
+                "allow[0] at 3:1: missing field `cidrs`",
+            ),
+            (
+                r#"
+name = "foobar"
+name = "barbaz"
+"#,
+                "name at 3:1: Body has duplicate key name",
+            ),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            let error = from_str::<SecurityGroup>(input).unwrap_err();
+            assert_eq!(error.to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn deserialize_from_slices_readers_and_config_files() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct DeserializeMe {
+            name: String,
+            index: usize,
+        }
+
+        let input = "name = \"second\"\nindex = 1\n";
+        let expected = DeserializeMe {
+            name: "second".to_string(),
+            index: 1,
+        };
+
+        assert_eq!(
+            from_slice::<DeserializeMe>(input.as_bytes()).unwrap(),
+            expected
+        );
+        assert_eq!(
+            from_reader::<_, DeserializeMe>(input.as_bytes()).unwrap(),
+            expected
+        );
+
+        let config_file = crate::parser::parse_str(input).unwrap();
+        let deserializer = Deserializer::from(config_file);
+        assert_eq!(DeserializeMe::deserialize(deserializer).unwrap(), expected);
+    }
+
+    #[test]
+    fn deserialize_from_slices_errors_on_invalid_unicode() {
+        #[derive(Deserialize, Debug)]
+        struct DeserializeMe {
+            #[allow(dead_code)]
+            name: String,
+        }
+
+        let error = from_slice::<DeserializeMe>(b"name = \"\xff\"\n").unwrap_err();
+        match error {
+            Error::ParseError(crate::Error::InvalidUnicodeToParse(_)) => {}
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn duplicate_attributes_follow_the_duplicate_policy() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct DeserializeMe {
+            name: String,
+        }
+
+        let input = "name = \"first\"\nname = \"last\"\n";
+        let deserialize = |duplicates| {
+            let options = Options::new().duplicates(duplicates);
+            let deserializer = Deserializer::from_str(input).unwrap().with_options(options);
+            DeserializeMe::deserialize(deserializer).map(|deserialized| deserialized.name)
+        };
+
+        assert_eq!(deserialize(MergeBehaviour::TakeFirst).unwrap(), "first");
+        assert_eq!(deserialize(MergeBehaviour::TakeLast).unwrap(), "last");
+
+        let error = Error::from(deserialize(MergeBehaviour::Error).unwrap_err());
+        match error.inner() {
+            Error::BodyDuplicateKey(key) => assert_eq!(key, "name"),
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn objects_with_string_keys_are_deserialized() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct DeserializeMe {
+            tags: HashMap<String, String>,
+        }
+
+        let input = "tags = { \"Name\" = \"foo\", env = \"prod\" }\n";
+        let deserialized = from_str::<DeserializeMe>(input).unwrap();
+        assert_eq!(deserialized.tags["Name"], "foo");
+        assert_eq!(deserialized.tags["env"], "prod");
+
+        let error = from_str::<DeserializeMe>("tags = { (var.key) = \"foo\" }\n").unwrap_err();
+        match error.inner() {
+            Error::ParseError(crate::Error::UnsupportedObjectKey(key)) => {
+                assert_eq!(key, "(var.key)")
+            }
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn values_requested_before_keys_are_errors() {
+        use serde::de::MapAccess as _;
+
+        let body = crate::parser::parse_str("name = \"foo\"\n").unwrap();
+        let mut map = MapAccess::new(body);
+        let error = Error::from(map.next_value::<String>().unwrap_err());
+        match error {
+            Error::ValueBeforeKey => {}
+            other => panic!("Unexpected error {:?}", other),
+        }
+    }
+
+    #[test]
+    fn attributes_and_blocks_with_the_same_name_are_duplicates() {
+        #[derive(Deserialize, Debug)]
+        struct DeserializeMe {
+            #[allow(dead_code)]
+            name: String,
+        }
+
+        let input = "name = \"foo\"\nname {\n}\n";
+        let error = from_str::<DeserializeMe>(input).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "name at 2:1: Body has duplicate key name"
+        );
+    }
+
+    #[test]
+    fn unknown_fields_can_be_denied() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Allow {
+            cidrs: Vec<String>,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct SecurityGroup {
+            name: String,
+            allow: Allow,
+        }
+
+        let input = r#"
+name = "foobar"
+allow {
+  cidrs = ["127.0.0.1/32"]
+  ports = [80]
+}
+"#;
+        let expected = SecurityGroup {
+            name: "foobar".to_string(),
+            allow: Allow {
+                cidrs: vec!["127.0.0.1/32".to_string()],
+            },
+        };
+        assert_eq!(from_str::<SecurityGroup>(input).unwrap(), expected);
+
+        let options = Options::new().deny_unknown_fields(true);
+        let deserializer = Deserializer::from_str(input).unwrap().with_options(options);
+        let error = Error::from(SecurityGroup::deserialize(deserializer).unwrap_err());
+        assert_eq!(
+            error.to_string(),
+            "allow.ports at 5:3: unknown field `ports`, expected `cidrs`"
+        );
+    }
+
+    #[test]
+    fn blocks_are_collected_by_the_block_collection_mode() {
+        use std::collections::HashMap;
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Allow {
+            name: String,
+            cidrs: Vec<String>,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct SecurityGroup {
+            allow: Allow,
+        }
+
+        let input = r#"
+allow {
+  name = "localhost"
+}
+allow {
+  cidrs = ["127.0.0.1/32"]
+}
+"#;
+        let options = Options::new().blocks(BlockCollection::Merge);
+        let deserializer = Deserializer::from_str(input).unwrap().with_options(options);
+        let expected = SecurityGroup {
+            allow: Allow {
+                name: "localhost".to_string(),
+                cidrs: vec!["127.0.0.1/32".to_string()],
+            },
+        };
+        assert_eq!(SecurityGroup::deserialize(deserializer).unwrap(), expected);
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(untagged)]
+        enum Blocks {
+            One(HashMap<String, String>),
+            Many(Vec<HashMap<String, String>>),
+        }
+
+        let input = "allow {\n  name = \"localhost\"\n}\n";
+        let expected_one = Blocks::One(
+            vec![("name".to_string(), "localhost".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        let deserialized: HashMap<String, Blocks> = from_str(input).unwrap();
+        assert_eq!(deserialized["allow"], expected_one);
+
+        let options = Options::new().blocks(BlockCollection::Seq);
+        let deserializer = Deserializer::from_str(input).unwrap().with_options(options);
+        let deserialized = HashMap::<String, Blocks>::deserialize(deserializer).unwrap();
+        match &deserialized["allow"] {
+            Blocks::Many(many) => assert_eq!(many.len(), 1),
+            other => panic!("Unexpected blocks {:?}", other),
+        }
+    }
+
+    #[test]
+    fn expressions_are_evaluated_with_the_context() {
+        use crate::eval::Context;
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Listener {
+            port: u16,
+            protocol: String,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Server {
+            name: String,
+            listener: Vec<Listener>,
+        }
+
+        let context = Context::new()
+            .variable(
+                "var",
+                Expression::new_object(vec![("base_port", Expression::from(8000))]),
+            )
+            .function("lower", |arguments| match arguments.as_slice() {
+                [Expression::String(string)] => Ok(Expression::from(string.to_lowercase())),
+                _ => Err("expected a single string".to_string()),
+            });
+        let input = r#"
+name = lower("SERVER")
+listener {
+  port = var.base_port + 1
+  protocol = "http"
+}
+listener {
+  port = var.base_port + 443 > 8000 ? 8443 : 443
+  protocol = "${lower("HTTPS")}"
+}
+"#;
+        let expected = Server {
+            name: "server".to_string(),
+            listener: vec![
+                Listener {
+                    port: 8001,
+                    protocol: "http".to_string(),
+                },
+                Listener {
+                    port: 8443,
+                    protocol: "https".to_string(),
+                },
+            ],
+        };
+        assert_eq!(
+            from_str_with_context::<Server>(input, &context).unwrap(),
+            expected
+        );
+
+        let error = from_str_with_context::<Server>(
+            "name = \"server\"\nlistener {\n  port = var.missing\n}\n",
+            &context,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "listener[0].port at 3:3: Error evaluating expression: Invalid operation: Object has \
+             no attribute missing"
+        );
+
+        let error = from_str::<Server>("name = lower(\"SERVER\")\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "name at 1:1: FunctionCall expressions can only be deserialized with an evaluation \
+             context"
+        );
+    }
+
+    #[test]
+    fn templates_are_deserialized_as_their_source_without_a_context() {
+        use crate::eval::Context;
+
+        let input = "name = \"a-${b}\"\nescaped = \"$${c}-${d + 1}\"\n";
+        let deserialized: HashMap<String, String> = from_str(input).unwrap();
+        assert_eq!(deserialized["name"], "a-${b}");
+        assert_eq!(deserialized["escaped"], "$${c}-${d + 1}");
+
+        // Like the bodies of `value`, which do not parse templates
+        let body =
+            crate::value::from_str("name = \"a-${b}\"\n", Some(crate::MergeBehaviour::Error))
+                .unwrap();
+        assert_eq!(
+            body.get("name"),
+            Some(crate::OneOrMany::One(&crate::Value::from("a-${b}")))
+        );
+
+        let context = Context::new().variable("b", "c").variable("d", 1);
+        let deserialized: HashMap<String, String> =
+            from_str_with_context("name = \"a-${b}\"\n", &context).unwrap();
+        assert_eq!(deserialized["name"], "a-c");
+    }
+
+    #[test]
+    fn raw_expressions_are_not_evaluated() {
+        use crate::eval::Context;
+        use crate::serde::{RawExpression, Spanned};
+        use serde::de::value::{Error as ValueError, StrDeserializer};
+        use serde::de::IntoDeserializer;
+
+        #[derive(Deserialize, Debug)]
+        struct Deployment {
+            name: String,
+            replicas: RawExpression,
+            ports: Vec<RawExpression>,
+            image: Spanned<RawExpression>,
+        }
+
+        let context = Context::new().variable("env", "production");
+        let input = r#"
+name = "server-${env}"
+replicas = var.count * 2 // evaluated at deploy time
+ports = [8000, 8000 + 443]
+image = <<EOT
+registry/${env}
+EOT
+"#;
+        let deserializer = Deserializer::from_str(input)
+            .unwrap()
+            .with_context(&context);
+        let deployment = Deployment::deserialize(deserializer).unwrap();
+        assert_eq!(deployment.name, "server-production");
+        assert_eq!(deployment.replicas.source(), "var.count * 2");
+        assert_eq!(
+            deployment.replicas.expression(),
+            &Expression::parse("var.count * 2").unwrap()
+        );
+        // Elements of tuples are evaluated along with the tuple
+        let ports: Vec<_> = deployment.ports.iter().map(RawExpression::source).collect();
+        assert_eq!(ports, ["8000", "8443"]);
+        assert_eq!(
+            deployment.image.get_ref().source(),
+            "<<EOT\nregistry/${env}\nEOT"
+        );
+        assert_eq!(deployment.image.span().to_string(), "5:1-7:4");
+
+        // Without the source, the formatted expression is the source text
+        let body = crate::parser::parse_str("replicas = var.count*2\n").unwrap();
+        let deserialized: HashMap<String, RawExpression> =
+            HashMap::deserialize(Deserializer::new(body)).unwrap();
+        assert_eq!(deserialized["replicas"].source(), "var.count * 2");
+
+        let error = from_str::<HashMap<String, RawExpression>>("replicas {\n}\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "replicas at 1:1: invalid type: blocks, expected an expression"
+        );
+
+        // Other deserializers provide the source text as a string
+        let deserializer: StrDeserializer<ValueError> = "max(a, 1)".into_deserializer();
+        let raw = RawExpression::deserialize(deserializer).unwrap();
+        assert_eq!(raw.source(), "max(a, 1)");
+        assert_eq!(raw.to_string(), "max(a, 1)");
+    }
+
+    #[test]
+    fn raw_expressions_reject_deep_nesting() {
+        use crate::serde::RawExpression;
+        use serde::de::value::{Error as ValueError, StringDeserializer};
+        use serde::de::IntoDeserializer;
+
+        let input = format!("{}1{}", "[".repeat(200_000), "]".repeat(200_000));
+        match RawExpression::new(input.as_str()) {
+            Err(crate::Error::LimitExceeded { position, .. }) => assert_eq!(position.offset, 64),
+            result => panic!("unexpected {:?}", result.map(|_| ())),
+        }
+
+        let deserializer: StringDeserializer<ValueError> = input.into_deserializer();
+        let error = RawExpression::deserialize(deserializer).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("nesting is deeper than 64 levels"));
+
+        let raw = RawExpression::new("  (a ?\n  1 : 2)  ").unwrap();
+        assert_eq!(raw.source(), "  (a ?\n  1 : 2)  ");
+        assert!(RawExpression::new("1 2").is_err());
+    }
+
+    #[test]
+    fn flattened_fields_and_untagged_enums_keep_blocks() {
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Listener {
+            port: u16,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(untagged)]
+        enum Source {
+            Path {
+                path: String,
+            },
+            Url {
+                url: String,
+                checksum: Option<String>,
+            },
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Common {
+            name: String,
+            listener: HashMap<String, HashMap<String, Listener>>,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Server {
+            #[serde(flatten)]
+            common: Common,
+            source: Vec<Source>,
+            #[serde(flatten)]
+            extra: HashMap<String, Expression<'static>>,
+        }
+
+        let input = r#"
+name = "server"
+replicas = 2 * 3
+tags = ["a", "b"]
+
+listener "http" "public" {
+  port = 80
+}
+
+listener "https" "public" {
+  port = 443
+}
+
+source {
+  path = "/srv"
+}
+
+source {
+  url = "https://example.com"
+}
+
+limits {
+  cpu = 2
+}
+"#;
+        let context = Context::new();
+        let deserializer = Deserializer::from_str(input)
+            .unwrap()
+            .with_context(&context);
+        let server = Server::deserialize(deserializer).unwrap();
+
+        let listener = |port| {
+            let mut public = HashMap::new();
+            public.insert("public".to_string(), Listener { port });
+            public
+        };
+        let mut listeners = HashMap::new();
+        listeners.insert("http".to_string(), listener(80));
+        listeners.insert("https".to_string(), listener(443));
+        assert_eq!(
+            server.common,
+            Common {
+                name: "server".to_string(),
+                listener: listeners,
+            }
+        );
+        assert_eq!(
+            server.source,
+            vec![
+                Source::Path {
+                    path: "/srv".to_string()
+                },
+                Source::Url {
+                    url: "https://example.com".to_string(),
+                    checksum: None
+                },
+            ]
+        );
+
+        let mut extra = HashMap::new();
+        extra.insert("replicas".to_string(), Expression::from(6));
+        extra.insert(
+            "tags".to_string(),
+            Expression::new_tuple(vec![Expression::from("a"), Expression::from("b")]),
+        );
+        extra.insert(
+            "limits".to_string(),
+            Expression::new_object(vec![("cpu", Expression::from(2))]),
+        );
+        assert_eq!(server.extra, extra);
+
+        // Blocks with and without labels are a sequence
+        #[derive(Deserialize, PartialEq, Debug)]
+        #[serde(untagged)]
+        enum Rule {
+            Default { allow: bool },
+            Named(HashMap<String, Rule>),
+        }
+
+        let rules: HashMap<String, Vec<Rule>> =
+            from_str("rule {\n  allow = true\n}\nrule \"ssh\" {\n  allow = false\n}\n").unwrap();
+        let mut ssh = HashMap::new();
+        ssh.insert("ssh".to_string(), Rule::Default { allow: false });
+        assert_eq!(
+            rules["rule"],
+            vec![Rule::Default { allow: true }, Rule::Named(ssh)]
+        );
+    }
+
+    #[test]
+    fn labelled_blocks_are_deserialized_in_document_order() {
+        use std::collections::HashMap;
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Variable {
+            default: String,
+        }
+
+        #[derive(Deserialize, PartialEq, Debug)]
+        struct Config {
+            variable: Vec<HashMap<String, Variable>>,
+        }
+
+        let input = r#"
+variable "zone" {
+  default = "a"
+}
+variable "region" {
+  default = "b"
+}
+variable "alpha" {
+  default = "c"
+}
+"#;
+        let config: Config = from_str(input).unwrap();
+        let names: Vec<_> = config
+            .variable
+            .iter()
+            .flat_map(|variable| variable.keys())
+            .collect();
+        assert_eq!(names, ["zone", "region", "alpha"]);
+    }
 }
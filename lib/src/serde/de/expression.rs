@@ -2,9 +2,12 @@ use serde::de::{self, IntoDeserializer, Visitor};
 use serde::forward_to_deserialize_any;
 
 use crate::parser::expression::Expression;
+use crate::serde::de::body::BodyValue;
+use crate::serde::de::variant::EnumAccess;
 use crate::serde::de::{
-    deserialize_number, deserialize_object, deserialize_string, deserialize_tuple, Compat,
+    deserialize_number, deserialize_object, deserialize_string, deserialize_tuple, Compat, Error,
 };
+use crate::serde::{model, raw};
 
 impl<'de> de::Deserializer<'de> for Expression<'de> {
     type Error = Compat;
@@ -21,6 +24,10 @@ impl<'de> de::Deserializer<'de> for Expression<'de> {
             String(string) => deserialize_string(string, visitor),
             Tuple(tuple) => deserialize_tuple(tuple, visitor, None),
             Object(object) => deserialize_object(object, visitor),
+            // Without an evaluation context, templates are deserialized as their source text,
+            // like strings with interpolations were before they were parsed
+            template @ Template(_) => visitor.visit_string(model::template_string(&template)),
+            other => Err(Error::Unevaluated(other.variant_name()).into()),
         }
     }
 
@@ -36,13 +43,17 @@ impl<'de> de::Deserializer<'de> for Expression<'de> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_newtype_struct(self)
+        match self {
+            expr if name == raw::NAME => visitor.visit_string(expr.to_string()),
+            Expression::Number(number) if name == model::NUMBER => visitor.visit_str(&number),
+            expr => visitor.visit_newtype_struct(expr),
+        }
     }
 
     fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
@@ -67,12 +78,48 @@ impl<'de> de::Deserializer<'de> for Expression<'de> {
         self.deserialize_tuple(len, visitor)
     }
 
-    // TODO: Enum Unit Variant from String
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            // Unit variant
+            Expression::String(variant) => visitor.visit_enum(EnumAccess::new(
+                variant,
+                BodyValue::Expression(Expression::Null).into(),
+            )),
+            Expression::Object(mut object) => {
+                if object.len() != 1 {
+                    return Err(Error::InvalidEnum(format!(
+                        "expected an Object with a single key, got {} keys",
+                        object.len()
+                    ))
+                    .into());
+                }
+                let (variant, value) = object.remove(0);
+                let variant = variant.as_str().map_err(Error::ParseError)?;
+                visitor.visit_enum(EnumAccess::new(
+                    variant,
+                    BodyValue::Expression(value).into(),
+                ))
+            }
+            other => Err(Error::InvalidEnum(format!(
+                "expected a String or an Object, got {}",
+                other.variant_name()
+            ))
+            .into()),
+        }
+    }
 
     forward_to_deserialize_any! {
         bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
         bytes byte_buf unit unit_struct seq
-        map struct enum identifier ignored_any
+        map struct identifier ignored_any
     }
 }
 
@@ -342,4 +389,79 @@ mod tests {
 
         assert_eq!(deserialized, expected);
     }
+
+    #[derive(Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "lowercase")]
+    enum Protocol {
+        Tcp,
+        Udp,
+        Port(u16),
+        Range(u16, u16),
+        Named { name: String, port: u16 },
+    }
+
+    #[test]
+    fn deserialize_unit_variants_from_strings() {
+        let deserialized = Protocol::deserialize(Expression::from("tcp")).unwrap();
+        assert_eq!(deserialized, Protocol::Tcp);
+
+        let deserialized = Protocol::deserialize(Expression::from("udp")).unwrap();
+        assert_eq!(deserialized, Protocol::Udp);
+    }
+
+    #[test]
+    #[should_panic(expected = "unknown variant `icmp`")]
+    fn deserialize_unit_variants_errors_on_unknown_variants() {
+        let _ = Protocol::deserialize(Expression::from("icmp")).unwrap();
+    }
+
+    #[test]
+    fn deserialize_variants_from_single_key_objects() {
+        let deserializer = Expression::new_object(vec![("tcp", Expression::Null)]);
+        let deserialized = Protocol::deserialize(deserializer).unwrap();
+        assert_eq!(deserialized, Protocol::Tcp);
+
+        let deserializer = Expression::new_object(vec![("port", Expression::from(80))]);
+        let deserialized = Protocol::deserialize(deserializer).unwrap();
+        assert_eq!(deserialized, Protocol::Port(80));
+
+        let deserializer = Expression::new_object(vec![(
+            "range",
+            Expression::new_tuple(vec![Expression::from(80), Expression::from(443)]),
+        )]);
+        let deserialized = Protocol::deserialize(deserializer).unwrap();
+        assert_eq!(deserialized, Protocol::Range(80, 443));
+
+        let deserializer = Expression::new_object(vec![(
+            "named",
+            Expression::new_object(vec![
+                ("name", Expression::from("http")),
+                ("port", Expression::from(80)),
+            ]),
+        )]);
+        let deserialized = Protocol::deserialize(deserializer).unwrap();
+        assert_eq!(
+            deserialized,
+            Protocol::Named {
+                name: "http".to_string(),
+                port: 80
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "expected an Object with a single key, got 2 keys")]
+    fn deserialize_variants_errors_on_objects_with_multiple_keys() {
+        let deserializer = Expression::new_object(vec![
+            ("port", Expression::from(80)),
+            ("tcp", Expression::Null),
+        ]);
+        let _ = Protocol::deserialize(deserializer).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a String or an Object, got Number")]
+    fn deserialize_variants_errors_on_other_expressions() {
+        let _ = Protocol::deserialize(Expression::from(80)).unwrap();
+    }
 }
@@ -5,6 +5,7 @@ use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess};
 
 use crate::parser::expression::Expression;
 use crate::parser::object::{Object, ObjectElementIdentifier};
+use crate::select::Segment;
 use crate::serde::de::{Compat, Error};
 
 #[derive(Debug)]
@@ -12,7 +13,7 @@ pub struct ObjectMapAccess<'de> {
     iterator: std::vec::IntoIter<(ObjectElementIdentifier<'de>, Expression<'de>)>,
     /// MapAccess users have to call `next_key_seed` before `next_value_seed`
     /// So we store the value extracted after calling `next_key_seed`
-    expression: Option<Expression<'de>>,
+    expression: Option<(Cow<'de, str>, Expression<'de>)>,
     /// Set of keys we have seen before
     seen_keys: HashSet<Cow<'de, str>>,
 }
@@ -40,13 +41,14 @@ impl<'de> MapAccess<'de> for ObjectMapAccess<'de> {
             None => return Ok(None),
             Some((key, value)) => (key, value),
         };
-        let key = key.as_str();
+        let key = key.as_str().map_err(Error::ParseError)?;
 
         if !self.seen_keys.insert(key.clone()) {
-            return Err(Error::ObjectDuplicateKey(key.to_string()).into());
+            let error = Compat::from(Error::ObjectDuplicateKey(key.to_string()));
+            return Err(error.at(Segment::Name(key.into_owned()), || None));
         }
 
-        self.expression = Some(value);
+        self.expression = Some((key.clone(), value));
         seed.deserialize(key.into_deserializer()).map(Some)
     }
 
@@ -54,8 +56,9 @@ impl<'de> MapAccess<'de> for ObjectMapAccess<'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        let expression = self.expression.take().expect("to be some");
+        let (key, expression) = self.expression.take().ok_or(Error::ValueBeforeKey)?;
         seed.deserialize(expression)
+            .map_err(|e| e.at(Segment::Name(key.into_owned()), || None))
     }
 
     fn size_hint(&self) -> Option<usize> {
@@ -0,0 +1,102 @@
+//! Options to customise deserialization
+//!
+//! ```rust
+//! use ferrous_chloride::serde::de::{Deserializer, Options};
+//! use ferrous_chloride::MergeBehaviour;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     name: String,
+//! }
+//!
+//! let options = Options::new()
+//!     .duplicates(MergeBehaviour::TakeLast)
+//!     .deny_unknown_fields(true);
+//! let deserializer = Deserializer::from_str("name = \"foo\"\nname = \"bar\"\n")
+//!     .unwrap()
+//!     .with_options(options);
+//!
+//! let config = Config::deserialize(deserializer).unwrap();
+//! assert_eq!(config.name, "bar");
+//! ```
+use std::sync::{Arc, OnceLock};
+
+use crate::eval::Context;
+use crate::parser::descent;
+use crate::span::{Extents, Span};
+use crate::MergeBehaviour;
+
+/// How the bodies of blocks with the same type are collected
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash, Default)]
+pub enum BlockCollection {
+    /// A single block is deserialized as a map and multiple blocks as a sequence of maps, unless
+    /// the type being deserialized asks for a sequence
+    #[default]
+    Auto,
+    /// Blocks are deserialized as a sequence even if there is a single block, unless the type
+    /// being deserialized asks for a struct
+    Seq,
+    /// The bodies of blocks with the same type and labels are merged into a single body.
+    /// Attributes that are repeated in the merged body follow the duplicate policy.
+    Merge,
+}
+
+/// Options for the [`Deserializer`](crate::serde::de::Deserializer)
+#[derive(Debug, PartialEq, Clone, Copy, Eq, Hash, Default)]
+pub struct Options {
+    /// What to do with attributes with the same name in a body
+    pub duplicates: MergeBehaviour,
+    /// Error on attributes and blocks that are not fields of the struct being deserialized, as if
+    /// every struct had `#[serde(deny_unknown_fields)]`
+    pub deny_unknown_fields: bool,
+    /// How the bodies of blocks with the same type are collected
+    pub blocks: BlockCollection,
+}
+
+impl Options {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn duplicates(mut self, duplicates: MergeBehaviour) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    pub fn deny_unknown_fields(mut self, deny_unknown_fields: bool) -> Self {
+        self.deny_unknown_fields = deny_unknown_fields;
+        self
+    }
+
+    pub fn blocks(mut self, blocks: BlockCollection) -> Self {
+        self.blocks = blocks;
+        self
+    }
+}
+
+/// State shared by the deserializers of a document
+#[derive(Debug, Clone, Default)]
+pub(crate) struct State<'de> {
+    /// The source the document was parsed from, if known
+    pub source: Option<&'de str>,
+    /// Byte ranges of the attributes and blocks in the source, which are recorded while parsing
+    /// or found by parsing the source again when they are first needed
+    pub extents: Arc<OnceLock<Extents>>,
+    pub options: Options,
+    /// The context to evaluate expressions with, if any
+    pub context: Option<&'de Context>,
+}
+
+impl<'de> State<'de> {
+    /// The span of the attribute or block named by `name`, a slice of the source
+    pub fn span(&self, name: &str) -> Option<Span> {
+        let source = self.source?;
+        let extents = self.extents.get_or_init(|| {
+            descent::parse_str_with_extents(source, &Default::default())
+                .map(|(_, extents)| extents)
+                .unwrap_or_default()
+        });
+        extents.span(source, name)
+    }
+}
@@ -0,0 +1,70 @@
+//! Sequences whose elements are annotated with their index in errors
+use serde::de::{self, DeserializeSeed, Visitor};
+
+use crate::select::Segment;
+use crate::serde::de::Compat;
+
+/// Access to the elements of a sequence
+#[derive(Debug)]
+pub struct SeqAccess<I> {
+    elements: I,
+    index: usize,
+}
+
+impl<I> SeqAccess<I> {
+    pub fn new(elements: I) -> Self {
+        Self { elements, index: 0 }
+    }
+}
+
+/// Visit the elements as a sequence, and check that the visitor has consumed all of them
+pub(crate) fn visit_seq<'de, I, D, V>(elements: I, visitor: V) -> Result<V::Value, Compat>
+where
+    I: Iterator<Item = D>,
+    D: de::Deserializer<'de, Error = Compat>,
+    V: Visitor<'de>,
+{
+    let mut access = SeqAccess::new(elements);
+    let value = visitor.visit_seq(&mut access)?;
+
+    let remaining = access.elements.count();
+    if remaining == 0 {
+        Ok(value)
+    } else {
+        Err(de::Error::invalid_length(
+            access.index + remaining,
+            &format!("{} elements in sequence", access.index).as_str(),
+        ))
+    }
+}
+
+impl<'de, I, D> de::SeqAccess<'de> for SeqAccess<I>
+where
+    I: Iterator<Item = D>,
+    D: de::Deserializer<'de, Error = Compat>,
+{
+    type Error = Compat;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.elements.next() {
+            None => Ok(None),
+            Some(element) => {
+                let index = self.index;
+                self.index += 1;
+                seed.deserialize(element)
+                    .map(Some)
+                    .map_err(|e| e.at(Segment::Index(index), || None))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        match self.elements.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,132 @@
+//! Externally tagged enums
+//!
+//! An enum is represented by the name of its variant followed by its content:
+//!
+//! - Unit variants can be deserialized from a string, like `protocol = "tcp"`.
+//! - All variants can be deserialized from an object with a single key, like
+//!   `rule = { allow = ["0.0.0.0/0"] }`.
+//! - All variants can be deserialized from a body with a single attribute or block type, like
+//!   `rule { allow { cidrs = ["0.0.0.0/0"] } }`.
+//! - All variants can be deserialized from blocks with a single label, like
+//!   `rule "allow" { cidrs = ["0.0.0.0/0"] }`.
+//!
+//! The content of a unit variant has to be empty, like `null`, `{}` or a block without
+//! attributes or blocks.
+use std::borrow::Cow;
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, IgnoredAny, IntoDeserializer, Unexpected, Visitor};
+
+use crate::select::Segment;
+use crate::serde::de::body::ValueDeserializer;
+use crate::serde::de::Compat;
+
+/// Access to an enum whose variant is selected by `variant`
+#[derive(Debug)]
+pub struct EnumAccess<'de> {
+    variant: Cow<'de, str>,
+    value: ValueDeserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> {
+    pub fn new(variant: Cow<'de, str>, value: ValueDeserializer<'de>) -> Self {
+        Self { variant, value }
+    }
+}
+
+/// Access to the content of a variant
+#[derive(Debug)]
+pub struct VariantAccess<'de> {
+    variant: Cow<'de, str>,
+    value: ValueDeserializer<'de>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Compat;
+    type Variant = VariantAccess<'de>;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self::Variant), Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(IntoDeserializer::<Compat>::into_deserializer(
+            self.variant.clone(),
+        ))?;
+        let access = VariantAccess {
+            variant: self.variant,
+            value: self.value,
+        };
+        Ok((variant, access))
+    }
+}
+
+/// The content of a unit variant, which can be `null` or an empty object or body
+struct UnitContent;
+
+impl<'de> Visitor<'de> for UnitContent {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("unit")
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(())
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: de::MapAccess<'de>,
+    {
+        match map.next_key::<IgnoredAny>()? {
+            None => Ok(()),
+            Some(_) => Err(de::Error::invalid_type(Unexpected::Map, &self)),
+        }
+    }
+}
+
+/// Errors in the content of a variant are found under the variant
+fn at_variant(variant: Cow<str>, error: Compat) -> Compat {
+    error.at(Segment::Name(variant.into_owned()), || None)
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess<'de> {
+    type Error = Compat;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        let VariantAccess { variant, value } = self;
+        de::Deserializer::deserialize_any(value, UnitContent).map_err(|e| at_variant(variant, e))
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Self::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        let VariantAccess { variant, value } = self;
+        seed.deserialize(value).map_err(|e| at_variant(variant, e))
+    }
+
+    fn tuple_variant<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let VariantAccess { variant, value } = self;
+        de::Deserializer::deserialize_tuple(value, len, visitor).map_err(|e| at_variant(variant, e))
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        let VariantAccess { variant, value } = self;
+        de::Deserializer::deserialize_struct(value, "", fields, visitor)
+            .map_err(|e| at_variant(variant, e))
+    }
+}
@@ -0,0 +1,790 @@
+//! `Serialize` and `Deserialize` implementations for the document model
+//!
+//! The parsed document can be embedded into other serde formats or converted between them. It
+//! is represented as follows:
+//!
+//! | Type                                    | Representation                                          |
+//! |-----------------------------------------|---------------------------------------------------------|
+//! | [`Body`](crate::parser::Body)           | A sequence of attributes and blocks, in order           |
+//! | [`Attribute`](crate::parser::Attribute) | A map `{ "name": name, "expression": expression }`      |
+//! | [`Block`]                               | A map `{ "type": type, "labels": [labels], "body": body }` |
+//! | [`BlockLabel`]                          | A string                                                |
+//! | [`Expression`]                          | The value of literals and collections, see below        |
+//! | [`Value`]                               | The value, see below                                    |
+//!
+//! Expressions that are literals are represented by their values: `null` as a unit, numbers as
+//! integers if they fit in 64 bits or as floats otherwise, booleans, strings, tuples as
+//! sequences and objects as maps. Like in the HCL JSON syntax, other expressions are represented
+//! as template strings: `var.name` is represented as `"${var.name}"`, and a literal `${` in a
+//! string is escaped as `$${`. When deserializing, a string that is a single interpolation is
+//! deserialized as the interpolated expression.
+//!
+//! When deserializing an attribute or a block, the `labels` and `body` of a block can be
+//! omitted. Labels are deserialized as string literals.
+//!
+//! A [`Value`] is represented by its value like an expression, with objects as a map if there is
+//! a single map and as a sequence of maps otherwise. A block is represented as a sequence of maps
+//! `{ "labels": [labels], "body": body }`. Deserializing a `Value` cannot distinguish blocks from
+//! objects, so maps are deserialized as objects and sequences as lists.
+//!
+//! ```rust
+//! use ferrous_chloride::parser::{parse_str, Body};
+//!
+//! let body = parse_str("port = var.base + 1\nlistener \"http\" {\n  enabled = true\n}\n").unwrap();
+//! let json = serde_json::to_string(&body).unwrap();
+//! assert_eq!(
+//!     json,
+//!     r#"[{"name":"port","expression":"${var.base + 1}"},{"type":"listener","labels":["http"],"body":[{"name":"enabled","expression":true}]}]"#
+//! );
+//!
+//! let deserialized: Body = serde_json::from_str(&json).unwrap();
+//! assert_eq!(deserialized, body);
+//! ```
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+
+use nom::types::CompleteStr;
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, SerializeStruct, Serializer};
+
+use crate::parser::block::{Block, BlockLabel};
+use crate::parser::body::BodyElement;
+use crate::parser::expression::Expression;
+use crate::parser::identifier::Identifier;
+use crate::parser::literals::Key;
+use crate::parser::number::{number, Number};
+use crate::parser::object::ObjectElementIdentifier;
+use crate::parser::string::{template, TemplatePart};
+use crate::value::{MapValues, Value};
+use crate::{AsOwned, KeyValuePairs};
+
+/// Name of the newtype struct that deserializers recognise to provide the literals of numbers
+pub(crate) const NUMBER: &str = "$__ferrous_chloride_private_Number";
+
+const ATTRIBUTE: &str = "Attribute";
+const BLOCK: &str = "Block";
+const BLOCK_FIELDS: &[&str] = &["type", "labels", "body"];
+const BODY_ELEMENT_FIELDS: &[&str] = &["name", "expression", "type", "labels", "body"];
+
+impl<'a> Serialize for Number<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if let Ok(number) = self.as_u64() {
+            serializer.serialize_u64(number)
+        } else if let Ok(number) = self.as_i64() {
+            serializer.serialize_i64(number)
+        } else if let Ok(number) = self.as_u128() {
+            serializer.serialize_u128(number)
+        } else if let Ok(number) = self.as_i128() {
+            serializer.serialize_i128(number)
+        } else {
+            let number = self.as_f64().map_err(serde::ser::Error::custom)?;
+            serializer.serialize_f64(number)
+        }
+    }
+}
+
+/// Numbers are deserialized from their literals if the deserializer provides them, like the HCL
+/// deserializer does, or from strings with a number literal, so big numbers can be deserialized
+/// without losing precision
+impl<'de, 'a> Deserialize<'de> for Number<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct NumberVisitor<'a>(PhantomData<Number<'a>>);
+
+        impl<'de, 'a> Visitor<'de> for NumberVisitor<'a> {
+            type Value = Number<'a>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a number")
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+                Ok(Number::from(v))
+            }
+
+            fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E> {
+                Ok(Number::from(v))
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+                Ok(Number::from(v))
+            }
+
+            fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E> {
+                Ok(Number::from(v))
+            }
+
+            fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+                Ok(Number::from(v))
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                match number(CompleteStr(v)) {
+                    Ok((remaining, number)) if remaining.is_empty() => Ok(number.as_owned()),
+                    _ => Err(E::invalid_value(de::Unexpected::Str(v), &self)),
+                }
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: Deserializer<'de>,
+            {
+                deserializer.deserialize_any(self)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(NUMBER, NumberVisitor(PhantomData))
+    }
+}
+
+/// Write a string as the contents of a template, escaping `${`
+fn escape_template(string: &str) -> Cow<'_, str> {
+    if string.contains("${") {
+        Cow::Owned(string.replace("${", "$${"))
+    } else {
+        Cow::Borrowed(string)
+    }
+}
+
+/// The template string that represents an expression that is not a literal or a collection
+pub(crate) fn template_string(expression: &Expression) -> String {
+    match expression {
+        Expression::Template(template) => template
+            .iter()
+            .map(|part| match part {
+                TemplatePart::Literal(literal) => escape_template(literal).into_owned(),
+                TemplatePart::Interpolation(expr) => format!("${{{}}}", expr),
+            })
+            .collect(),
+        expr => format!("${{{}}}", expr),
+    }
+}
+
+impl<'a> Serialize for Expression<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Expression::Null => serializer.serialize_unit(),
+            Expression::Number(number) => number.serialize(serializer),
+            Expression::Boolean(boolean) => serializer.serialize_bool(*boolean),
+            Expression::String(string) => serializer.serialize_str(&escape_template(string)),
+            Expression::Tuple(tuple) => {
+                let mut seq = serializer.serialize_seq(Some(tuple.len()))?;
+                for expr in tuple {
+                    seq.serialize_element(expr)?;
+                }
+                seq.end()
+            }
+            Expression::Object(object) => {
+                let mut map = serializer.serialize_map(Some(object.len()))?;
+                for (key, expr) in object {
+                    let key: &str = std::borrow::Borrow::borrow(key);
+                    map.serialize_entry(key, expr)?;
+                }
+                map.end()
+            }
+            expr => serializer.serialize_str(&template_string(expr)),
+        }
+    }
+}
+
+/// Parse a template string into an expression
+fn parse_template<E: de::Error>(string: &str) -> Result<Expression<'static>, E> {
+    if !string.contains("${") {
+        return Ok(Expression::from(string.to_string()));
+    }
+
+    let (_, mut parts) = template(CompleteStr(string)).map_err(|e| {
+        E::custom(format!(
+            "invalid template {}: {}",
+            string,
+            crate::Error::from_err_str(&e)
+        ))
+    })?;
+    match parts.as_mut_slice() {
+        [TemplatePart::Interpolation(expr)] => {
+            Ok(std::mem::replace(expr, Expression::Null).as_owned())
+        }
+        _ => Ok(Expression::from_template(parts).as_owned()),
+    }
+}
+
+struct ExpressionVisitor<'a>(PhantomData<Expression<'a>>);
+
+impl<'de, 'a> Visitor<'de> for ExpressionVisitor<'a> {
+    type Value = Expression<'a>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a HCL expression")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Expression::from(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Expression::from(v))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<Self::Value, E> {
+        Ok(Expression::from(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(Expression::from(v))
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<Self::Value, E> {
+        Ok(Expression::from(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Expression::from(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        parse_template(v)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(Expression::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(Expression::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut tuple = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(expr) = seq.next_element()? {
+            tuple.push(expr);
+        }
+        Ok(Expression::Tuple(tuple))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut object = Vec::with_capacity(map.size_hint().unwrap_or_default());
+        while let Some((key, expr)) = map.next_entry::<String, _>()? {
+            object.push((ObjectElementIdentifier::Identifier(Cow::Owned(key)), expr));
+        }
+        Ok(Expression::Object(object))
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for Expression<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ExpressionVisitor(PhantomData))
+    }
+}
+
+impl<'a> Serialize for BlockLabel<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for BlockLabel<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(|s| BlockLabel::StringLiteral(Cow::Owned(s)))
+    }
+}
+
+impl<'a> Serialize for Block<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut block = serializer.serialize_struct(BLOCK, 3)?;
+        block.serialize_field("type", &self.r#type)?;
+        block.serialize_field("labels", &self.labels)?;
+        block.serialize_field("body", &self.body)?;
+        block.end()
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for Block<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match deserializer.deserialize_struct(BLOCK, BLOCK_FIELDS, BodyElementVisitor::BLOCK)? {
+            BodyElement::Block(block) => Ok(block),
+            BodyElement::Attribute(_) => unreachable!("only blocks are deserialized"),
+        }
+    }
+}
+
+impl<'a> Serialize for BodyElement<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            BodyElement::Attribute((name, expression)) => {
+                let mut attribute = serializer.serialize_struct(ATTRIBUTE, 2)?;
+                attribute.serialize_field("name", name)?;
+                attribute.serialize_field("expression", expression)?;
+                attribute.end()
+            }
+            BodyElement::Block(block) => block.serialize(serializer),
+        }
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for BodyElement<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(BodyElementVisitor::ANY)
+    }
+}
+
+/// Visitor for attributes and blocks, which are told apart by their fields
+struct BodyElementVisitor<'a> {
+    blocks_only: bool,
+    marker: PhantomData<BodyElement<'a>>,
+}
+
+impl<'a> BodyElementVisitor<'a> {
+    const ANY: Self = Self {
+        blocks_only: false,
+        marker: PhantomData,
+    };
+    const BLOCK: Self = Self {
+        blocks_only: true,
+        marker: PhantomData,
+    };
+}
+
+impl<'de, 'a> Visitor<'de> for BodyElementVisitor<'a> {
+    type Value = BodyElement<'a>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        if self.blocks_only {
+            formatter.write_str("a block")
+        } else {
+            formatter.write_str("an attribute or a block")
+        }
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        fn set<T, E: de::Error>(
+            field: &mut Option<T>,
+            name: &'static str,
+            value: T,
+        ) -> Result<(), E> {
+            match field.replace(value) {
+                Some(_) => Err(E::duplicate_field(name)),
+                None => Ok(()),
+            }
+        }
+
+        let mut name: Option<Identifier> = None;
+        let mut expression: Option<Expression> = None;
+        let mut r#type: Option<Identifier> = None;
+        let mut labels: Option<Vec<BlockLabel>> = None;
+        let mut body = None;
+
+        while let Some(key) = map.next_key::<Cow<str>>()? {
+            match &*key {
+                "name" => set(&mut name, "name", Cow::Owned(map.next_value()?))?,
+                "expression" => set(&mut expression, "expression", map.next_value()?)?,
+                "type" => set(&mut r#type, "type", Cow::Owned(map.next_value()?))?,
+                "labels" => set(&mut labels, "labels", map.next_value()?)?,
+                "body" => set(&mut body, "body", map.next_value()?)?,
+                other => return Err(de::Error::unknown_field(other, BODY_ELEMENT_FIELDS)),
+            }
+        }
+
+        match (name, expression, r#type) {
+            (None, None, Some(r#type)) => Ok(BodyElement::Block(Block::new(
+                r#type,
+                labels.unwrap_or_default(),
+                body.unwrap_or_default(),
+            ))),
+            (None, None, None) => Err(de::Error::missing_field("type")),
+            _ if self.blocks_only => Err(de::Error::custom("expected a block, got an attribute")),
+            (Some(name), Some(expression), None) if labels.is_none() && body.is_none() => {
+                Ok(BodyElement::Attribute((name, expression)))
+            }
+            (None, Some(_), _) => Err(de::Error::missing_field("name")),
+            (Some(_), None, _) => Err(de::Error::missing_field("expression")),
+            _ => Err(de::Error::custom(
+                "an element cannot have the fields of both an attribute and a block",
+            )),
+        }
+    }
+}
+
+impl<'a> Serialize for Key<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self)
+    }
+}
+
+impl<K, V> Serialize for KeyValuePairs<K, V>
+where
+    K: Serialize + std::hash::Hash + Eq,
+    V: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (key, value) in self.iter() {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A block of a [`Value`], with its labels and body
+struct LabelledBody<'s, 'a> {
+    labels: &'s [String],
+    body: &'s MapValues<'a>,
+}
+
+impl<'s, 'a> Serialize for LabelledBody<'s, 'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut block = serializer.serialize_struct(BLOCK, 2)?;
+        block.serialize_field("labels", self.labels)?;
+        block.serialize_field("body", self.body)?;
+        block.end()
+    }
+}
+
+impl<'a> Serialize for Value<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Integer(integer) => serializer.serialize_i64(*integer),
+            Value::Float(float) => serializer.serialize_f64(*float),
+            Value::Boolean(boolean) => serializer.serialize_bool(*boolean),
+            Value::String(string) => serializer.serialize_str(string),
+            Value::List(list) => list.serialize(serializer),
+            Value::Object(maps) if maps.len() == 1 => maps[0].serialize(serializer),
+            Value::Object(maps) => maps.serialize(serializer),
+            Value::Block(blocks) => {
+                let mut seq = serializer.serialize_seq(Some(blocks.len()))?;
+                for (labels, body) in blocks.iter() {
+                    seq.serialize_element(&LabelledBody { labels, body })?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+struct ValueVisitor<'a>(PhantomData<Value<'a>>);
+
+impl<'de, 'a> Visitor<'de> for ValueVisitor<'a> {
+    type Value = Value<'a>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a HCL value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Self::Value, E> {
+        Ok(Value::Boolean(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Value::Integer(v))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E> {
+        if v > i64::MAX as u64 {
+            Ok(Value::Float(v as f64))
+        } else {
+            Ok(Value::Integer(v as i64))
+        }
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Self::Value, E> {
+        Ok(Value::Float(v))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Self::Value, E> {
+        Ok(Value::String(v))
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut list = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(value) = seq.next_element()? {
+            list.push(value);
+        }
+        Ok(Value::List(list))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut pairs = Vec::with_capacity(map.size_hint().unwrap_or_default());
+        while let Some(key) = map.next_key::<String>()? {
+            let value = map.next_value_seed(ValueSeed(PhantomData))?;
+            pairs.push((Key::from(key), value));
+        }
+        Ok(Value::Object(vec![KeyValuePairs::Unmerged(pairs)]))
+    }
+}
+
+/// Seed to deserialize values with any lifetime
+struct ValueSeed<'a>(PhantomData<Value<'a>>);
+
+impl<'de, 'a> DeserializeSeed<'de> for ValueSeed<'a> {
+    type Value = Value<'a>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor(PhantomData))
+    }
+}
+
+impl<'de, 'a> Deserialize<'de> for Value<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        ValueSeed(PhantomData).deserialize(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::fixtures;
+    use crate::parser::body::Body;
+    use crate::parser::parse_str;
+
+    #[test]
+    fn expressions_are_serialized_as_values() {
+        let test_cases = [
+            ("null", "null"),
+            ("123", "123"),
+            ("-123", "-123"),
+            ("1.5", "1.5"),
+            ("1e3", "1000"),
+            (
+                "340282366920938463463374607431768211455",
+                "340282366920938463463374607431768211455",
+            ),
+            ("1e40", "1e40"),
+            ("true", "true"),
+            (r#""cost: $5""#, r#""cost: $5""#),
+            (r#""foo$${bar}""#, r#""foo$${bar}""#),
+            (r#""foo${bar}""#, r#""foo${bar}""#),
+            (r#"[1, "a"]"#, r#"[1,"a"]"#),
+            (r#"{ a = 1, b = [] }"#, r#"{"a":1,"b":[]}"#),
+            ("var.list[0] + 1", r#""${var.list[0] + 1}""#),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            println!("Testing {}", input);
+            let expression = Expression::parse(input).unwrap();
+            assert_eq!(serde_json::to_string(&expression).unwrap(), *expected);
+        }
+    }
+
+    #[test]
+    fn expressions_are_deserialized_from_values() {
+        let test_cases = [
+            ("null", Expression::Null),
+            ("-123", Expression::from(-123)),
+            ("1.5", Expression::from(1.5)),
+            (r#""foo$${bar}""#, Expression::from("foo${bar}")),
+            (r#""${var.name}""#, Expression::parse("var.name").unwrap()),
+            (
+                r#""a-${var.name}""#,
+                Expression::parse(r#""a-${var.name}""#).unwrap(),
+            ),
+            (
+                r#"[1, {"a": null}]"#,
+                Expression::parse("[1, { a = null }]").unwrap(),
+            ),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            println!("Testing {}", input);
+            let expression: Expression = serde_json::from_str(input).unwrap();
+            assert_eq!(expression, *expected);
+        }
+
+        let error = serde_json::from_str::<Expression>(r#""${""#).unwrap_err();
+        assert!(error.to_string().starts_with("invalid template ${"));
+    }
+
+    #[test]
+    fn bodies_are_round_tripped() {
+        for hcl in fixtures::ALL {
+            let body = parse_str(hcl).unwrap();
+            let json = serde_json::to_string(&body).unwrap();
+            let deserialized: Body = serde_json::from_str(&json).unwrap();
+            // Labels are deserialized as string literals
+            assert_eq!(
+                crate::format::body(&deserialized),
+                crate::format::body(&body)
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_body_elements_are_errors() {
+        let test_cases = [
+            (r#"{"name": "a"}"#, "missing field `expression`"),
+            (r#"{"expression": 1}"#, "missing field `name`"),
+            (r#"{}"#, "missing field `type`"),
+            (
+                r#"{"name": "a", "expression": 1, "body": []}"#,
+                "an element cannot have the fields of both an attribute and a block",
+            ),
+            (r#"{"type": "a", "type": "b"}"#, "duplicate field `type`"),
+            (r#"{"other": 1}"#, "unknown field `other`"),
+        ];
+
+        for (input, expected) in test_cases.iter() {
+            println!("Testing {}", input);
+            let error = serde_json::from_str::<BodyElement>(input).unwrap_err();
+            assert!(error.to_string().starts_with(expected), "{}", error);
+        }
+
+        let error = serde_json::from_str::<Block>(r#"{"name": "a", "expression": 1}"#);
+        assert!(error.is_err());
+    }
+
+    #[test]
+    fn values_are_serialized_and_deserialized() {
+        let value = Value::new_list(vec![
+            Value::Integer(1),
+            Value::Float(1.5),
+            Value::Null,
+            Value::new_map(vec![vec![(Key::from("a"), Value::from("b"))]]),
+            Value::Block(KeyValuePairs::Unmerged(vec![(
+                vec!["label".to_string()],
+                KeyValuePairs::Unmerged(vec![(Key::from("c"), Value::Boolean(true))]),
+            )])),
+        ]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(
+            json,
+            r#"[1,1.5,null,{"a":"b"},[{"labels":["label"],"body":{"c":true}}]]"#
+        );
+
+        let deserialized: Value = serde_json::from_str(r#"[1,1.5,null,{"a":"b"}]"#).unwrap();
+        assert_eq!(
+            deserialized,
+            Value::new_list(vec![
+                Value::Integer(1),
+                Value::Float(1.5),
+                Value::Null,
+                Value::Object(vec![KeyValuePairs::Unmerged(vec![(
+                    Key::from("a"),
+                    Value::from("b")
+                )])]),
+            ])
+        );
+    }
+
+    #[test]
+    fn numbers_are_deserialized_without_losing_precision() {
+        #[derive(serde::Deserialize)]
+        struct Numbers {
+            small: Number<'static>,
+            big: Number<'static>,
+        }
+
+        let numbers: Numbers = crate::from_str(
+            "small = 0.1000000000000000000001
+big = 1e400
+",
+        )
+        .unwrap();
+        assert_eq!(&*numbers.small, "0.1000000000000000000001");
+        assert_eq!(&*numbers.big, "1e400");
+
+        let number: Number = serde_json::from_str(r#""-12.5e-100""#).unwrap();
+        assert_eq!(&*number, "-12.5e-100");
+        let number: Number = serde_json::from_str("12").unwrap();
+        assert_eq!(number, Number::from(12));
+
+        let error = serde_json::from_str::<Number>(r#""12 apples""#).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("invalid value: string \"12 apples\""));
+    }
+}
@@ -0,0 +1,139 @@
+//! Expressions that are deserialized without being evaluated
+//!
+//! Fields of type [`RawExpression`] capture the expression of an attribute as it was written, to
+//! be evaluated later:
+//!
+//! ```rust
+//! use ferrous_chloride::eval::Context;
+//! use ferrous_chloride::parser::Expression;
+//! use ferrous_chloride::serde::RawExpression;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     name: String,
+//!     replicas: RawExpression,
+//! }
+//!
+//! let config: Config =
+//!     ferrous_chloride::from_str("name = \"server\"\nreplicas = var.count * 2 # deferred\n")
+//!         .unwrap();
+//! assert_eq!(config.replicas.source(), "var.count * 2");
+//!
+//! let context = Context::new()
+//!     .variable("var", Expression::new_object(vec![("count", Expression::from(3))]));
+//! assert_eq!(config.replicas.evaluate(&context).unwrap(), Expression::from(6));
+//! ```
+//!
+//! The source text is the text of the expression in the source, if the source is known, or the
+//! formatted expression otherwise. Expressions are not evaluated even when deserializing with an
+//! [evaluation context](crate::eval::Context), unless they are part of a tuple or an object whose
+//! elements are being evaluated.
+//!
+//! Other deserializers can deserialize a `RawExpression` from a string with the source of the
+//! expression.
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{self, Deserialize, Visitor};
+
+use crate::eval::Context;
+use crate::parser::descent;
+use crate::parser::expression::Expression;
+use crate::{AsOwned, Error};
+
+/// Name of the newtype struct that deserializers recognise to provide the source of expressions
+pub(crate) const NAME: &str = "$__ferrous_chloride_private_RawExpression";
+
+/// An unevaluated expression along with its source text
+#[derive(Clone, Debug, PartialEq)]
+pub struct RawExpression {
+    source: String,
+    expression: Expression<'static>,
+}
+
+impl RawExpression {
+    /// Parse the source text of an expression
+    ///
+    /// The source is parsed with the default [`ParseOptions`](crate::parser::ParseOptions).
+    pub fn new<S: Into<String>>(source: S) -> Result<Self, Error> {
+        let source = source.into();
+        // Heredocs have to be terminated by a newline
+        let input = format!("{}\n", source.trim());
+        let expression = descent::parse_expression(&input, &Default::default())?.as_owned();
+        Ok(Self { source, expression })
+    }
+
+    /// The source text of the expression
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The parsed expression
+    pub fn expression(&self) -> &Expression<'static> {
+        &self.expression
+    }
+
+    pub fn into_expression(self) -> Expression<'static> {
+        self.expression
+    }
+
+    /// Evaluate the expression with `context`
+    pub fn evaluate(&self, context: &Context) -> Result<Expression<'static>, Error> {
+        context.evaluate(&self.expression)
+    }
+}
+
+impl FromStr for RawExpression {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl fmt::Display for RawExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for RawExpression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct RawExpressionVisitor;
+
+        impl<'de> Visitor<'de> for RawExpressionVisitor {
+            type Value = RawExpression;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("the source of an expression")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                RawExpression::new(v).map_err(de::Error::custom)
+            }
+
+            fn visit_string<E>(self, v: String) -> Result<Self::Value, E>
+            where
+                E: de::Error,
+            {
+                RawExpression::new(v).map_err(de::Error::custom)
+            }
+
+            fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+            where
+                D: de::Deserializer<'de>,
+            {
+                deserializer.deserialize_str(self)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(NAME, RawExpressionVisitor)
+    }
+}
@@ -0,0 +1,228 @@
+//! Values with their location in the source
+//!
+//! Wrap the type of a field in [`Spanned`] to find out where the attribute or block it was
+//! deserialized from is in the source:
+//!
+//! ```rust
+//! use ferrous_chloride::serde::Spanned;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     port: Spanned<u16>,
+//! }
+//!
+//! let source = "name = \"server\"\nport = 8080\n";
+//! let config: Config = ferrous_chloride::from_str(source).unwrap();
+//! assert_eq!(*config.port.get_ref(), 8080);
+//! assert_eq!(config.port.span().to_string(), "2:1-2:12");
+//! assert_eq!(config.port.span().slice(source), Some("port = 8080"));
+//! ```
+//!
+//! Spans are only known for attributes and blocks of a body that was deserialized along with
+//! its source, like with [`from_str`](crate::serde::from_str). The span of a block field covers
+//! all the blocks of the same type, from the first one to the last one.
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, Visitor};
+
+use crate::span::{Position, Span};
+
+/// Name of the struct that deserializers recognise to provide spans
+pub(crate) const NAME: &str = "$__ferrous_chloride_private_Spanned";
+pub(crate) const SPAN_FIELD: &str = "$__ferrous_chloride_private_span";
+pub(crate) const VALUE_FIELD: &str = "$__ferrous_chloride_private_value";
+pub(crate) const FIELDS: &[&str] = &[SPAN_FIELD, VALUE_FIELD];
+
+/// A deserialized value along with the span of the source it came from
+///
+/// Comparisons and hashing only take the value into account.
+#[derive(Clone, Debug)]
+pub struct Spanned<T> {
+    span: Span,
+    value: T,
+}
+
+impl<T> Spanned<T> {
+    /// The span of the attribute or block the value was deserialized from
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Position of the start of the span
+    pub fn start(&self) -> Position {
+        self.span.start
+    }
+
+    /// Position of the end of the span
+    pub fn end(&self) -> Position {
+        self.span.end
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl Borrow<str> for Spanned<String> {
+    fn borrow(&self) -> &str {
+        &self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Eq> Eq for Spanned<T> {}
+
+impl<T: Hash> Hash for Spanned<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.value.hash(state)
+    }
+}
+
+impl<T: PartialOrd> PartialOrd for Spanned<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<T: Ord> Ord for Spanned<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for Spanned<T>
+where
+    T: Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct SpannedVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> Visitor<'de> for SpannedVisitor<T>
+        where
+            T: Deserialize<'de>,
+        {
+            type Value = Spanned<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an attribute or block with its span")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                if map.next_key::<&str>()? != Some(SPAN_FIELD) {
+                    return Err(de::Error::missing_field(SPAN_FIELD));
+                }
+                let [start, start_line, start_column, end, end_line, end_column]: [usize; 6] =
+                    map.next_value()?;
+
+                if map.next_key::<&str>()? != Some(VALUE_FIELD) {
+                    return Err(de::Error::missing_field(VALUE_FIELD));
+                }
+                let value = map.next_value()?;
+
+                let span = Span {
+                    start: Position {
+                        offset: start,
+                        line: start_line,
+                        column: start_column,
+                    },
+                    end: Position {
+                        offset: end,
+                        line: end_line,
+                        column: end_column,
+                    },
+                };
+                Ok(Spanned { span, value })
+            }
+        }
+
+        deserializer.deserialize_struct(NAME, FIELDS, SpannedVisitor(PhantomData))
+    }
+}
+
+/// Map with the span and the value of a `Spanned`, in that order
+#[derive(Debug)]
+pub(crate) struct SpannedMapAccess<D> {
+    span: Option<Span>,
+    value: Option<D>,
+}
+
+impl<D> SpannedMapAccess<D> {
+    pub(crate) fn new(span: Span, value: D) -> Self {
+        Self {
+            span: Some(span),
+            value: Some(value),
+        }
+    }
+}
+
+impl<'de, D> de::MapAccess<'de> for SpannedMapAccess<D>
+where
+    D: de::Deserializer<'de>,
+{
+    type Error = D::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let key = if self.span.is_some() {
+            SPAN_FIELD
+        } else if self.value.is_some() {
+            VALUE_FIELD
+        } else {
+            return Ok(None);
+        };
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.span.take() {
+            Some(Span { start, end }) => {
+                let span = vec![
+                    start.offset,
+                    start.line,
+                    start.column,
+                    end.offset,
+                    end.line,
+                    end.column,
+                ];
+                seed.deserialize(span.into_deserializer())
+            }
+            None => match self.value.take() {
+                Some(value) => seed.deserialize(value),
+                None => Err(de::Error::custom(
+                    "a map value was requested before its key",
+                )),
+            },
+        }
+    }
+}
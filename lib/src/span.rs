@@ -0,0 +1,252 @@
+//! Locations in HCL source
+//!
+//! The parser borrows identifiers such as attribute names and block types from the input where
+//! possible. The location of a borrowed string can be recovered from its position in the
+//! source that was parsed.
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+use crate::parser::body::BodyElement;
+use crate::parser::descent;
+
+/// A position in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// Byte offset from the start of the source
+    pub offset: usize,
+    /// Line number, starting from 1
+    pub line: usize,
+    /// Column in characters, starting from 1
+    pub column: usize,
+}
+
+/// A range in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+    /// Position of the first character
+    pub start: Position,
+    /// Position after the last character
+    pub end: Position,
+}
+
+impl Position {
+    /// Compute the position of the byte `offset` in `source`
+    ///
+    /// Returns `None` if `offset` is out of bounds or not at a character boundary.
+    pub fn from_offset(source: &str, offset: usize) -> Option<Self> {
+        let before = source.get(..offset)?;
+        let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+
+        Some(Self {
+            offset,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        })
+    }
+}
+
+impl Span {
+    /// Compute the span of the byte range `start..end` in `source`
+    pub fn from_offsets(source: &str, start: usize, end: usize) -> Option<Self> {
+        if start > end {
+            return None;
+        }
+        Some(Self {
+            start: Position::from_offset(source, start)?,
+            end: Position::from_offset(source, end)?,
+        })
+    }
+
+    /// Compute the span of `slice` in `source`
+    ///
+    /// Returns `None` if `slice` is not a part of `source`, for example if it is a copy.
+    pub fn of(source: &str, slice: &str) -> Option<Self> {
+        let start = offset_of(source, slice)?;
+        Self::from_offsets(source, start, start + slice.len())
+    }
+
+    /// Compute the span of the attribute named by `name`, a slice of `source`
+    ///
+    /// The span starts at the name and ends after the expression of the attribute.
+    pub fn of_attribute(source: &str, name: &str) -> Option<Self> {
+        match Self::of_element(source, name)? {
+            (BodyElement::Attribute(_), span) => Some(span),
+            (BodyElement::Block(_), _) => None,
+        }
+    }
+
+    /// Compute the span of the block whose type is `block_type`, a slice of `source`
+    ///
+    /// The span starts at the type and ends after the closing brace of the block.
+    pub fn of_block(source: &str, block_type: &str) -> Option<Self> {
+        match Self::of_element(source, block_type)? {
+            (BodyElement::Block(_), span) => Some(span),
+            (BodyElement::Attribute(_), _) => None,
+        }
+    }
+
+    /// Parse the element named by `name` again to find its end
+    fn of_element<'a>(source: &'a str, name: &str) -> Option<(BodyElement<'a>, Self)> {
+        let start = offset_of(source, name)?;
+        let (element, end) = descent::parse_body_element(source, start).ok()?;
+        Some((element, Self::from_offsets(source, start, end)?))
+    }
+
+    /// Returns the spanned part of `source`
+    pub fn slice<'a>(&self, source: &'a str) -> Option<&'a str> {
+        source.get(self.start.offset..self.end.offset)
+    }
+}
+
+/// Byte offset of `slice` in `source`, if it is a part of `source`
+fn offset_of(source: &str, slice: &str) -> Option<usize> {
+    let source_start = source.as_ptr() as usize;
+    let slice_start = slice.as_ptr() as usize;
+
+    if slice_start < source_start || slice_start + slice.len() > source_start + source.len() {
+        return None;
+    }
+    Some(slice_start - source_start)
+}
+
+/// Byte ranges of the attributes and blocks of a document, which are recorded while parsing it
+///
+/// The ranges are looked up by the names of the elements, which are borrowed from the source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Extents {
+    /// End offsets by start offsets
+    ends: HashMap<usize, usize>,
+}
+
+impl Extents {
+    pub(crate) fn insert(&mut self, range: Range<usize>) {
+        self.ends.insert(range.start, range.end);
+    }
+
+    /// The span of the element named by `name`, a slice of `source`
+    pub(crate) fn span(&self, source: &str, name: &str) -> Option<Span> {
+        let start = offset_of(source, name)?;
+        let end = *self.ends.get(&start)?;
+        Span::from_offsets(source, start, end)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::parser::body::BodyElement;
+
+    #[test]
+    fn positions_are_computed_correctly() {
+        let source = "a = 1\nb = \"é\"\nc";
+        let test_cases = [
+            (0, Some((1, 1))),
+            (4, Some((1, 5))),
+            (6, Some((2, 1))),
+            // After the multi-byte character
+            (13, Some((2, 7))),
+            (15, Some((3, 1))),
+            (16, Some((3, 2))),
+            // Inside the multi-byte character, and out of bounds
+            (12, None),
+            (17, None),
+        ];
+
+        for (offset, expected) in test_cases.iter() {
+            let position = Position::from_offset(source, *offset);
+            assert_eq!(
+                position.map(|position| (position.line, position.column)),
+                *expected,
+                "offset {}",
+                offset
+            );
+        }
+    }
+
+    #[test]
+    fn spans_of_slices_are_computed_correctly() {
+        let source = "a = 1\nfoo = 2\n";
+        let foo = &source[6..9];
+
+        let span = Span::of(source, foo).unwrap();
+        assert_eq!(span.to_string(), "2:1-2:4");
+        assert_eq!(span.slice(source), Some("foo"));
+
+        let copy = "foo".to_string();
+        assert_eq!(Span::of(source, &copy), None);
+    }
+
+    #[test]
+    fn spans_of_attributes_and_blocks_are_computed_correctly() {
+        let source = r#"
+a = [1, 2] /* comment */ # comment
+b "label" { c = 1 }
+d {
+  e = "}"
+}  /* comment */
+"#;
+        let body = crate::parser::parse_str(source).unwrap();
+        let names: Vec<&str> = body
+            .iter()
+            .map(|element| match element {
+                BodyElement::Attribute((name, _)) => name.as_ref(),
+                BodyElement::Block(block) => block.r#type.as_ref(),
+            })
+            .collect();
+
+        let attribute = Span::of_attribute(source, names[0]).unwrap();
+        assert_eq!(attribute.slice(source), Some("a = [1, 2]"));
+        assert_eq!(attribute.to_string(), "2:1-2:11");
+
+        let one_line = Span::of_block(source, names[1]).unwrap();
+        assert_eq!(one_line.slice(source), Some(r#"b "label" { c = 1 }"#));
+
+        let block = Span::of_block(source, names[2]).unwrap();
+        assert_eq!(block.slice(source), Some("d {\n  e = \"}\"\n}"));
+        assert_eq!(block.to_string(), "4:1-6:2");
+
+        assert_eq!(Span::of_block(source, names[0]), None);
+        assert_eq!(Span::of_attribute(source, names[1]), None);
+    }
+
+    #[test]
+    fn spans_of_expressions_across_lines_are_computed_correctly() {
+        let source = "a = (b ?\n  c : d)\n";
+        let span = Span::of_attribute(source, &source[..1]).unwrap();
+        assert_eq!(span.slice(source), Some("a = (b ?\n  c : d)"));
+    }
+
+    #[test]
+    fn extents_are_recorded_while_parsing() {
+        let source = "a = 1 # comment\nb \"c\" { d = [\n  2,\n] }\ne {\n  f = 3\n}\n";
+        let (_, extents) = descent::parse_str_with_extents(source, &Default::default()).unwrap();
+
+        let test_cases = [
+            (0, "a = 1"),
+            (16, "b \"c\" { d = [\n  2,\n] }"),
+            (24, "d = [\n  2,\n]"),
+            (39, "e {\n  f = 3\n}"),
+            (45, "f = 3"),
+        ];
+        for (start, expected) in test_cases.iter() {
+            let name = &source[*start..*start + 1];
+            let span = extents.span(source, name).unwrap();
+            assert_eq!(span.slice(source), Some(*expected));
+        }
+        assert_eq!(extents.span(source, &source[2..3]), None);
+    }
+}
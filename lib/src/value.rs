@@ -1,13 +1,14 @@
-use std::collections::HashMap;
 use std::iter::FromIterator;
 use std::string::ToString;
 
+use indexmap::IndexMap;
 use nom::types::CompleteStr;
 
 use crate::constants::*;
 use crate::parser::literals::Key;
+use crate::parser::options::DEFAULT_MAX_DEPTH;
 use crate::MergeBehaviour;
-use crate::{AsOwned, Error, KeyValuePairs, ScalarLength};
+use crate::{AsOwned, Document, Error, KeyValuePairs, ScalarLength};
 
 #[derive(Debug, PartialEq, Clone)]
 /// Value in HCL
@@ -209,6 +210,7 @@ impl<'a> Value<'a> {
         self.borrow_string_mut().unwrap()
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn string(self) -> Result<String, (Error, Self)> {
         if let Value::String(v) = self {
             Ok(v)
@@ -266,6 +268,7 @@ impl<'a> Value<'a> {
         self.borrow_list_mut().unwrap()
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn list(self) -> Result<List<'a>, (Error, Self)> {
         if let Value::List(v) = self {
             Ok(v)
@@ -323,6 +326,7 @@ impl<'a> Value<'a> {
         self.borrow_map_mut().unwrap()
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn map(self) -> Result<Object<'a>, (Error, Self)> {
         if let Value::Object(v) = self {
             Ok(v)
@@ -380,6 +384,7 @@ impl<'a> Value<'a> {
         self.borrow_block_mut().unwrap()
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn block(self) -> Result<Block<'a>, (Error, Self)> {
         if let Value::Block(v) = self {
             Ok(v)
@@ -617,7 +622,7 @@ impl<'a> Block<'a> {
         K: IntoIterator<Item = S>,
         S: ToString,
     {
-        let mut merged = HashMap::default();
+        let mut merged = IndexMap::default();
         for (key, value) in iter {
             let _ = merged.insert(
                 key.into_iter().map(|s| s.to_string()).collect(),
@@ -711,9 +716,10 @@ impl<'a> Block<'a> {
     ///
     /// # Motivation
     /// A Block is implemented as [`KeyValuePairs`] with `Vec<String>` as keys.
-    /// Behind the scenes, a merged [`KeyValuePairs`] is backed by a [`HashMap`].
+    /// Behind the scenes, a merged [`KeyValuePairs`] is backed by an [`IndexMap`], which looks up
+    /// keys like a [`HashMap`](std::collections::HashMap).
     ///
-    /// Retrieving a key from a [`HashMap`] involves using the [`HashMap::get`] method
+    /// Retrieving a key from a `HashMap` involves using the `HashMap::get` method
     /// which specifies that to lookup a key of type `K`, you may use any type `Q` that
     /// implements [`std::borrow::Borrow`]`<K>`.
     ///
@@ -783,9 +789,9 @@ impl<'a> MapValues<'a> {
     where
         T: IntoIterator<Item = (Key<'a>, Value<'a>)>,
     {
-        use std::collections::hash_map::Entry;
+        use indexmap::map::Entry;
 
-        let mut map = HashMap::default();
+        let mut map = IndexMap::default();
         for (key, value) in iter {
             let mut value = value.merge()?;
             match map.entry(key) {
@@ -893,24 +899,21 @@ impl<'a> FromIterator<(Key<'a>, Value<'a>)> for MapValues<'a> {
 ///
 /// You can opt to merge the parsed body after parsing. The behaviour of merging is determined by
 /// the [`MergeBehaviour`] enum.
+///
+/// Lists and maps may be nested up to [`DEFAULT_MAX_DEPTH`] levels deep.
 pub fn from_str(input: &str, merge: Option<MergeBehaviour>) -> Result<Body, Error> {
+    crate::parser::descent::check_depth(input, DEFAULT_MAX_DEPTH)?;
     let (remaining_input, unmerged) =
         crate::parser::body(CompleteStr(input)).map_err(|e| Error::from_err_str(&e))?;
 
     if !remaining_input.is_empty() {
-        return Err(Error::Bug(format!(
-            r#"Input was not completely parsed:
-Input: {},
-Remaining: {}
-"#,
-            input, remaining_input
-        )));
+        return Err(Error::UnexpectedRemainingInput(remaining_input.to_string()));
     }
 
     let pairs = match merge {
         None => unmerged,
         Some(MergeBehaviour::Error) => unmerged.merge()?,
-        Some(_) => unimplemented!("Not implemented yet"),
+        Some(merge) => return Err(Error::UnsupportedMergeBehaviour(merge)),
     };
 
     Ok(pairs)
@@ -918,21 +921,30 @@ Remaining: {}
 
 /// Parse a HCL string from a IO stream reader
 ///
-/// The entire IO stream has to be buffered in memory first before parsing can occur.
+/// The entire IO stream has to be buffered in memory first before parsing can occur. The parsed
+/// body is copied out of the buffer, see [`read_document`] to keep the buffer instead.
 ///
 /// When reading from a source against which short reads are not efficient, such as a
 /// [`File`](std::fs::File), you will want to apply your own buffering because the library
 /// will not buffer the input. See [`std::io::BufReader`].
 pub fn from_reader<R: std::io::Read>(
-    mut reader: R,
+    reader: R,
     merge: Option<MergeBehaviour>,
 ) -> Result<Body<'static>, Error> {
-    let mut buffer = String::new();
-    reader.read_to_string(&mut buffer)?;
+    let document = read_document(reader, merge)?;
+    Ok(document.body().as_owned())
+}
 
-    // FIXME: Can we do better? We are allocating twice. Once for reading into a buffer
-    // and second time calling `as_owned`.
-    Ok(from_str(&buffer, merge)?.as_owned())
+/// Parse a HCL string from a IO stream reader into a [`Document`]
+///
+/// The entire IO stream has to be buffered in memory first before parsing can occur. The
+/// returned [`Document`] keeps the buffer, which the parsed body borrows from, so that nothing
+/// has to be copied out of it.
+pub fn read_document<R: std::io::Read>(
+    reader: R,
+    merge: Option<MergeBehaviour>,
+) -> Result<Document<Body<'static>>, Error> {
+    Document::read(reader, |input| from_str(input, merge))
 }
 
 /// Parse a HCL string from a slice of bytes
@@ -962,4 +974,57 @@ mod tests {
             assert!(parsed.is_merged());
         }
     }
+
+    #[test]
+    fn merging_keeps_document_order() {
+        let hcl = r#"
+zone = "a"
+region = "b"
+resource "b" {}
+alpha = "c"
+resource "a" {}
+"#;
+        let parsed = from_str(hcl, Some(MergeBehaviour::Error)).unwrap();
+        let keys: Vec<_> = parsed.keys().map(|key| &**key).collect();
+        assert_eq!(keys, ["zone", "region", "resource", "alpha"]);
+
+        let resource = parsed.get("resource").unwrap().unwrap_one();
+        let labels: Vec<_> = resource.unwrap_borrow_block().keys().cloned().collect();
+        assert_eq!(labels, [vec!["b".to_string()], vec!["a".to_string()]]);
+    }
+
+    #[test]
+    fn unsupported_merge_behaviours_are_errors() {
+        for merge in &[MergeBehaviour::TakeFirst, MergeBehaviour::TakeLast] {
+            match from_str(fixtures::SINGLE, Some(*merge)) {
+                Err(Error::UnsupportedMergeBehaviour(actual)) => assert_eq!(actual, *merge),
+                result => panic!("unexpected {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn merged_and_unmerged_pairs_are_unwrapped_fallibly() {
+        let hcl = "a = 1\nb = 2\n";
+        let unmerged = from_str(hcl, None).unwrap();
+        let merged = from_str(hcl, Some(MergeBehaviour::Error)).unwrap();
+
+        assert!(merged.get("a").unwrap().one().is_ok());
+        assert!(unmerged.get("a").unwrap().many().is_ok());
+        match unmerged.get("a").unwrap().one() {
+            Err(Error::UnexpectedVariant { actual, .. }) => assert_eq!(actual, MANY),
+            result => panic!("unexpected {:?}", result),
+        }
+
+        assert_eq!(merged.clone().merged().unwrap().len(), 2);
+        assert_eq!(unmerged.clone().unmerged().unwrap().len(), 2);
+        match merged.unmerged() {
+            Err(Error::UnexpectedVariant { actual, .. }) => assert_eq!(actual, MERGED),
+            result => panic!("unexpected {:?}", result),
+        }
+        match unmerged.merged() {
+            Err(Error::UnexpectedVariant { actual, .. }) => assert_eq!(actual, UNMERGED),
+            result => panic!("unexpected {:?}", result),
+        }
+    }
 }